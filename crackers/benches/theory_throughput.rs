@@ -0,0 +1,84 @@
+//! Measures how many candidate assignments a single theory worker checks per second.
+//!
+//! The benchmark runs the synthesis described by a crackers config single-threaded for a fixed
+//! budget and reports the rate of completed theory checks, along with how long the first one
+//! took to complete, which is dominated by setting up the theory. `CRACKERS_BENCH_PARALLEL`
+//! runs the synthesis on that many workers instead, to see how setup scales with them. It
+//! needs a config whose library and sleigh paths resolve on this machine, so it is driven by
+//! environment variables:
+//!
+//! ```sh
+//! CRACKERS_BENCH_CONFIG=crackers.toml CRACKERS_BENCH_SECS=30 cargo bench --bench theory_throughput
+//! ```
+//!
+//! The benchmark only uses the public API, so running it against an older checkout gives the
+//! rate to compare with.
+
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use crackers::config::CrackersConfig;
+use crackers::synthesis::DecisionResult;
+use crackers::synthesis::progress::SynthesisEvent;
+
+const DEFAULT_SECS: u64 = 30;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let Ok(path) = std::env::var("CRACKERS_BENCH_CONFIG") else {
+        eprintln!("Set CRACKERS_BENCH_CONFIG to a crackers config to run this benchmark");
+        return Ok(());
+    };
+    let secs = match std::env::var("CRACKERS_BENCH_SECS") {
+        Ok(secs) => secs.parse()?,
+        Err(_) => DEFAULT_SECS,
+    };
+    let config: CrackersConfig = toml_edit::de::from_str(&std::fs::read_to_string(&path)?)?;
+    let parallel = match std::env::var("CRACKERS_BENCH_PARALLEL") {
+        Ok(parallel) => Some(parallel.parse()?),
+        Err(_) => None,
+    };
+    let mut params = config.resolve()?;
+    params.parallel = parallel.unwrap_or(1);
+    params.timeout = Some(Duration::from_secs(secs));
+    // the clock starts once the candidates are collected
+    let start: Arc<OnceLock<Instant>> = Arc::new(OnceLock::new());
+    let first_check = Arc::new(OnceLock::new());
+    let (started, first) = (start.clone(), first_check.clone());
+    params.progress_callback = Some(Arc::new(move |event: &SynthesisEvent| {
+        if matches!(
+            event,
+            SynthesisEvent::AssignmentRefuted | SynthesisEvent::ChainFound
+        ) && let Some(start) = started.get()
+        {
+            first.get_or_init(|| start.elapsed());
+        }
+    }));
+
+    let mut synth = params.build_single()?;
+    let start = *start.get_or_init(Instant::now);
+    // keep going past found chains, so that the whole budget is spent checking assignments
+    loop {
+        let result = match parallel {
+            Some(_) => synth.decide()?,
+            None => synth.decide_single_threaded()?,
+        };
+        if !matches!(result, DecisionResult::AssignmentFound(_)) {
+            break;
+        }
+    }
+    let elapsed = start.elapsed().as_secs_f64();
+
+    let progress = synth.progress();
+    let checks = progress.assignments_checked + progress.theory_timeouts;
+    println!(
+        "{checks} assignments checked in {elapsed:.1}s: {:.2} per second ({} chains, {} conflicts, {} timeouts)",
+        checks as f64 / elapsed,
+        progress.chains_found,
+        progress.conflicts_learned,
+        progress.theory_timeouts
+    );
+    if let Some(first) = first_check.get() {
+        println!("first check completed after {:.2}s", first.as_secs_f64());
+    }
+    Ok(())
+}
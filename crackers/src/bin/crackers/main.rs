@@ -1,12 +1,17 @@
 use std::collections::HashMap;
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
 use clap::{Parser, Subcommand};
 use jingle::analysis::varnode::VarNodeSet;
 use toml_edit::ser::to_string_pretty;
-use tracing::{Level, event};
+use tracing::{Level, Span, event, info_span};
 use tracing_indicatif::IndicatifLayer;
+use tracing_indicatif::span_ext::IndicatifSpanExt;
+use tracing_indicatif::style::ProgressStyle;
 use tracing_subscriber::EnvFilter;
 use tracing_subscriber::filter::LevelFilter;
 use tracing_subscriber::layer::SubscriberExt;
@@ -18,9 +23,17 @@ use crackers::config::constraint::{
 };
 use crackers::config::sleigh::SleighConfig;
 use crackers::config::specification::SpecificationConfig;
+use crackers::error::CrackersError;
 use crackers::gadget::library::builder::GadgetLibraryConfig;
-use crackers::synthesis::DecisionResult;
 use crackers::synthesis::assignment_model::AssignmentModel;
+use crackers::synthesis::assignment_model::builder::AssignmentModelBuilder;
+use crackers::synthesis::assignment_model::payload::PayloadBuilder;
+use crackers::synthesis::assignment_model::pwntools::PwntoolsExporter;
+use crackers::synthesis::cancellation::CancellationToken;
+use crackers::synthesis::progress::ProgressCallback;
+use crackers::synthesis::report::SynthesisReport;
+use crackers::synthesis::validation::ConcreteValidator;
+use crackers::synthesis::{DecisionResult, SynthesisProgress};
 use jingle::display::JingleDisplayable;
 use jingle::modeling::ModelingContext;
 use jingle::sleigh::{SpaceType, VarNode};
@@ -41,7 +54,84 @@ pub enum CrackersCommands {
         library: Option<PathBuf>,
     },
     /// Attempt to synthesize a code-reuse attack based on the provided configuration file
-    Synth { config: PathBuf },
+    Synth {
+        config: PathBuf,
+        /// Write the raw stack payload of a successful chain to this file
+        #[arg(long, requires = "stack_pointer")]
+        payload: Option<PathBuf>,
+        /// Name of the stack pointer register payloads are laid out relative to
+        #[arg(long)]
+        stack_pointer: Option<String>,
+        /// Write a versioned JSON report of the synthesis result to this file
+        #[arg(long)]
+        json: Option<PathBuf>,
+        /// Write a pwntools script reproducing a successful chain to this file
+        #[arg(long, requires = "stack_pointer")]
+        pwntools: Option<PathBuf>,
+        /// Re-execute a successful chain concretely and check it against the reference program
+        #[arg(long)]
+        validate: bool,
+        /// Keep searching after a success, until this many distinct chains are found
+        #[arg(long, default_value_t = 1)]
+        chains: usize,
+        /// Require every chain to use a different gadget than all previous chains in at least
+        /// this many slots
+        #[arg(long, default_value_t = 1, requires = "chains")]
+        min_distance: usize,
+    },
+}
+
+/// Process exit codes. `synth` exits with [`EXIT_UNSAT`] when no chain exists for the
+/// specification, so that scripts can tell it apart from a failure, and with
+/// [`EXIT_INVALID`] when `--validate` finds that a chain does not behave as modeled.
+/// [`EXIT_TIMEOUT`] means the configured synthesis timeout ran out first, and
+/// [`EXIT_CANCELLED`] that it was interrupted with Ctrl-C.
+const EXIT_SUCCESS: i32 = 0;
+const EXIT_ERROR: i32 = 1;
+const EXIT_UNSAT: i32 = 2;
+const EXIT_INVALID: i32 = 3;
+const EXIT_TIMEOUT: i32 = 4;
+const EXIT_CANCELLED: i32 = 130;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn on_interrupt(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+    // a second Ctrl-C kills the process outright
+    unsafe {
+        libc::signal(libc::SIGINT, libc::SIG_DFL);
+    }
+}
+
+/// Cancels `token` on Ctrl-C, so that the solvers are stopped cleanly.
+fn cancel_on_interrupt(token: CancellationToken) {
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            on_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t,
+        );
+    }
+    // only async-signal-safe work may happen in the handler itself
+    std::thread::spawn(move || {
+        while !INTERRUPTED.load(Ordering::SeqCst) {
+            std::thread::sleep(Duration::from_millis(100));
+        }
+        event!(Level::WARN, "Interrupted, stopping synthesis");
+        token.cancel();
+    });
+}
+
+/// Optional artifacts that `synth` writes alongside its human-readable output. When more than
+/// one chain is requested, the artifacts of each chain are numbered.
+#[derive(Debug, Clone, Default)]
+struct SynthOutputs {
+    stack_pointer: Option<String>,
+    payload: Option<PathBuf>,
+    json: Option<PathBuf>,
+    pwntools: Option<PathBuf>,
+    validate: bool,
+    chains: usize,
+    min_distance: usize,
 }
 
 #[derive(Parser, Debug)]
@@ -84,16 +174,44 @@ fn main() {
                 }
                 new(default_path, library.clone())
             }
+            .map(|_| EXIT_SUCCESS)
         }
-        CrackersCommands::Synth { config } => {
+        CrackersCommands::Synth {
+            config,
+            payload,
+            stack_pointer,
+            json,
+            pwntools,
+            validate,
+            chains,
+            min_distance,
+        } => {
             // Synth initializes its own logging with config
-            synthesize(config.clone())
+            let outputs = SynthOutputs {
+                stack_pointer: stack_pointer.clone(),
+                payload: payload.clone(),
+                json: json.clone(),
+                pwntools: pwntools.clone(),
+                validate: *validate,
+                chains: *chains,
+                min_distance: *min_distance,
+            };
+            let result = synthesize(config.clone(), &outputs);
+            if let (Err(e), Some(path)) = (&result, &outputs.json)
+                && let Err(write_err) = write_report(path, &SynthesisReport::error(e))
+            {
+                event!(Level::ERROR, "Failed to write JSON report: {}", write_err);
+            }
+            result
         }
     };
 
-    if let Err(e) = result {
-        event!(Level::ERROR, "Command failed: {}", e);
-        std::process::exit(1);
+    match result {
+        Ok(code) => std::process::exit(code),
+        Err(e) => {
+            event!(Level::ERROR, "Command failed: {}", e);
+            std::process::exit(EXIT_ERROR);
+        }
     }
 }
 
@@ -147,6 +265,7 @@ fn new(path: PathBuf, library: Option<PathBuf>) -> anyhow::Result<()> {
             sample_size: None,
             base_address: None,
             loaded_libraries: None,
+            cache_path: None,
         },
         sleigh: SleighConfig {
             ghidra_path: "/Applications/ghidra".to_string(),
@@ -172,6 +291,7 @@ fn new(path: PathBuf, library: Option<PathBuf>) -> anyhow::Result<()> {
                     max: 0x8000_0080,
                 }]),
             }),
+            bad_bytes: None,
         }),
         synthesis: Default::default(),
     };
@@ -190,7 +310,7 @@ fn new(path: PathBuf, library: Option<PathBuf>) -> anyhow::Result<()> {
     Ok(())
 }
 
-fn synthesize(config: PathBuf) -> anyhow::Result<()> {
+fn synthesize(config: PathBuf, outputs: &SynthOutputs) -> anyhow::Result<i32> {
     event!(
         Level::INFO,
         "Loading configuration from: {}",
@@ -217,41 +337,208 @@ fn synthesize(config: PathBuf) -> anyhow::Result<()> {
         .init();
 
     event!(Level::INFO, "Resolving configuration parameters");
-    let params = p.resolve()?;
+    let mut params = p.resolve()?;
+    cancel_on_interrupt(params.cancellation.clone());
+    let progress_span = info_span!("synthesis");
+    progress_span.pb_set_style(&ProgressStyle::with_template(
+        "{spinner} [{elapsed_precise}] {msg}",
+    )?);
+    params.progress_callback = Some(progress_display(progress_span.clone()));
+    let _progress = progress_span.enter();
 
     event!(
         Level::INFO,
         "Starting synthesis (combine_instructions: {})",
         params.combine_instructions
     );
-    let result = match params.combine_instructions {
+    let results: Box<dyn Iterator<Item = _>> = match params.combine_instructions {
         true => {
             event!(Level::DEBUG, "Building combined synthesis");
-            params.build_combined().and_then(|mut c| c.decide())
+            let mut combined = params.build_combined()?;
+            match outputs.chains {
+                // a single chain may come from several partitions searched at once
+                1 => Box::new(std::iter::once(combined.decide())),
+                n => Box::new(combined.chains(n, outputs.min_distance)),
+            }
         }
         false => {
             event!(Level::DEBUG, "Building single synthesis");
-            params.build_single().and_then(|mut c| c.decide())
+            Box::new(
+                params
+                    .build_single()?
+                    .chains(outputs.chains, outputs.min_distance),
+            )
         }
     };
 
-    match result {
-        Ok(res) => match res {
-            DecisionResult::AssignmentFound(a) => {
-                event!(Level::DEBUG, "Building assignment result");
-                let a = a.build()?;
-                event!(Level::INFO, "Synthesis successful :)");
-                event!(Level::INFO, "{}", a);
-                print_assignment_details(&a);
+    let postcondition = p.constraint.and_then(|c| c.postcondition);
+    let mut found = 0;
+    let mut code = EXIT_SUCCESS;
+    for result in results {
+        let result = match result {
+            Err(CrackersError::Cancelled) if found > 0 => {
+                event!(
+                    Level::WARN,
+                    "Synthesis cancelled after finding {} chains",
+                    found
+                );
+                break;
+            }
+            Err(CrackersError::Cancelled) => {
+                event!(Level::ERROR, "Synthesis cancelled");
+                if let Some(path) = &outputs.json {
+                    write_report(path, &SynthesisReport::error(CrackersError::Cancelled))?;
+                }
+                return Ok(EXIT_CANCELLED);
+            }
+            r => r?,
+        };
+        event!(Level::INFO, "Search statistics: {}", result.statistics());
+        match result {
+            DecisionResult::AssignmentFound(builder) => {
+                if !write_chain(&builder, found, outputs, &postcondition)? {
+                    code = EXIT_INVALID;
+                }
+                found += 1;
+            }
+            DecisionResult::Unsat(a) if found > 0 => {
+                event!(Level::INFO, "No further chains exist: {:?}", a);
             }
             DecisionResult::Unsat(a) => {
                 event!(Level::ERROR, "Synthesis unsuccessful: {:?}", a);
+                if let Some(path) = &outputs.json {
+                    write_report(path, &SynthesisReport::unsat(&a))?;
+                }
+                return Ok(exit_code(&DecisionResult::Unsat(a)));
+            }
+            DecisionResult::Timeout(progress) if found > 0 => {
+                event!(
+                    Level::WARN,
+                    "Synthesis timed out after finding {} chains: {:?}",
+                    found,
+                    progress
+                );
+            }
+            DecisionResult::Timeout(progress) => {
+                event!(Level::ERROR, "Synthesis timed out: {:?}", progress);
+                if let Some(path) = &outputs.json {
+                    write_report(path, &SynthesisReport::timeout(&progress))?;
+                }
+                return Ok(exit_code(&DecisionResult::Timeout(progress)));
             }
-        },
-        Err(e) => {
-            event!(Level::ERROR, "Synthesis error: {}", e)
         }
     }
+    Ok(code)
+}
+
+/// The exit code for a synthesis whose first result is `result`, before any validation.
+fn exit_code(result: &DecisionResult) -> i32 {
+    match result {
+        DecisionResult::AssignmentFound(_) => EXIT_SUCCESS,
+        DecisionResult::Unsat(_) => EXIT_UNSAT,
+        DecisionResult::Timeout(_) => EXIT_TIMEOUT,
+    }
+}
+
+/// Shows the running totals of a synthesis as the message of `span`'s progress bar.
+fn progress_display(span: Span) -> Arc<ProgressCallback> {
+    let stats = Mutex::new(SynthesisProgress::default());
+    Arc::new(move |event| {
+        let mut stats = stats.lock().unwrap();
+        stats.record(event);
+        span.pb_set_message(&stats.to_string());
+    })
+}
+
+/// Reports a found chain and writes its artifacts. Returns `false` if `--validate` rejected it.
+fn write_chain(
+    builder: &AssignmentModelBuilder,
+    index: usize,
+    outputs: &SynthOutputs,
+    postcondition: &Option<StateEqualityConstraint>,
+) -> anyhow::Result<bool> {
+    event!(Level::DEBUG, "Building assignment result");
+    let a = builder.build()?;
+    event!(Level::INFO, "Synthesis successful :)");
+    if outputs.chains > 1 {
+        println!("--- Chain {index} ---\n");
+    }
+    event!(Level::INFO, "{}", a);
+    print_assignment_details(&a);
+    let numbered = |path: &PathBuf| numbered_path(path, index, outputs.chains);
+    if let (Some(path), Some(stack_pointer)) = (&outputs.payload, &outputs.stack_pointer) {
+        let payload = PayloadBuilder::new(stack_pointer).with_bad_bytes(&builder.bad_bytes);
+        write_payload(&a, &numbered(path), &payload)?;
+    }
+    if let (Some(path), Some(stack_pointer)) = (&outputs.pwntools, &outputs.stack_pointer) {
+        let path = numbered(path);
+        let payload = PayloadBuilder::new(stack_pointer).with_bad_bytes(&builder.bad_bytes);
+        let script = PwntoolsExporter::new(payload)
+            .with_libraries(&builder.libraries)
+            .export(&a)?;
+        fs::write(&path, script.to_string())?;
+        event!(Level::INFO, "Wrote pwntools script to {}", path.display());
+    }
+    if let Some(path) = &outputs.json {
+        write_report(&numbered(path), &SynthesisReport::from_model(builder, &a))?;
+    }
+    if outputs.validate {
+        event!(Level::INFO, "Validating chain by concrete execution");
+        let divergence = ConcreteValidator::new(builder)?
+            .with_postcondition(postcondition.clone())
+            .validate(&a)?;
+        if let Some(d) = divergence {
+            event!(Level::ERROR, "Concrete validation failed: {}", d);
+            return Ok(false);
+        }
+        event!(Level::INFO, "Concrete validation passed");
+    }
+    Ok(true)
+}
+
+/// Inserts the chain index before the extension of `path` (`payload.bin` becomes
+/// `payload.2.bin`) when several chains are being written.
+fn numbered_path(path: &Path, index: usize, chains: usize) -> PathBuf {
+    if chains <= 1 {
+        return path.to_path_buf();
+    }
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(ext) => format!("{stem}.{index}.{}", ext.to_string_lossy()),
+        None => format!("{stem}.{index}"),
+    };
+    path.with_file_name(name)
+}
+
+fn write_report(path: &PathBuf, report: &SynthesisReport) -> anyhow::Result<()> {
+    fs::write(path, serde_json::to_string_pretty(report)?)?;
+    event!(Level::INFO, "Wrote JSON report to {}", path.display());
+    Ok(())
+}
+
+fn write_payload<T: ModelingContext>(
+    model: &AssignmentModel<T>,
+    path: &PathBuf,
+    builder: &PayloadBuilder,
+) -> anyhow::Result<()> {
+    event!(Level::DEBUG, "Building payload with {:?}", builder);
+    let payload = builder.build(model)?;
+    println!("--- Payload ---\n");
+    println!("{payload}");
+    if !payload.external.is_empty() {
+        event!(
+            Level::WARN,
+            "{} controlled location(s) fall outside of the stack buffer and are not written",
+            payload.external.len()
+        );
+    }
+    fs::write(path, &payload.bytes)?;
+    event!(
+        Level::INFO,
+        "Wrote {} payload bytes to {}",
+        payload.bytes.len(),
+        path.display()
+    );
     Ok(())
 }
 
@@ -420,3 +707,49 @@ fn print_assignment_details<T: ModelingContext>(model: &AssignmentModel<T>) {
 
     println!("\n==============================================\n");
 }
+
+#[cfg(test)]
+mod tests {
+    use crackers::synthesis::DecisionResult;
+    use crackers::synthesis::SynthesisProgress;
+    use crackers::synthesis::assignment_model::builder::AssignmentModelBuilder;
+    use crackers::synthesis::selection_strategy::SelectionFailure;
+    use jingle::sleigh::SleighArchInfo;
+
+    use crate::{EXIT_SUCCESS, EXIT_TIMEOUT, EXIT_UNSAT, exit_code};
+
+    #[test]
+    fn test_exit_code() {
+        let builder = AssignmentModelBuilder {
+            templates: Default::default(),
+            gadgets: vec![],
+            fallback: None,
+            preconditions: vec![],
+            postconditions: vec![],
+            pointer_invariants: vec![],
+            arch_info: SleighArchInfo::new(
+                "test".to_string(),
+                std::iter::empty(),
+                std::iter::empty(),
+                0,
+                vec![],
+            ),
+            libraries: vec![],
+            bad_bytes: vec![],
+            statistics: SynthesisProgress::default(),
+        };
+        assert_eq!(
+            exit_code(&DecisionResult::AssignmentFound(builder)),
+            EXIT_SUCCESS
+        );
+        let failure = SelectionFailure {
+            indices: vec![0],
+            statistics: SynthesisProgress::default(),
+        };
+        assert_eq!(exit_code(&DecisionResult::Unsat(failure)), EXIT_UNSAT);
+        assert_eq!(
+            exit_code(&DecisionResult::Timeout(SynthesisProgress::default())),
+            EXIT_TIMEOUT
+        );
+    }
+}
@@ -19,6 +19,9 @@ pub struct ConstraintConfig {
     pub precondition: Option<StateEqualityConstraint>,
     pub postcondition: Option<StateEqualityConstraint>,
     pub pointer: Option<PointerRangeConstraints>,
+    /// Bytes that may not appear in the payload: neither in attacker-controlled memory read by
+    /// the chain, nor in the addresses of its gadgets.
+    pub bad_bytes: Option<Vec<u8>>,
 }
 
 impl ConstraintConfig {
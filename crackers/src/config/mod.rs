@@ -6,8 +6,13 @@ use crate::config::synthesis::SynthesisConfig;
 use crate::error::CrackersError;
 use crate::gadget::library::builder::GadgetLibraryConfig;
 use crate::reference_program::ReferenceProgram;
-use crate::synthesis::builder::{SynthesisParams, SynthesisParamsBuilder};
+use crate::synthesis::builder::{
+    SynthesisParams, SynthesisParamsBuilder, SynthesisSelectionStrategy,
+};
+use crate::synthesis::pcode_theory::DEFAULT_MINIMIZATION_BUDGET;
+use crate::synthesis::selection_strategy::portfolio::Portfolio;
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 pub mod constraint;
 pub mod error;
@@ -34,13 +39,20 @@ pub struct CrackersConfig {
 
 impl CrackersConfig {
     pub fn resolve(&self) -> Result<SynthesisParams, CrackersError> {
-        let library = self.library.build(&self.sleigh)?;
+        let mut library = self.library.build(&self.sleigh)?;
         let lang_id = library.language_id.clone();
         let mut b = SynthesisParamsBuilder::default();
         if let Some(c) = &self.constraint {
             b.preconditions(c.get_preconditions(&library.arch_info()).collect());
             b.postconditions(c.get_postconditions(&library.arch_info()).collect());
             b.pointer_invariants(c.get_pointer_constraints().collect());
+            if let Some(write) = c.pointer.as_ref().and_then(|p| p.write.clone()) {
+                b.pointer_write_ranges(write);
+            }
+            if let Some(bad_bytes) = &c.bad_bytes {
+                library.remove_bad_addresses(bad_bytes);
+                b.bad_bytes(bad_bytes.clone());
+            }
         }
         b.gadget_library(library)
             .seed(self.meta.seed)
@@ -50,10 +62,31 @@ impl CrackersConfig {
                 &self.library.operation_blacklist,
                 &lang_id,
             )?);
-        b.selection_strategy(self.synthesis.strategy);
+        match (&self.synthesis.strategy, &self.synthesis.portfolio) {
+            (SynthesisSelectionStrategy::PortfolioStrategy, Some(members)) => {
+                b.selection_strategy(Portfolio::new(members.clone()))
+            }
+            (strategy, _) => b.selection_strategy(*strategy),
+        };
+        b.objectives(self.synthesis.objectives.clone().unwrap_or_default());
         b.combine_instructions(self.synthesis.combine_instructions);
         b.candidates_per_slot(self.synthesis.max_candidates_per_slot);
         b.parallel(self.synthesis.parallel).seed(self.meta.seed);
+        b.timeout(self.synthesis.timeout_secs.map(Duration::from_secs));
+        b.theory_timeout(self.synthesis.theory_timeout_ms.map(Duration::from_millis));
+        b.concurrent_partitions(self.synthesis.concurrent_partitions.unwrap_or(1));
+        b.candidate_expansions(self.synthesis.candidate_expansions.unwrap_or(0));
+        b.prefilter_candidates(self.synthesis.prefilter_candidates.unwrap_or(true));
+        b.conflict_minimization(
+            self.synthesis
+                .conflict_minimization
+                .unwrap_or(DEFAULT_MINIMIZATION_BUDGET),
+        );
+        b.improvement_timeout(
+            self.synthesis
+                .improvement_timeout_secs
+                .map(Duration::from_secs),
+        );
 
         let params = b.build()?;
         Ok(params)
@@ -5,7 +5,7 @@ use std::path::Path;
 
 use jingle::sleigh::context::image::gimli::{OwnedFile, map_gimli_architecture};
 use jingle::sleigh::context::loaded::LoadedSleighContext;
-use object::{File, Object};
+use object::{File, Object, ObjectSegment};
 
 use crate::config::error::CrackersConfigError;
 use crate::config::error::CrackersConfigError::UnrecognizedArchitecture;
@@ -37,10 +37,24 @@ fn load_image_spec<T: AsRef<Path>>(
     Ok((img, arch))
 }
 
+/// The page-aligned address of the lowest segment of the image at `path`, before any rebasing.
+/// This is what exploit tooling typically considers the base address of a binary.
+pub fn load_image_base<T: AsRef<Path>>(path: T) -> Result<u64, CrackersConfigError> {
+    let data = fs::read(path.as_ref())?;
+    let file = File::parse(&*data)?;
+    let base = file.segments().map(|s| s.address()).min().unwrap_or(0);
+    Ok(base & !0xfff)
+}
+
+/// Loads the executable segments of the image at `path`, at their link-time addresses.
+pub fn load_segments<T: AsRef<Path>>(path: T) -> Result<SegmentFile, CrackersConfigError> {
+    load_image(path).map(|(img, _)| img)
+}
+
 pub fn load_sleigh<T: AsRef<Path>>(
     file_path: T,
     sleigh_config: &SleighConfig,
-) -> Result<LoadedSleighContext<'_>, CrackersConfigError> {
+) -> Result<LoadedSleighContext<'static>, CrackersConfigError> {
     let (img, arch) = load_image(file_path)?;
     let builder = sleigh_config.context_builder()?;
     let ctx = builder.build(arch)?;
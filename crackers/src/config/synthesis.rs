@@ -3,23 +3,62 @@ use pyo3::{pyclass, pymethods};
 use serde::{Deserialize, Serialize};
 
 use crate::synthesis::builder::SynthesisSelectionStrategy;
+use crate::synthesis::selection_strategy::objective::OptimizationObjective;
+use crate::synthesis::selection_strategy::portfolio::PortfolioMember;
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[cfg_attr(feature = "pyo3", pyclass(get_all, set_all))]
 pub struct SynthesisConfig {
     pub strategy: SynthesisSelectionStrategy,
+    /// Terms of the cost minimized by the `optimize` strategy. Defaults to the number of
+    /// instructions.
+    pub objectives: Option<Vec<OptimizationObjective>>,
+    /// The outer problems raced by the `portfolio` strategy. Defaults to a SAT problem, an
+    /// optimize problem and a differently seeded SAT problem.
+    pub portfolio: Option<Vec<PortfolioMember>>,
     pub max_candidates_per_slot: usize,
     pub parallel: usize,
     pub combine_instructions: bool,
+    /// Wall-clock budget for the whole synthesis run, in seconds. When it runs out, synthesis
+    /// stops and reports how far it got.
+    pub timeout_secs: Option<u64>,
+    /// Timeout for each theory check of a candidate chain, in milliseconds. Chains whose check
+    /// times out are retried once on another worker, then skipped.
+    pub theory_timeout_ms: Option<u64>,
+    /// Number of instruction partitions to search at once when `combine_instructions` is set,
+    /// sharing the `parallel` workers. At most `parallel` partitions are searched at once.
+    /// Defaults to one.
+    pub concurrent_partitions: Option<usize>,
+    /// Number of times a step that runs out of candidate gadgets may be given another
+    /// `max_candidates_per_slot` of them from the library. Defaults to zero.
+    pub candidate_expansions: Option<usize>,
+    /// Whether to check each candidate gadget against its step alone before the search, ruling
+    /// out the incompatible ones up front. Defaults to true.
+    pub prefilter_candidates: Option<bool>,
+    /// Extra solver calls each refuted theory check may make to narrow its conflicts to
+    /// restrictions on the first or last slot and to shrink them. Defaults to one.
+    pub conflict_minimization: Option<usize>,
+    /// Once a chain is found, how many more seconds to search for cheaper ones. The cheapest
+    /// chain found is returned.
+    pub improvement_timeout_secs: Option<u64>,
 }
 
 impl Default for SynthesisConfig {
     fn default() -> Self {
         SynthesisConfig {
             strategy: SynthesisSelectionStrategy::SatStrategy,
+            objectives: None,
+            portfolio: None,
             max_candidates_per_slot: 200,
             parallel: 6,
             combine_instructions: true,
+            timeout_secs: None,
+            theory_timeout_ms: None,
+            concurrent_partitions: None,
+            candidate_expansions: None,
+            prefilter_candidates: None,
+            conflict_minimization: None,
+            improvement_timeout_secs: None,
         }
     }
 }
@@ -28,17 +67,37 @@ impl Default for SynthesisConfig {
 #[pymethods]
 impl SynthesisConfig {
     #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (strategy, max_candidates_per_slot, parallel, combine_instructions, timeout_secs=None, theory_timeout_ms=None, concurrent_partitions=None, candidate_expansions=None, objectives=None, portfolio=None, improvement_timeout_secs=None, prefilter_candidates=None, conflict_minimization=None))]
     fn new(
         strategy: SynthesisSelectionStrategy,
         max_candidates_per_slot: usize,
         parallel: usize,
         combine_instructions: bool,
+        timeout_secs: Option<u64>,
+        theory_timeout_ms: Option<u64>,
+        concurrent_partitions: Option<usize>,
+        candidate_expansions: Option<usize>,
+        objectives: Option<Vec<OptimizationObjective>>,
+        portfolio: Option<Vec<PortfolioMember>>,
+        improvement_timeout_secs: Option<u64>,
+        prefilter_candidates: Option<bool>,
+        conflict_minimization: Option<usize>,
     ) -> Self {
         SynthesisConfig {
             strategy,
+            objectives,
+            portfolio,
             max_candidates_per_slot,
             parallel,
             combine_instructions,
+            timeout_secs,
+            theory_timeout_ms,
+            concurrent_partitions,
+            candidate_expansions,
+            prefilter_candidates,
+            conflict_minimization,
+            improvement_timeout_secs,
         }
     }
 }
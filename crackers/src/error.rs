@@ -27,8 +27,14 @@ pub enum CrackersError {
     ModelGenerationError,
     #[error("Outer gadget assignment solver timed out.")]
     BooleanAssignmentTimeout,
+    #[error("Synthesis was cancelled")]
+    Cancelled,
+    #[error("The theory workers exited before the search was over")]
+    WorkersExited,
     #[error("Unexpected terms found in assignment model")]
     ModelParsingError,
+    #[error("Unknown register: {0}")]
+    UnknownRegister(String),
     #[error("Config error: {0}")]
     Config(#[from] CrackersConfigError),
     #[error("Jingle error")]
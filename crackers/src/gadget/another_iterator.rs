@@ -1,6 +1,7 @@
 use jingle::modeling::{ModeledInstruction, ModelingContext};
 use jingle::sleigh::{Instruction, OpCode, SleighArchInfo};
 use std::borrow::Borrow;
+use std::sync::Arc;
 use tracing::trace;
 use z3::Solver;
 use z3::ast::Ast;
@@ -8,19 +9,20 @@ use z3::ast::Ast;
 use crate::gadget::Gadget;
 use crate::gadget::signature::GadgetSignature;
 
-pub struct TraceCandidateIterator<'a, T>
+pub struct TraceCandidateIterator<T>
 where
-    T: Iterator<Item = &'a Gadget>,
+    T: Iterator<Item = Arc<Gadget>>,
 {
     info: SleighArchInfo,
     _solver: Solver,
     gadgets: T,
     trace: Vec<ModeledInstruction>,
+    consumed: usize,
 }
 
-impl<'a, T> TraceCandidateIterator<'a, T>
+impl<T> TraceCandidateIterator<T>
 where
-    T: Iterator<Item = &'a Gadget>,
+    T: Iterator<Item = Arc<Gadget>>,
 {
     pub(crate) fn new<S: Borrow<SleighArchInfo>>(
         jingle: S,
@@ -33,20 +35,27 @@ where
             _solver,
             gadgets,
             trace,
+            consumed: 0,
         }
     }
+
+    /// The number of gadgets taken from the underlying iterator so far.
+    pub(crate) fn consumed(&self) -> usize {
+        self.consumed
+    }
 }
-impl<'a, T> Iterator for TraceCandidateIterator<'a, T>
+impl<T> Iterator for TraceCandidateIterator<T>
 where
-    T: Iterator<Item = &'a Gadget>,
+    T: Iterator<Item = Arc<Gadget>>,
 {
-    type Item = Vec<Option<&'a Gadget>>;
+    type Item = Vec<Option<Arc<Gadget>>>;
 
     fn next(&mut self) -> Option<Self::Item> {
         let mut next_entry = vec![None; self.trace.len()];
         loop {
             let gadget = self.gadgets.next()?;
-            let gadget_signature = GadgetSignature::from(gadget);
+            self.consumed += 1;
+            let gadget_signature = GadgetSignature::from_gadget(&gadget, &self.info);
             trace!("Evaluating gadget at {:x}", gadget.address());
             let is_candidate: Vec<bool> = self
                 .trace
@@ -59,7 +68,7 @@ where
 
                     gadget_signature
                         .covers(&GadgetSignature::from_instr(&i.instr, i.get_arch_info()))
-                        && has_compatible_control_flow(&i.instr, gadget)
+                        && has_compatible_control_flow(&i.instr, &gadget)
                 })
                 .collect();
             if is_candidate.iter().any(|b| *b) {
@@ -72,7 +81,7 @@ where
                                 .unwrap()
                                 .simplify();
                             if !expr.is_const() || expr.as_bool().unwrap() {
-                                next_entry[i] = Some(gadget)
+                                next_entry[i] = Some(gadget.clone())
                             }
                         }
                     })
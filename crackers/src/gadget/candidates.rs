@@ -1,9 +1,13 @@
 use crate::error::CrackersError;
 use crate::error::CrackersError::UnsimulatedOperation;
-use crate::gadget::Gadget;
+use crate::gadget::another_iterator::TraceCandidateIterator;
+use crate::gadget::{EquivalenceKey, Gadget};
 use jingle::modeling::ModeledBlock;
 use jingle::sleigh::SleighArchInfo;
 use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::sync::Arc;
 
 #[derive(Clone, Debug, Default)]
 pub struct CandidateBuilder {
@@ -16,48 +20,172 @@ impl CandidateBuilder {
         self
     }
 
-    pub fn build<'a, T: Iterator<Item = Vec<Option<&'a Gadget>>>>(
+    pub fn build<G: Borrow<Gadget>, T: Iterator<Item = Vec<Option<G>>>>(
         &self,
         iter: T,
     ) -> Result<Candidates, CrackersError> {
-        let mut candidates: Vec<Vec<Gadget>> = vec![];
-        for gc in iter {
+        self.collect(iter, |_| None)
+    }
+
+    /// Like [`build`](Self::build), but remembers how far into the library each slot was
+    /// filled, so that it can later be [extended](Candidates::extend_slot).
+    pub(crate) fn build_resumable<I: Iterator<Item = Arc<Gadget>>>(
+        &self,
+        iter: TraceCandidateIterator<I>,
+    ) -> Result<Candidates, CrackersError> {
+        self.collect(iter, |i| Some(i.consumed()))
+    }
+
+    fn collect<G, T, P>(&self, mut iter: T, position: P) -> Result<Candidates, CrackersError>
+    where
+        G: Borrow<Gadget>,
+        T: Iterator<Item = Vec<Option<G>>>,
+        P: Fn(&T) -> Option<usize>,
+    {
+        let mut candidates = Candidates::default();
+        while let Some(gc) = iter.next() {
             // todo: this feels ugly but I just need something that works for now
-            if gc.len() != candidates.len() {
-                candidates = gc.iter().map(|_| vec![]).collect();
+            if gc.len() != candidates.candidates.len() {
+                candidates = Candidates::with_slots(gc.len());
             }
-            gc.iter()
+            for (i, g) in gc
+                .iter()
                 .enumerate()
-                .filter_map(|(i, g)| g.map(|g| (i, g.clone())))
-                .for_each(|(i, g)| {
-                    if candidates[i].len() < self.random_sample_size {
-                        candidates[i].push(g)
-                    }
-                });
-            if !candidates.iter().any(|g| g.len() < self.random_sample_size) {
+                .filter_map(|(i, g)| g.as_ref().map(|g| (i, g.borrow())))
+            {
+                if candidates.candidates[i].len() < self.random_sample_size
+                    && candidates.add(i, g)
+                    && candidates.candidates[i].len() == self.random_sample_size
+                {
+                    candidates.cursors[i] = position(&iter);
+                }
+            }
+            if !candidates
+                .candidates
+                .iter()
+                .any(|g| g.len() < self.random_sample_size)
+            {
                 break;
             }
         }
         // We never found ANY candidates for ANYTHING
-        if candidates.is_empty() {
+        if candidates.candidates.is_empty() {
             Err(UnsimulatedOperation { index: 0 })
         }
         // We never found candidates for something
-        else if let Some((index, _)) = candidates.iter().enumerate().find(|(_, f)| f.is_empty()) {
+        else if let Some((index, _)) = candidates
+            .candidates
+            .iter()
+            .enumerate()
+            .find(|(_, f)| f.is_empty())
+        {
             Err(UnsimulatedOperation { index })
         } else {
             // candidates!
-            Ok(Candidates { candidates })
+            Ok(candidates)
         }
     }
 }
 
-#[derive(Clone)]
+/// The candidate gadgets for each slot of a chain.
+///
+/// [Equivalent](Gadget::equivalent) gadgets are grouped into classes: each candidate stands for
+/// its class, and the other members are kept aside as its
+/// [`equivalents`](Self::equivalents). The search chooses among classes, so that a refuted
+/// gadget takes its whole class with it, and a concrete member is only picked for the model.
+#[derive(Clone, Default)]
 pub struct Candidates {
     pub candidates: Vec<Vec<Gadget>>,
+    /// For each slot and candidate, the other gadgets of its class.
+    equivalents: Vec<Vec<Vec<Gadget>>>,
+    /// For each slot, the candidate standing for each class.
+    classes: Vec<HashMap<EquivalenceKey, usize>>,
+    /// For each slot, how far into the library its candidates were taken from, or `None` if
+    /// the library has no more candidates for it.
+    cursors: Vec<Option<usize>>,
 }
 
 impl Candidates {
+    fn with_slots(slots: usize) -> Self {
+        Self {
+            candidates: vec![vec![]; slots],
+            equivalents: vec![vec![]; slots],
+            classes: vec![HashMap::new(); slots],
+            cursors: vec![None; slots],
+        }
+    }
+
+    /// Adds `gadget` to the `index`th slot: to the class of an equivalent candidate if there
+    /// is one, or else as a new candidate. Returns whether it is a new candidate.
+    fn add(&mut self, index: usize, gadget: &Gadget) -> bool {
+        match self.classes[index].entry(gadget.equivalence_key()) {
+            Entry::Occupied(class) => {
+                self.equivalents[index][*class.get()].push(gadget.clone());
+                false
+            }
+            Entry::Vacant(class) => {
+                class.insert(self.candidates[index].len());
+                self.candidates[index].push(gadget.clone());
+                self.equivalents[index].push(vec![]);
+                true
+            }
+        }
+    }
+
+    /// The gadgets equivalent to the `choice`th candidate of the `index`th slot, besides
+    /// itself.
+    pub fn equivalents(&self, index: usize, choice: usize) -> &[Gadget] {
+        &self.equivalents[index][choice]
+    }
+
+    /// Makes every other member of the class of the `choice`th candidate of the `index`th slot
+    /// a candidate of its own, for when the candidate was refuted because of its address rather
+    /// than its p-code. Returns the number of candidates added.
+    pub(crate) fn split_class(&mut self, index: usize, choice: usize) -> usize {
+        let members = std::mem::take(&mut self.equivalents[index][choice]);
+        let added = members.len();
+        for gadget in members {
+            self.candidates[index].push(gadget);
+            self.equivalents[index].push(vec![]);
+        }
+        added
+    }
+
+    /// Where to resume searching the library for more candidates for the `index`th slot, or
+    /// `None` if it has no more.
+    pub(crate) fn cursor(&self, index: usize) -> Option<usize> {
+        self.cursors.get(index).copied().flatten()
+    }
+
+    /// Adds up to `limit` more candidates to the `index`th slot, taken from `iter`, an iterator
+    /// over the candidates for just that slot that resumes the library at its
+    /// [`cursor`](Self::cursor). Gadgets equivalent to a candidate join its class and don't
+    /// count toward `limit`. Returns the number of candidates added.
+    pub(crate) fn extend_slot<I: Iterator<Item = Arc<Gadget>>>(
+        &mut self,
+        index: usize,
+        mut iter: TraceCandidateIterator<I>,
+        limit: usize,
+    ) -> usize {
+        let Some(start) = self.cursor(index) else {
+            return 0;
+        };
+        let mut added = 0;
+        self.cursors[index] = None;
+        while let Some(gc) = iter.next() {
+            if let Some(Some(g)) = gc.first()
+                && self.add(index, g)
+            {
+                added += 1;
+                if added == limit {
+                    self.cursors[index] = Some(start + iter.consumed());
+                    break;
+                }
+            }
+        }
+        added
+    }
+
     pub fn model<T: Borrow<SleighArchInfo>>(
         &self,
         info: T,
@@ -74,3 +202,79 @@ impl Candidates {
         Ok(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use jingle::sleigh::{PcodeOperation, VarNode};
+
+    use crate::gadget::Gadget;
+    use crate::gadget::candidates::CandidateBuilder;
+    use crate::test_util::instruction;
+
+    const CODE: usize = 1;
+
+    fn varnode(space_index: usize, offset: u64) -> VarNode {
+        VarNode {
+            space_index,
+            offset,
+            size: 8,
+        }
+    }
+
+    /// A two-instruction gadget at `address`, copying from `register` and falling through to
+    /// another copy.
+    fn gadget(address: u64, register: u64) -> Gadget {
+        Gadget::new(
+            CODE,
+            vec![
+                instruction(
+                    address,
+                    vec![
+                        PcodeOperation::Copy {
+                            input: varnode(2, register),
+                            output: varnode(2, 0),
+                        },
+                        PcodeOperation::Branch {
+                            input: varnode(CODE, address + 1),
+                        },
+                    ],
+                ),
+                instruction(
+                    address + 1,
+                    vec![PcodeOperation::Copy {
+                        input: varnode(2, 0),
+                        output: varnode(2, 24),
+                    }],
+                ),
+            ],
+        )
+    }
+
+    #[test]
+    fn test_equivalence_classes() {
+        let gadgets = [
+            gadget(0x1000, 8),
+            gadget(0x2000, 8),
+            gadget(0x3000, 16),
+            gadget(0x4000, 8),
+        ];
+        assert!(gadgets[0].equivalent(&gadgets[1]));
+        assert!(!gadgets[0].equivalent(&gadgets[2]));
+        let candidates = CandidateBuilder::default()
+            .with_random_sample_size(2)
+            .build(gadgets.iter().map(|g| vec![Some(g)]))
+            .unwrap();
+        // the copies of the first gadget don't take up a candidate of their own
+        let addresses: Vec<u64> = candidates.candidates[0]
+            .iter()
+            .map(Gadget::address)
+            .collect();
+        assert_eq!(addresses, vec![0x1000, 0x3000]);
+        let equivalents: Vec<u64> = candidates
+            .equivalents(0, 0)
+            .iter()
+            .map(Gadget::address)
+            .collect();
+        assert_eq!(equivalents, vec![0x2000]);
+    }
+}
@@ -6,11 +6,14 @@ use pyo3::pyclass;
 use pyo3::pymethods;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
+use std::sync::Arc;
 
 use crate::config::error::CrackersConfigError;
-use crate::config::object::load_sleigh;
+use crate::config::object::{load_image_base, load_segments, load_sleigh};
 use crate::config::sleigh::SleighConfig;
-use crate::gadget::library::GadgetLibrary;
+use crate::gadget::library::cache::LibraryFingerprint;
+use crate::gadget::library::{GadgetLibrary, LibraryImage};
+use jingle::sleigh::context::loaded::LoadedSleighContext;
 use tracing::{Level, event};
 
 const LIB_ALIGNMENT: u64 = 0x4000; // 16 KiB alignment for loaded libraries
@@ -46,10 +49,62 @@ pub struct GadgetLibraryConfig {
     /// builder will attempt to place the library in an address region that does
     /// not conflict with the main library or previously placed libraries.
     pub loaded_libraries: Option<Vec<LoadedLibraryConfig>>,
+    /// If set, the built library is cached at this path and reused on later runs, as long as
+    /// the binaries, `max_gadget_length`, `operation_blacklist` and the sleigh configuration are
+    /// unchanged.
+    pub cache_path: Option<String>,
 }
 
 impl GadgetLibraryConfig {
     pub fn build(&self, sleigh: &SleighConfig) -> Result<GadgetLibrary, CrackersConfigError> {
+        let (sleighs, images) = self.load_images(sleigh)?;
+        let Some(cache_path) = &self.cache_path else {
+            return self.scan(sleigh, sleighs, images);
+        };
+        let fingerprint = LibraryFingerprint::new(self, sleigh, sleighs[0].get_language_id())?;
+        match GadgetLibrary::load_cache(cache_path, &fingerprint, &sleighs) {
+            Ok(Some(library)) => {
+                event!(
+                    Level::INFO,
+                    "Loaded {} gadgets from cache at {}",
+                    library.size(),
+                    cache_path
+                );
+                return Ok(library);
+            }
+            Ok(None) => event!(
+                Level::INFO,
+                "No up-to-date gadget cache at {}; rebuilding",
+                cache_path
+            ),
+            Err(e) => event!(
+                Level::WARN,
+                "Unable to read gadget cache at {} ({}); rebuilding",
+                cache_path,
+                e
+            ),
+        }
+        let library = self.scan(sleigh, sleighs, images)?;
+        match library.save_cache(cache_path, self, &fingerprint) {
+            Ok(()) => event!(Level::INFO, "Wrote gadget cache to {}", cache_path),
+            Err(e) => event!(
+                Level::WARN,
+                "Unable to write gadget cache to {}: {}",
+                cache_path,
+                e
+            ),
+        }
+        Ok(library)
+    }
+
+    /// Loads the primary library and any additional libraries, placing the additional ones so
+    /// that they do not overlap.
+    #[allow(clippy::type_complexity)]
+    fn load_images(
+        &self,
+        sleigh: &SleighConfig,
+    ) -> Result<(Vec<Arc<LoadedSleighContext<'static>>>, Vec<LibraryImage>), CrackersConfigError>
+    {
         let mut library_sleigh = load_sleigh(&self.path, sleigh)?;
         if let Some(addr) = self.base_address {
             let aligned = align_up(addr, LIB_ALIGNMENT);
@@ -67,6 +122,7 @@ impl GadgetLibraryConfig {
 
         // Prepare a vector of sleigh contexts (main + any additional libraries)
         // Start with the primary library context.
+        let mut images = vec![library_image(&self.path, &library_sleigh)?];
         let mut sleighs = vec![library_sleigh];
 
         // If there are additional libraries to load, load them and
@@ -133,13 +189,84 @@ impl GadgetLibraryConfig {
 
                 // Keep the loaded context so we can pass all contexts to the
                 // gadget library builder.
+                images.push(library_image(&cfg.path, &other)?);
                 sleighs.push(other);
             }
         }
 
-        // Build gadget library from all provided sleigh contexts.
-        GadgetLibrary::build_from_image(sleighs, self).map_err(CrackersConfigError::Sleigh)
+        Ok((sleighs.into_iter().map(Arc::new).collect(), images))
     }
+
+    /// Scans the loaded images for gadgets. Scanning threads load their own copies of the
+    /// images, at the same addresses. A thread moving on to another image swaps the image of
+    /// its copy rather than loading the language definition again, which takes far longer.
+    fn scan(
+        &self,
+        sleigh: &SleighConfig,
+        sleighs: Vec<Arc<LoadedSleighContext<'static>>>,
+        images: Vec<LibraryImage>,
+    ) -> Result<GadgetLibrary, CrackersConfigError> {
+        let paths: Vec<String> = images.iter().map(|i| i.path.clone()).collect();
+        let bases: Vec<u64> = sleighs.iter().map(|s| s.get_base_address()).collect();
+        let languages: Vec<String> = sleighs
+            .iter()
+            .map(|s| s.get_language_id().to_string())
+            .collect();
+        let fork = |library: usize, previous: Option<LoadedSleighContext<'static>>| {
+            let copy = match previous {
+                Some(copy) if copy.get_language_id() == languages[library] => {
+                    replace_image(copy, &paths[library])
+                }
+                _ => load_sleigh(&paths[library], sleigh),
+            };
+            match copy {
+                Ok(mut copy) => {
+                    copy.set_base_address(bases[library]);
+                    Some(copy)
+                }
+                Err(e) => {
+                    event!(
+                        Level::WARN,
+                        "Unable to load another copy of {} for scanning ({}); sharing one",
+                        paths[library],
+                        e
+                    );
+                    None
+                }
+            }
+        };
+        GadgetLibrary::build_from_image(sleighs, images, self, fork)
+            .map_err(CrackersConfigError::Sleigh)
+    }
+}
+
+/// Replaces the image of `sleigh` with the one at `path`, keeping its language definition.
+fn replace_image(
+    mut sleigh: LoadedSleighContext<'static>,
+    path: &str,
+) -> Result<LoadedSleighContext<'static>, CrackersConfigError> {
+    sleigh.set_image(load_segments(path)?)?;
+    Ok(sleigh)
+}
+
+fn library_image(
+    path: &str,
+    sleigh: &LoadedSleighContext,
+) -> Result<LibraryImage, CrackersConfigError> {
+    let base_address = load_image_base(path)?.wrapping_add(sleigh.get_base_address());
+    let ranges = sleigh
+        .get_sections()
+        .filter(|s| s.perms.exec)
+        .map(|s| {
+            let start = s.base_address as u64;
+            (start, start + s.data.len() as u64)
+        })
+        .collect();
+    Ok(LibraryImage {
+        path: path.to_string(),
+        base_address,
+        ranges,
+    })
 }
 
 fn default_blacklist() -> HashSet<OpCode> {
@@ -253,4 +380,14 @@ impl GadgetLibraryConfig {
     pub fn set_loaded_libraries(&mut self, l: Option<Vec<LoadedLibraryConfig>>) {
         self.loaded_libraries = l;
     }
+
+    #[getter]
+    pub fn get_cache_path(&self) -> Option<&str> {
+        self.cache_path.as_deref()
+    }
+
+    #[setter]
+    pub fn set_cache_path(&mut self, l: Option<String>) {
+        self.cache_path = l;
+    }
 }
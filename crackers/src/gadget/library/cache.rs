@@ -0,0 +1,305 @@
+//! On-disk caching of [`GadgetLibrary`]s, so that large binaries only need to be scanned once.
+//!
+//! A cache file consists of a short header (magic and format version) followed by a
+//! deflate-compressed JSON document holding the library together with a [`LibraryFingerprint`]
+//! of the inputs that produced it. A cache is only used if its fingerprint matches the current
+//! configuration and binaries.
+
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::sync::Arc;
+
+use flate2::Compression;
+use flate2::read::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use jingle::sleigh::context::loaded::LoadedSleighContext;
+use jingle::sleigh::{SleighArchInfo, SpaceInfo, VarNode};
+use serde::{Deserialize, Serialize};
+use tracing::{Level, event};
+use twox_hash::XxHash64;
+
+use crate::config::error::CrackersConfigError;
+use crate::config::sleigh::SleighConfig;
+use crate::error::CrackersError;
+use crate::gadget::library::builder::GadgetLibraryConfig;
+use crate::gadget::library::{GadgetEntry, GadgetLibrary, LibraryImage};
+
+const CACHE_MAGIC: &[u8; 8] = b"CRKRSLIB";
+
+/// Version of the cache format. Caches written with a different version are ignored.
+pub const CACHE_FORMAT_VERSION: u32 = 3;
+
+/// Identifies the inputs a [`GadgetLibrary`] was built from. Any change to these invalidates
+/// a cached library.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LibraryFingerprint {
+    pub max_gadget_length: usize,
+    /// The blacklisted opcodes, as sorted raw opcode values.
+    pub operation_blacklist: Vec<u32>,
+    /// The Ghidra installation the processor specification was loaded from.
+    pub ghidra_path: String,
+    /// The sleigh language the binaries were decoded with.
+    pub language_id: String,
+    pub binaries: Vec<BinaryFingerprint>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BinaryFingerprint {
+    pub path: String,
+    pub base_address: Option<u64>,
+    /// xxHash64 of the contents of the file.
+    pub hash: u64,
+}
+
+impl LibraryFingerprint {
+    /// Computes the fingerprint of a library config, hashing the primary library and any
+    /// additional loaded libraries, which are decoded as `language_id` using `sleigh`.
+    pub fn new(
+        config: &GadgetLibraryConfig,
+        sleigh: &SleighConfig,
+        language_id: &str,
+    ) -> Result<Self, CrackersConfigError> {
+        let mut operation_blacklist: Vec<u32> =
+            config.operation_blacklist.iter().map(|o| o.repr).collect();
+        operation_blacklist.sort();
+        let mut binaries = vec![BinaryFingerprint::new(&config.path, config.base_address)?];
+        for lib in config.loaded_libraries.iter().flatten() {
+            binaries.push(BinaryFingerprint::new(&lib.path, lib.base_address)?);
+        }
+        Ok(Self {
+            max_gadget_length: config.max_gadget_length,
+            operation_blacklist,
+            ghidra_path: sleigh.ghidra_path.clone(),
+            language_id: language_id.to_string(),
+            binaries,
+        })
+    }
+}
+
+impl BinaryFingerprint {
+    fn new(path: &str, base_address: Option<u64>) -> Result<Self, CrackersConfigError> {
+        let data = fs::read(path)?;
+        Ok(Self {
+            path: path.to_string(),
+            base_address,
+            hash: XxHash64::oneshot(0, &data),
+        })
+    }
+}
+
+/// [`SleighArchInfo`] is not serializable, so it is stored as the parts it is built from.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct ArchInfoRecord {
+    language_id: String,
+    registers: Vec<(VarNode, String)>,
+    spaces: Vec<SpaceInfo>,
+    default_code_space: usize,
+    userops: Vec<String>,
+}
+
+impl From<&SleighArchInfo> for ArchInfoRecord {
+    fn from(value: &SleighArchInfo) -> Self {
+        Self {
+            language_id: value.language_id().to_string(),
+            registers: value.registers().collect(),
+            spaces: value.spaces().to_vec(),
+            default_code_space: value.default_code_space_index(),
+            userops: value.userops().cloned().collect(),
+        }
+    }
+}
+
+impl From<ArchInfoRecord> for SleighArchInfo {
+    fn from(value: ArchInfoRecord) -> Self {
+        SleighArchInfo::new(
+            value.language_id,
+            value.registers.into_iter(),
+            value.spaces.into_iter(),
+            value.default_code_space,
+            value.userops,
+        )
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct LibraryCache {
+    fingerprint: LibraryFingerprint,
+    config: GadgetLibraryConfig,
+    language_id: String,
+    arch_info: ArchInfoRecord,
+    images: Vec<LibraryImage>,
+    gadgets: Vec<GadgetEntry>,
+}
+
+impl GadgetLibrary {
+    /// Writes this library to a cache file at `path`, tagged with the fingerprint and config
+    /// that produced it.
+    pub fn save_cache<P: AsRef<Path>>(
+        &self,
+        path: P,
+        config: &GadgetLibraryConfig,
+        fingerprint: &LibraryFingerprint,
+    ) -> Result<(), CrackersError> {
+        let cache = LibraryCache {
+            fingerprint: fingerprint.clone(),
+            config: config.clone(),
+            language_id: self.language_id.clone(),
+            arch_info: ArchInfoRecord::from(&self.arch_info),
+            images: self.images.clone(),
+            gadgets: self.gadgets.clone(),
+        };
+        let mut bytes = CACHE_MAGIC.to_vec();
+        bytes.extend(CACHE_FORMAT_VERSION.to_le_bytes());
+        let mut encoder = DeflateEncoder::new(bytes, Compression::default());
+        serde_json::to_writer(&mut encoder, &cache).map_err(|e| {
+            event!(Level::ERROR, "Failed to serialize gadget library: {}", e);
+            CrackersError::LibrarySerialization
+        })?;
+        let bytes = encoder
+            .finish()
+            .map_err(|_| CrackersError::LibrarySerialization)?;
+        fs::write(path, bytes).map_err(|e| {
+            event!(Level::ERROR, "Failed to write gadget library cache: {}", e);
+            CrackersError::LibrarySerialization
+        })
+    }
+
+    /// Loads a library from the cache file at `path`, decoding its gadgets from `sleighs`, the
+    /// images it was built from. Returns `None` if there is no cache, or if it was written by a
+    /// different format version or from inputs that do not match `fingerprint`.
+    pub fn load_cache<P: AsRef<Path>>(
+        path: P,
+        fingerprint: &LibraryFingerprint,
+        sleighs: &[Arc<LoadedSleighContext<'static>>],
+    ) -> Result<Option<Self>, CrackersError> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(None);
+        }
+        let data = fs::read(path).map_err(|e| {
+            event!(Level::ERROR, "Failed to read gadget library cache: {}", e);
+            CrackersError::LibraryDeserialization
+        })?;
+        let Some(body) = data.strip_prefix(CACHE_MAGIC.as_slice()) else {
+            return Err(CrackersError::LibraryDeserialization);
+        };
+        let (version, body) = body
+            .split_first_chunk::<4>()
+            .ok_or(CrackersError::LibraryDeserialization)?;
+        if u32::from_le_bytes(*version) != CACHE_FORMAT_VERSION {
+            event!(
+                Level::INFO,
+                "Ignoring gadget library cache with format version {}",
+                u32::from_le_bytes(*version)
+            );
+            return Ok(None);
+        }
+        let mut json = vec![];
+        DeflateDecoder::new(body)
+            .read_to_end(&mut json)
+            .map_err(|_| CrackersError::LibraryDeserialization)?;
+        let cache: LibraryCache = serde_json::from_slice(&json).map_err(|e| {
+            event!(Level::ERROR, "Failed to deserialize gadget library: {}", e);
+            CrackersError::LibraryDeserialization
+        })?;
+        if &cache.fingerprint != fingerprint {
+            return Ok(None);
+        }
+        Ok(Some(GadgetLibrary {
+            gadgets: cache.gadgets,
+            sleighs: sleighs.to_vec(),
+            decoded: Default::default(),
+            arch_info: cache.arch_info.into(),
+            language_id: cache.language_id,
+            images: cache.images,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jingle::sleigh::{SleighArchInfo, SleighEndianness, SpaceInfo, SpaceType, VarNode};
+
+    use crate::gadget::library::builder::GadgetLibraryConfig;
+    use crate::gadget::library::cache::{BinaryFingerprint, LibraryFingerprint};
+    use crate::gadget::library::{GadgetEntry, GadgetLibrary, LibraryImage};
+
+    #[test]
+    fn test_cache_round_trip() {
+        let spaces = ["const", "ram", "register"]
+            .into_iter()
+            .enumerate()
+            .map(|(index, name)| SpaceInfo {
+                name: name.to_string(),
+                index,
+                index_size_bytes: 8,
+                word_size_bytes: 1,
+                _type: SpaceType::IPTR_PROCESSOR,
+                endianness: SleighEndianness::Little,
+            });
+        let rax = VarNode {
+            space_index: 2,
+            offset: 0,
+            size: 8,
+        };
+        let registers = std::iter::once((rax.clone(), "RAX".to_string()));
+        let info = SleighArchInfo::new("test".to_string(), registers, spaces, 1, vec![]);
+        let library = GadgetLibrary {
+            gadgets: vec![GadgetEntry {
+                library: 0,
+                length: 5,
+                address: 0x1234,
+            }],
+            sleighs: vec![],
+            decoded: Default::default(),
+            arch_info: info,
+            language_id: "test".to_string(),
+            images: vec![LibraryImage {
+                path: "libc.so.6".to_string(),
+                base_address: 0x1000,
+                ranges: vec![(0x1000, 0x2000)],
+            }],
+        };
+        let fingerprint = LibraryFingerprint {
+            max_gadget_length: 4,
+            operation_blacklist: vec![1, 2],
+            ghidra_path: "/opt/ghidra".to_string(),
+            language_id: "x86:LE:64:default".to_string(),
+            binaries: vec![BinaryFingerprint {
+                path: "libc.so.6".to_string(),
+                base_address: None,
+                hash: 0x1234,
+            }],
+        };
+        let path = std::env::temp_dir().join(format!("crackers-cache-{}", std::process::id()));
+        library
+            .save_cache(&path, &GadgetLibraryConfig::default(), &fingerprint)
+            .unwrap();
+
+        let loaded = GadgetLibrary::load_cache(&path, &fingerprint, &[])
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.language_id, "test");
+        assert_eq!(loaded.gadgets, library.gadgets);
+        assert_eq!(loaded.images, library.images);
+        assert_eq!(loaded.arch_info.register("RAX"), Some(&rax));
+        assert_eq!(loaded.arch_info.spaces(), library.arch_info.spaces());
+
+        let mut stale = fingerprint.clone();
+        stale.max_gadget_length = 5;
+        assert!(
+            GadgetLibrary::load_cache(&path, &stale, &[])
+                .unwrap()
+                .is_none()
+        );
+        let mut stale = fingerprint.clone();
+        stale.language_id = "x86:LE:32:default".to_string();
+        assert!(
+            GadgetLibrary::load_cache(&path, &stale, &[])
+                .unwrap()
+                .is_none()
+        );
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -2,7 +2,9 @@ use crate::error::CrackersError;
 use jingle::JingleError::Sleigh;
 use jingle::sleigh::JingleSleighError::ImageLoadError;
 use jingle::sleigh::VarNode;
-use jingle::sleigh::context::image::{ImageProvider, ImageSection, ImageSectionIterator, Perms};
+use jingle::sleigh::context::image::{
+    ImageSection, ImageSectionIterator, ImageSections, Perms, SleighImageCore,
+};
 use object::elf::{PF_R, PF_W, PF_X};
 use object::macho::{VM_PROT_EXECUTE, VM_PROT_READ, VM_PROT_WRITE};
 use object::pe::{IMAGE_SCN_MEM_EXECUTE, IMAGE_SCN_MEM_READ, IMAGE_SCN_MEM_WRITE};
@@ -20,7 +22,7 @@ impl<'a> From<&'a ImageSegment> for ImageSection<'a> {
     fn from(value: &'a ImageSegment) -> Self {
         ImageSection {
             data: value.data.as_slice(),
-            perms: value.perms.clone(),
+            perms: value.perms,
             base_address: value.base_address,
         }
     }
@@ -82,15 +84,26 @@ impl SegmentFile {
         }
         Ok(Self { segments })
     }
+
+    /// The byte at the given address, if it falls within one of the loaded segments.
+    pub fn read_byte(&self, address: u64) -> Option<u8> {
+        let address = address as usize;
+        self.segments.iter().find_map(|s| {
+            address
+                .checked_sub(s.base_address)
+                .and_then(|offset| s.data.get(offset))
+                .copied()
+        })
+    }
 }
 
-impl ImageProvider for SegmentFile {
+impl SleighImageCore for SegmentFile {
     fn load(&self, vn: &VarNode, output: &mut [u8]) -> usize {
         let mut written = 0;
         output.fill(0);
         let output_start_addr = vn.offset as usize;
         let output_end_addr = output_start_addr + vn.size;
-        if let Some(x) = self.get_section_info().find(|s| {
+        if let Some(x) = self.image_sections().find(|s| {
             output_start_addr >= s.base_address
                 && output_start_addr < (s.base_address + s.data.len())
         }) {
@@ -113,13 +126,15 @@ impl ImageProvider for SegmentFile {
     }
 
     fn has_full_range(&self, vn: &VarNode) -> bool {
-        self.get_section_info().any(|s| {
+        self.image_sections().any(|s| {
             s.base_address <= vn.offset as usize
                 && (s.base_address + s.data.len()) >= (vn.offset as usize + vn.size)
         })
     }
+}
 
-    fn get_section_info(&self) -> ImageSectionIterator<'_> {
+impl ImageSections for SegmentFile {
+    fn image_sections(&self) -> ImageSectionIterator<'_> {
         ImageSectionIterator::new(self.segments.iter().map(ImageSection::from))
     }
 }
@@ -5,21 +5,121 @@ use jingle::sleigh::{Instruction, SleighArchInfo};
 use rand::SeedableRng;
 use rand::rngs::StdRng;
 use rand::seq::IndexedRandom;
+use serde::{Deserialize, Serialize};
 use std::borrow::Borrow;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use tracing::{Level, event};
 
-use crate::gadget::Gadget;
 use crate::gadget::another_iterator::TraceCandidateIterator;
 use crate::gadget::library::builder::GadgetLibraryConfig;
+use crate::gadget::{Gadget, uses_blacklisted_op};
 
 pub mod builder;
+pub mod cache;
 pub mod image;
 
+/// Where a binary contributing gadgets to a [`GadgetLibrary`] was placed in memory.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LibraryImage {
+    pub path: String,
+    /// The load base of the image after any rebasing, i.e. the address its lowest
+    /// segment was mapped at during synthesis.
+    pub base_address: u64,
+    /// The address ranges of the executable segments of the image, as placed.
+    pub ranges: Vec<(u64, u64)>,
+}
+
+impl LibraryImage {
+    pub fn contains(&self, address: u64) -> bool {
+        self.ranges
+            .iter()
+            .any(|(start, end)| address >= *start && address < *end)
+    }
+}
+
+/// The number of decoded gadgets a [`GadgetLibrary`] keeps around.
+const DECODED_CAPACITY: usize = 1 << 16;
+
+/// The number of independently locked shards the decoded gadgets are split between.
+const DECODED_SHARDS: usize = 64;
+
+/// A gadget of a [`GadgetLibrary`], as the range of bytes it spans in one of the library's
+/// images. It is decoded and lifted on demand.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub(crate) struct GadgetEntry {
+    /// The index of the loaded image the gadget is in.
+    library: u32,
+    /// The length of the gadget in bytes.
+    length: u32,
+    address: u64,
+}
+
+/// The most recently decoded gadgets of one shard of a library's cache, evicted in insertion
+/// order.
+#[derive(Debug, Default)]
+struct DecodedGadgets {
+    gadgets: HashMap<GadgetEntry, Arc<Gadget>>,
+    order: VecDeque<GadgetEntry>,
+}
+
+impl DecodedGadgets {
+    fn get(&self, entry: &GadgetEntry) -> Option<Arc<Gadget>> {
+        self.gadgets.get(entry).cloned()
+    }
+
+    fn insert(&mut self, entry: GadgetEntry, gadget: Arc<Gadget>) {
+        if self.gadgets.insert(entry, gadget).is_none() {
+            self.order.push_back(entry);
+        }
+        while self.order.len() > DECODED_CAPACITY / DECODED_SHARDS {
+            if let Some(old) = self.order.pop_front() {
+                self.gadgets.remove(&old);
+            }
+        }
+    }
+}
+
+/// The decoded gadgets of a library, sharded by entry so that threads decoding different gadgets
+/// rarely wait on each other.
+#[derive(Debug)]
+struct DecodedCache {
+    shards: Vec<Mutex<DecodedGadgets>>,
+}
+
+impl Default for DecodedCache {
+    fn default() -> Self {
+        Self {
+            shards: (0..DECODED_SHARDS).map(|_| Default::default()).collect(),
+        }
+    }
+}
+
+impl DecodedCache {
+    fn shard(&self, entry: &GadgetEntry) -> &Mutex<DecodedGadgets> {
+        let mut hasher = DefaultHasher::new();
+        entry.hash(&mut hasher);
+        &self.shards[hasher.finish() as usize % self.shards.len()]
+    }
+}
+
+/// The gadgets of one or more loaded images.
+///
+/// Gadgets are stored as compact [`GadgetEntry`]s over the images they were found in, and
+/// only decoded into [`Gadget`]s when the search asks for them. The library holds on to the
+/// sleigh contexts of its images to do so, and keeps a bounded cache of decoded gadgets. Each
+/// context owns the bytes of its image as a [`SegmentFile`](image::SegmentFile), so the library
+/// does not borrow from the files it was loaded from.
 #[derive(Clone, Debug)]
 pub struct GadgetLibrary {
-    pub(crate) gadgets: Vec<Gadget>,
+    pub(crate) gadgets: Vec<GadgetEntry>,
+    sleighs: Vec<Arc<LoadedSleighContext<'static>>>,
+    decoded: Arc<DecodedCache>,
     arch_info: SleighArchInfo,
     pub(crate) language_id: String,
+    pub(crate) images: Vec<LibraryImage>,
 }
 
 impl AsRef<SleighArchInfo> for GadgetLibrary {
@@ -36,93 +136,371 @@ impl GadgetLibrary {
     pub(crate) fn arch_info(&self) -> SleighArchInfo {
         self.arch_info.clone()
     }
-    pub fn get_random_candidates_for_trace<'a, S: Borrow<SleighArchInfo>>(
-        &'a self,
+
+    pub fn images(&self) -> &[LibraryImage] {
+        &self.images
+    }
+
+    /// The `index`th gadget of the library, or `None` if there is no such gadget or it could not
+    /// be decoded.
+    pub fn gadget(&self, index: usize) -> Option<Arc<Gadget>> {
+        self.gadgets.get(index).and_then(|e| self.decode(e))
+    }
+
+    /// Decodes the gadget at `entry`, or takes it from the cache if it was decoded recently. The
+    /// cache shard of `entry` stays locked meanwhile, so that no gadget is decoded twice.
+    fn decode(&self, entry: &GadgetEntry) -> Option<Arc<Gadget>> {
+        let mut decoded = self.decoded.shard(entry).lock().unwrap();
+        if let Some(gadget) = decoded.get(entry) {
+            return Some(gadget);
+        }
+        let sleigh = self.sleighs.get(entry.library as usize)?;
+        let mut instructions = vec![];
+        let mut length = 0;
+        // the entry records the gadget's length in bytes, not instructions
+        for instr in sleigh.read(entry.address, usize::MAX) {
+            length += instr.length;
+            instructions.push(instr);
+            if length >= entry.length as usize {
+                break;
+            }
+        }
+        if length != entry.length as usize {
+            event!(
+                Level::WARN,
+                "Gadget at {:#x} no longer decodes to {} bytes",
+                entry.address,
+                entry.length
+            );
+            return None;
+        }
+        let gadget = Arc::new(Gadget {
+            code_space_idx: sleigh.arch_info().default_code_space_index(),
+            instructions,
+        });
+        decoded.insert(*entry, gadget.clone());
+        Some(gadget)
+    }
+
+    /// A library of already decoded `gadgets`, for tests without a binary to load them from.
+    #[cfg(test)]
+    pub(crate) fn from_gadgets(arch_info: SleighArchInfo, gadgets: Vec<Gadget>) -> Self {
+        let decoded = DecodedCache::default();
+        let entries = gadgets
+            .into_iter()
+            .map(|gadget| {
+                let entry = GadgetEntry {
+                    library: 0,
+                    length: gadget.instructions.iter().map(|i| i.length as u32).sum(),
+                    address: gadget.address(),
+                };
+                let shard = decoded.shard(&entry);
+                shard.lock().unwrap().insert(entry, Arc::new(gadget));
+                entry
+            })
+            .collect();
+        GadgetLibrary {
+            gadgets: entries,
+            sleighs: vec![],
+            decoded: Arc::new(decoded),
+            arch_info,
+            language_id: "test".to_string(),
+            images: vec![],
+        }
+    }
+
+    /// Removes every gadget whose address contains one of `bad_bytes`, as such gadgets can never
+    /// be placed in a payload.
+    pub fn remove_bad_addresses(&mut self, bad_bytes: &[u8]) {
+        if bad_bytes.is_empty() {
+            return;
+        }
+        let width = self
+            .arch_info
+            .get_space(self.arch_info.default_code_space_index())
+            .map(|s| s.index_size_bytes as usize)
+            .unwrap_or(8);
+        let before = self.gadgets.len();
+        self.gadgets
+            .retain(|g| !has_bad_byte(g.address, width, bad_bytes));
+        event!(
+            Level::INFO,
+            "Removed {} gadgets with bad bytes in their address",
+            before - self.gadgets.len()
+        );
+    }
+
+    pub fn get_random_candidates_for_trace<S: Borrow<SleighArchInfo>>(
+        &self,
+        info: S,
+        trace: &[ModeledInstruction],
+        seed: i64,
+    ) -> impl Iterator<Item = Vec<Option<Arc<Gadget>>>> {
+        self.get_random_candidates_for_trace_from(info, trace, seed, 0)
+    }
+
+    /// Like [`get_random_candidates_for_trace`](Self::get_random_candidates_for_trace), but
+    /// resumes the shuffled library after its first `start` gadgets.
+    pub(crate) fn get_random_candidates_for_trace_from<S: Borrow<SleighArchInfo>>(
+        &self,
         info: S,
         trace: &[ModeledInstruction],
         seed: i64,
-    ) -> impl Iterator<Item = Vec<Option<&'a Gadget>>> {
+        start: usize,
+    ) -> TraceCandidateIterator<impl Iterator<Item = Arc<Gadget>>> {
         let mut rng = StdRng::seed_from_u64(seed as u64);
-        let r = self.gadgets.choose_multiple(&mut rng, self.gadgets.len());
+        let r = self
+            .gadgets
+            .choose_multiple(&mut rng, self.gadgets.len())
+            .skip(start)
+            .filter_map(|e| self.decode(e));
         TraceCandidateIterator::new(info, r, trace.to_vec())
     }
-    pub(super) fn build_from_image(
-        sleighs: Vec<LoadedSleighContext>,
+
+    /// Scans the executable sections of `sleighs` for gadgets, in parallel.
+    ///
+    /// Sections are split into chunks of at most [`SCAN_CHUNK_SIZE`] bytes, which are handed
+    /// out to one worker per available core. A sleigh context decodes one instruction at a
+    /// time, so every worker but the first decodes from a copy of its own. `fork(library,
+    /// previous)` loads a copy of the context of `library`, reusing the worker's `previous` copy
+    /// of another library if it has one; if it returns `None`, the worker shares the context in
+    /// `sleighs`. The gadgets are kept in the order a single-threaded scan would find them in,
+    /// so that the library shuffles the same way for the same seed.
+    pub(super) fn build_from_image<F>(
+        sleighs: Vec<Arc<LoadedSleighContext<'static>>>,
+        images: Vec<LibraryImage>,
         builder: &GadgetLibraryConfig,
-    ) -> Result<Self, JingleError> {
+        fork: F,
+    ) -> Result<Self, JingleError>
+    where
+        F: Fn(usize, Option<LoadedSleighContext<'static>>) -> Option<LoadedSleighContext<'static>>
+            + Sync,
+    {
         // We expect at least one sleigh (the primary library) to be provided.
         // Use the first sleigh's arch info / language id as the library-wide info.
-        let mut iter = sleighs.into_iter();
-        let first = iter.next().unwrap();
-        let mut lib: GadgetLibrary = GadgetLibrary {
+        let first = &sleighs[0];
+        let mut lib = GadgetLibrary {
             gadgets: vec![],
             arch_info: first.arch_info().clone(),
             language_id: first.get_language_id().to_string(),
+            sleighs: vec![],
+            decoded: Default::default(),
+            images: vec![],
         };
 
-        event!(Level::INFO, "Loading gadgets from sleighs");
-
-        // process the first sleigh
-        for section in first.get_sections().filter(|s| s.perms.exec) {
-            let start = section.base_address as u64;
-            let end = start + section.data.len() as u64;
-            let mut curr = start;
-
-            while curr < end {
-                let instrs: Vec<Instruction> =
-                    first.read(curr, builder.max_gadget_length).collect();
-                if let Some(i) = instrs.iter().position(|b| b.terminates_basic_block()) {
-                    let gadget = Gadget {
-                        code_space_idx: first.arch_info().default_code_space_index(),
-                        spaces: first.arch_info().spaces().to_vec(),
-                        instructions: instrs[0..=i].to_vec(),
-                    };
-                    if !gadget.has_blacklisted_op(&builder.operation_blacklist) {
-                        lib.gadgets.push(gadget);
-                    }
+        let mut sections = vec![];
+        let mut progress = vec![];
+        for (library, sleigh) in sleighs.iter().enumerate() {
+            let mut total = 0;
+            for section in sleigh.get_sections().filter(|s| s.perms.exec) {
+                let start = section.base_address as u64;
+                let end = start + section.data.len() as u64;
+                total += end - start;
+                sections.push((library, start, end));
+            }
+            let path = images.get(library).map(|i| i.path.as_str());
+            progress.push(ScanProgress::new(path.unwrap_or("library"), total));
+        }
+        let chunks = scan_chunks(sections);
+        let workers = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .clamp(1, chunks.len().max(1));
+        event!(
+            Level::INFO,
+            "Scanning {} libraries for gadgets on {} threads",
+            sleighs.len(),
+            workers
+        );
+
+        // the library each worker's copy is of, and the copy itself
+        type SleighCopy = Option<(usize, Option<LoadedSleighContext<'static>>)>;
+        let scan = |(worker, copy): &mut (usize, SleighCopy), chunk: &ScanChunk| {
+            if *worker != 0 && copy.as_ref().is_none_or(|(l, _)| *l != chunk.library) {
+                let previous = copy.take().and_then(|(_, c)| c);
+                let forked = fork(chunk.library, previous);
+                if forked.is_none() {
+                    event!(
+                        Level::WARN,
+                        "Could not copy the sleigh context of {}, so scan thread {} shares it",
+                        progress[chunk.library].path,
+                        worker
+                    );
                 }
-                curr += 1
+                *copy = Some((chunk.library, forked));
             }
-            event!(Level::INFO, "Found {} gadgets...", lib.gadgets.len());
+            let sleigh = copy
+                .as_ref()
+                .and_then(|(_, c)| c.as_ref())
+                .unwrap_or(&sleighs[chunk.library]);
+            let gadgets = chunk.scan(sleigh, builder);
+            progress[chunk.library].record(chunk.end - chunk.start, gadgets.len());
+            gadgets
+        };
+        lib.gadgets = scan_in_order(&chunks, workers, |worker| (worker, None), scan);
+        for progress in &progress {
+            event!(
+                Level::INFO,
+                "Found {} gadgets in {}",
+                progress.found.load(Ordering::Relaxed),
+                progress.path
+            );
         }
+        lib.sleighs = sleighs;
+        lib.images = images;
 
-        // process remaining sleighs (additional loaded libraries)
-        for sleigh in iter {
-            for section in sleigh.get_sections().filter(|s| s.perms.exec) {
-                let start = section.base_address as u64;
-                let end = start + section.data.len() as u64;
-                let mut curr = start;
-
-                while curr < end {
-                    let instrs: Vec<Instruction> =
-                        sleigh.read(curr, builder.max_gadget_length).collect();
-                    if let Some(i) = instrs.iter().position(|b| b.terminates_basic_block()) {
-                        let gadget = Gadget {
-                            code_space_idx: sleigh.arch_info().default_code_space_index(),
-                            spaces: sleigh.arch_info().spaces().to_vec(),
-                            instructions: instrs[0..=i].to_vec(),
+        Ok(lib)
+    }
+}
+
+/// The largest number of bytes of a section that are scanned as one unit of work.
+const SCAN_CHUNK_SIZE: u64 = 0x4000;
+
+/// A range of start addresses in an executable section of a library.
+#[derive(Debug, PartialEq, Eq)]
+struct ScanChunk {
+    library: usize,
+    start: u64,
+    end: u64,
+}
+
+/// Splits each `(library, start, end)` section into chunks of at most [`SCAN_CHUNK_SIZE`]
+/// bytes, in order.
+fn scan_chunks<I: IntoIterator<Item = (usize, u64, u64)>>(sections: I) -> Vec<ScanChunk> {
+    sections
+        .into_iter()
+        .flat_map(|(library, start, end)| {
+            (start..end)
+                .step_by(SCAN_CHUNK_SIZE as usize)
+                .map(move |s| ScanChunk {
+                    library,
+                    start: s,
+                    end: end.min(s + SCAN_CHUNK_SIZE),
+                })
+        })
+        .collect()
+}
+
+/// Runs `scan` over every chunk on `workers` threads, each taking the next chunk as it
+/// finishes one, with state of its own made by `state(worker)`. The results are concatenated in
+/// chunk order, whatever order the chunks finish in.
+fn scan_in_order<T, S, N, F>(chunks: &[ScanChunk], workers: usize, state: N, scan: F) -> Vec<T>
+where
+    T: Send,
+    N: Fn(usize) -> S + Sync,
+    F: Fn(&mut S, &ScanChunk) -> Vec<T> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let mut found: Vec<Option<Vec<T>>> = (0..chunks.len()).map(|_| None).collect();
+    std::thread::scope(|s| {
+        let handles: Vec<_> = (0..workers.max(1))
+            .map(|worker| {
+                let (next, state, scan) = (&next, &state, &scan);
+                s.spawn(move || {
+                    let mut state = state(worker);
+                    let mut found = vec![];
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(chunk) = chunks.get(index) else {
+                            break;
                         };
-                        if !gadget.has_blacklisted_op(&builder.operation_blacklist) {
-                            lib.gadgets.push(gadget);
-                        }
+                        found.push((index, scan(&mut state, chunk)));
                     }
-                    curr += 1
+                    found
+                })
+            })
+            .collect();
+        for handle in handles {
+            for (index, results) in handle.join().expect("gadget scan thread panicked") {
+                found[index] = Some(results);
+            }
+        }
+    });
+    found.into_iter().flatten().flatten().collect()
+}
+
+impl ScanChunk {
+    /// The gadgets starting in this chunk, in address order, leaving out those that use a
+    /// blacklisted operation.
+    fn scan(
+        &self,
+        sleigh: &LoadedSleighContext,
+        builder: &GadgetLibraryConfig,
+    ) -> Vec<GadgetEntry> {
+        let mut gadgets = vec![];
+        for curr in self.start..self.end {
+            let instrs: Vec<Instruction> = sleigh.read(curr, builder.max_gadget_length).collect();
+            if let Some(i) = instrs.iter().position(|b| b.terminates_basic_block()) {
+                let instructions = &instrs[0..=i];
+                if !uses_blacklisted_op(instructions, &builder.operation_blacklist) {
+                    gadgets.push(GadgetEntry {
+                        library: self.library as u32,
+                        length: instructions.iter().map(|i| i.length).sum::<usize>() as u32,
+                        address: curr,
+                    });
                 }
-                event!(Level::INFO, "Found {} gadgets...", lib.gadgets.len());
             }
         }
+        gadgets
+    }
+}
 
-        Ok(lib)
+/// How far the scan of one library has come.
+struct ScanProgress<'a> {
+    path: &'a str,
+    /// The number of bytes of executable sections in the library.
+    total: u64,
+    scanned: AtomicU64,
+    found: AtomicUsize,
+}
+
+impl<'a> ScanProgress<'a> {
+    fn new(path: &'a str, total: u64) -> Self {
+        Self {
+            path,
+            total,
+            scanned: AtomicU64::new(0),
+            found: AtomicUsize::new(0),
+        }
+    }
+
+    /// Records a scanned chunk of `bytes` bytes holding `found` gadgets, logging each time
+    /// another tenth of the library is done.
+    fn record(&self, bytes: u64, found: usize) {
+        let found = self.found.fetch_add(found, Ordering::Relaxed) + found;
+        let scanned = self.scanned.fetch_add(bytes, Ordering::Relaxed) + bytes;
+        let tenths = scanned * 10 / self.total;
+        if tenths > (scanned - bytes) * 10 / self.total {
+            event!(
+                Level::INFO,
+                "Scanned {}% of {} ({} gadgets so far)",
+                tenths * 10,
+                self.path,
+                found
+            );
+        }
     }
 }
 
+/// Whether the lowest `width` bytes of `address` contain one of `bad_bytes`.
+fn has_bad_byte(address: u64, width: usize, bad_bytes: &[u8]) -> bool {
+    address.to_le_bytes()[..width.min(8)]
+        .iter()
+        .any(|b| bad_bytes.contains(b))
+}
+
 #[cfg(test)]
 mod tests {
     use std::fs;
     use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
 
-    use crate::gadget::library::GadgetLibrary;
     use crate::gadget::library::builder::GadgetLibraryConfig;
+    use crate::gadget::library::image::SegmentFile;
+    use crate::gadget::library::{
+        GadgetLibrary, SCAN_CHUNK_SIZE, ScanChunk, has_bad_byte, scan_chunks, scan_in_order,
+    };
     use jingle::sleigh::context::SleighContextBuilder;
     use object::File;
 
@@ -135,9 +513,84 @@ mod tests {
         let data = fs::read(path).unwrap();
         let file = File::parse(&*data).unwrap();
         let sleigh = builder.build("x86:LE:64:default").unwrap();
-        let bin_sleigh = sleigh.initialize_with_image(file).unwrap();
-        let _lib =
-            GadgetLibrary::build_from_image(vec![bin_sleigh], &GadgetLibraryConfig::default())
-                .unwrap();
+        let bin_sleigh = sleigh
+            .initialize_with_image(SegmentFile::new(&file).unwrap())
+            .unwrap();
+        let _lib = GadgetLibrary::build_from_image(
+            vec![Arc::new(bin_sleigh)],
+            vec![],
+            &GadgetLibraryConfig::default(),
+            |_, _| None,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_has_bad_byte() {
+        assert!(has_bad_byte(0x0040_1020, 4, &[0x00]));
+        assert!(!has_bad_byte(0x4142_4344, 4, &[0x0a]));
+        // only the bytes of a code space address are considered
+        assert!(!has_bad_byte(0x4142_4344, 4, &[0x00]));
+        assert!(has_bad_byte(0x4142_4344, 8, &[0x00]));
+        assert!(has_bad_byte(0x4142_0a44, 4, &[0x0a]));
+    }
+
+    #[test]
+    fn test_scan_chunks() {
+        let chunk = |library, start, end| ScanChunk {
+            library,
+            start,
+            end,
+        };
+        let size = SCAN_CHUNK_SIZE;
+        assert_eq!(
+            scan_chunks([
+                (0, 0x1000, 0x1000 + size + 1),
+                (1, 0x1000, 0x1010),
+                (1, 0x2000, 0x2000)
+            ]),
+            vec![
+                chunk(0, 0x1000, 0x1000 + size),
+                chunk(0, 0x1000 + size, 0x1000 + size + 1),
+                chunk(1, 0x1000, 0x1010),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scan_in_order() {
+        let sections: Vec<_> = (0..3)
+            .map(|library| (library, 0x1000, 0x1000 + 5 * SCAN_CHUNK_SIZE + 7))
+            .collect();
+        let chunks = scan_chunks(sections);
+        // a stand-in for the gadgets of a chunk: some of its addresses, tagged with the library
+        let gadgets = |chunk: &ScanChunk| -> Vec<(usize, u64)> {
+            let library = chunk.library;
+            (chunk.start..chunk.end)
+                .step_by(0x1001)
+                .map(|a| (library, a))
+                .collect()
+        };
+        let serial: Vec<_> = chunks.iter().flat_map(gadgets).collect();
+
+        let finished = Mutex::new(vec![]);
+        let parallel = scan_in_order(
+            &chunks,
+            4,
+            |worker| worker,
+            |_, chunk| {
+                // the first chunks take longest, so that they finish after later ones
+                let index = chunks.iter().position(|c| c == chunk).unwrap();
+                std::thread::sleep(Duration::from_millis(
+                    20u64.saturating_sub(5 * index as u64),
+                ));
+                finished.lock().unwrap().push(index);
+                gadgets(chunk)
+            },
+        );
+        let finished = finished.into_inner().unwrap();
+        assert!(!finished.is_sorted());
+        assert_eq!(finished.len(), chunks.len());
+        assert_eq!(parallel, serial);
     }
 }
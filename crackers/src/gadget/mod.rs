@@ -1,5 +1,5 @@
 use jingle::modeling::ModeledBlock;
-use jingle::sleigh::{Instruction, OpCode, PcodeOperation, SleighArchInfo, SpaceInfo};
+use jingle::sleigh::{Instruction, OpCode, PcodeOperation, SleighArchInfo, VarNode};
 use serde::{Deserialize, Serialize};
 use std::borrow::Borrow;
 use std::collections::HashSet;
@@ -16,14 +16,20 @@ pub mod signature;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Gadget {
-    // todo: This is obviously not ideal, but it's not that much extra data and
-    // I'd rather not deal with another lifetime
-    spaces: Vec<SpaceInfo>,
     code_space_idx: usize,
     pub instructions: Vec<Instruction>,
 }
 
 impl Gadget {
+    /// A gadget of already decoded `instructions`, for tests without a binary to decode them from.
+    #[cfg(test)]
+    pub(crate) fn new(code_space_idx: usize, instructions: Vec<Instruction>) -> Self {
+        Self {
+            code_space_idx,
+            instructions,
+        }
+    }
+
     pub fn address(&self) -> u64 {
         self.instructions.first().map(|f| f.address).unwrap()
     }
@@ -43,10 +49,44 @@ impl Gadget {
         }
     }
 
-    pub fn has_blacklisted_op(&self, blacklist: &HashSet<OpCode>) -> bool {
-        self.instructions
+    /// Whether `other` does the same as this gadget from its own address, so that either may
+    /// stand in for the other in a chain.
+    pub fn equivalent(&self, other: &Self) -> bool {
+        self.equivalence_key() == other.equivalence_key()
+    }
+
+    /// The p-code of each instruction, with the targets of direct branches and calls into the
+    /// code space taken relative to the gadget's address. Gadgets with equal keys differ only in
+    /// where they are. Other constants (e.g. return addresses pushed by a call) are kept as is,
+    /// so such gadgets are only equivalent to themselves.
+    pub fn equivalence_key(&self) -> EquivalenceKey {
+        let base = self.address();
+        let relative = |dest: &mut VarNode| {
+            if dest.space_index == self.code_space_idx {
+                dest.offset = dest.offset.wrapping_sub(base);
+            }
+        };
+        let ops = self
+            .instructions
             .iter()
-            .any(|i| i.ops.iter().any(|o| blacklist.contains(&o.opcode())))
+            .map(|i| {
+                let mut ops = i.ops.clone();
+                for op in &mut ops {
+                    match op {
+                        PcodeOperation::Branch { input } => relative(input),
+                        PcodeOperation::CBranch { input0, .. } => relative(input0),
+                        PcodeOperation::Call { dest, .. } => relative(dest),
+                        _ => {}
+                    }
+                }
+                ops
+            })
+            .collect();
+        EquivalenceKey(ops)
+    }
+
+    pub fn has_blacklisted_op(&self, blacklist: &HashSet<OpCode>) -> bool {
+        uses_blacklisted_op(&self.instructions, blacklist)
     }
 
     pub fn model<I: Borrow<SleighArchInfo>>(
@@ -58,6 +98,20 @@ impl Gadget {
     }
 }
 
+/// Whether any of `instructions` uses an operation in `blacklist`.
+pub(crate) fn uses_blacklisted_op(
+    instructions: &[Instruction],
+    blacklist: &HashSet<OpCode>,
+) -> bool {
+    instructions
+        .iter()
+        .any(|i| i.ops.iter().any(|o| blacklist.contains(&o.opcode())))
+}
+
+/// Identifies the gadgets [equivalent](Gadget::equivalent) to a gadget.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct EquivalenceKey(Vec<Vec<PcodeOperation>>);
+
 impl Display for Gadget {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         for x in &self.instructions {
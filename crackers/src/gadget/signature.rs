@@ -46,6 +46,27 @@ impl PartialOrd<GadgetSignature> for GadgetSignature {
     }
 }
 impl GadgetSignature {
+    pub(crate) fn from_gadget<T: Borrow<SleighArchInfo>>(value: &Gadget, t: T) -> Self {
+        let t = t.borrow();
+        let mut outputs = Vec::new();
+        for op in value.ops() {
+            if let Some(op) = op.output() {
+                if let GeneralizedVarNode::Direct(v) = &op {
+                    // todo: fix this once the new syntax is in stable
+                    #[allow(clippy::collapsible_if)]
+                    if let Some(h) = t.get_space(v.space_index) {
+                        if h._type == SpaceType::IPTR_PROCESSOR {
+                            outputs.push(op);
+                        }
+                    }
+                } else {
+                    outputs.push(op);
+                }
+            }
+        }
+        Self { outputs }
+    }
+
     pub(crate) fn from_instr<T: Borrow<SleighArchInfo>>(value: &Instruction, t: T) -> Self {
         let t = t.borrow();
         let mut outputs = Vec::new();
@@ -85,28 +106,6 @@ impl From<&ModeledBlock> for GadgetSignature {
     }
 }
 
-impl From<&Gadget> for GadgetSignature {
-    fn from(value: &Gadget) -> Self {
-        let mut outputs = Vec::new();
-        for op in value.instructions.iter().flat_map(|i| &i.ops) {
-            if let Some(op) = op.output() {
-                if let GeneralizedVarNode::Direct(v) = &op {
-                    // todo: fix this once the new syntax is in stable
-                    #[allow(clippy::collapsible_if)]
-                    if let Some(h) = value.spaces.get(v.space_index) {
-                        if h._type == SpaceType::IPTR_PROCESSOR {
-                            outputs.push(op);
-                        }
-                    }
-                } else {
-                    outputs.push(op);
-                }
-            }
-        }
-        Self { outputs }
-    }
-}
-
 fn varnode_set_covers(our_set: &[GeneralizedVarNode], other_set: &[GeneralizedVarNode]) -> bool {
     let mut self_dir_sig = VarNodeSet::default();
     let self_indirect: Vec<&IndirectVarNode> = our_set
@@ -6,3 +6,5 @@ pub mod error;
 pub mod gadget;
 mod reference_program;
 pub mod synthesis;
+#[cfg(test)]
+mod test_util;
@@ -33,6 +33,15 @@ impl Display for ReferenceProgram {
 }
 
 impl ReferenceProgram {
+    /// A program of `steps`, starting from an empty memory valuation.
+    #[cfg(test)]
+    pub(crate) fn from_steps(steps: Vec<Step>) -> Self {
+        Self {
+            steps,
+            initial_memory: MemoryValuation::default(),
+        }
+    }
+
     pub fn try_load(
         spec: &SpecificationConfig,
         sleigh_config: &SleighConfig,
@@ -9,6 +9,19 @@ use crate::error::CrackersError;
 pub struct MemoryValuation(pub(super) HashMap<VarNode, Vec<u8>>);
 
 impl MemoryValuation {
+    /// The value this valuation assigns to a single byte, if any.
+    pub fn read_byte(&self, space_index: usize, offset: u64) -> Option<u8> {
+        self.0.iter().find_map(|(vn, value)| {
+            if vn.space_index != space_index {
+                return None;
+            }
+            offset
+                .checked_sub(vn.offset)
+                .and_then(|i| value.get(i as usize))
+                .copied()
+        })
+    }
+
     pub fn to_constraint(&self) -> impl Fn(&State) -> Result<Bool, CrackersError> {
         let map = self.0.clone();
         move |state| {
@@ -1,6 +1,8 @@
 use crate::error::CrackersError;
 use crate::gadget::Gadget;
+use crate::gadget::library::LibraryImage;
 use crate::reference_program::ReferenceProgram;
+use crate::synthesis::SynthesisProgress;
 use crate::synthesis::assignment_model::AssignmentModel;
 use crate::synthesis::builder::{StateConstraintGenerator, TransitionConstraintGenerator};
 use crate::synthesis::pcode_theory::pcode_assignment::PcodeAssignment;
@@ -15,10 +17,17 @@ use z3::Solver;
 pub struct AssignmentModelBuilder {
     pub templates: ReferenceProgram,
     pub gadgets: Vec<Gadget>,
+    /// The gadgets the theory checked, if `gadgets` are other members of their classes. The
+    /// model is built from them instead if `gadgets` turn out not to satisfy the constraints.
+    pub fallback: Option<Vec<Gadget>>,
     pub preconditions: Vec<Arc<StateConstraintGenerator>>,
     pub postconditions: Vec<Arc<StateConstraintGenerator>>,
     pub pointer_invariants: Vec<Arc<TransitionConstraintGenerator>>,
     pub arch_info: SleighArchInfo,
+    pub libraries: Vec<LibraryImage>,
+    pub bad_bytes: Vec<u8>,
+    /// Statistics of the search that found this chain.
+    pub statistics: SynthesisProgress,
 }
 
 impl Debug for AssignmentModelBuilder {
@@ -26,7 +35,11 @@ impl Debug for AssignmentModelBuilder {
         f.debug_struct("AssignmentModelBuilder")
             .field("templates", &self.templates)
             .field("gadgets", &self.gadgets)
+            .field("fallback", &self.fallback)
             .field("arch_info", &self.arch_info)
+            .field("libraries", &self.libraries)
+            .field("bad_bytes", &self.bad_bytes)
+            .field("statistics", &self.statistics)
             .finish()
     }
 }
@@ -53,6 +66,7 @@ impl AssignmentModelBuilder {
             self.preconditions.clone(),
             self.postconditions.clone(),
             self.pointer_invariants.clone(),
+            self.bad_bytes.clone(),
         ))
     }
     pub fn build(&self) -> Result<AssignmentModel<ModeledBlock>, CrackersError> {
@@ -60,6 +74,14 @@ impl AssignmentModelBuilder {
 
         let pcode_model = self.make_pcode_model(&self.arch_info)?;
         let s = Solver::new();
-        pcode_model.check(&self.arch_info, &s)
+        match (pcode_model.check(&self.arch_info, &s), &self.fallback) {
+            (Err(_), Some(fallback)) => Self {
+                gadgets: fallback.clone(),
+                fallback: None,
+                ..self.clone()
+            }
+            .build(),
+            (result, _) => result,
+        }
     }
 }
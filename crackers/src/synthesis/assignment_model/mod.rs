@@ -1,4 +1,6 @@
 pub mod builder;
+pub mod payload;
+pub mod pwntools;
 
 use std::fmt::{Display, Formatter};
 
@@ -8,6 +10,9 @@ use jingle::varnode::ResolvedVarnode;
 use z3::ast::BV;
 use z3::{Context, Model, Translate};
 
+use crate::error::CrackersError;
+use crate::synthesis::assignment_model::payload::{Payload, PayloadBuilder};
+
 #[derive(Debug)]
 pub struct AssignmentModel<T: ModelingContext> {
     model: Model,
@@ -84,6 +89,12 @@ impl<T: ModelingContext> AssignmentModel<T> {
     pub fn outputs(&self) -> impl Iterator<Item = ResolvedVarnode> {
         self.gadgets.iter().flat_map(|gadget| gadget.get_outputs())
     }
+
+    /// Lay out the controlled memory of this chain relative to the initial value of the
+    /// given stack pointer register. See [`PayloadBuilder`] for more options.
+    pub fn payload(&self, stack_pointer: &str) -> Result<Payload, CrackersError> {
+        PayloadBuilder::new(stack_pointer).build(self)
+    }
 }
 
 impl<T: ModelingContext + Display> Display for AssignmentModel<T> {
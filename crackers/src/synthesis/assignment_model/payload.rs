@@ -0,0 +1,256 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+use jingle::modeling::ModelingContext;
+use jingle::sleigh::{SleighArchInfo, VarNode};
+use jingle::varnode::ResolvedVarnode;
+#[cfg(feature = "pyo3")]
+use pyo3::pyclass;
+use z3::ast::BV;
+
+use crate::error::CrackersError;
+use crate::synthesis::assignment_model::AssignmentModel;
+
+/// The default upper bound on the size of the reconstructed stack buffer.
+pub const DEFAULT_MAX_PAYLOAD_LENGTH: usize = 0x1000;
+
+/// A run of attacker-controlled bytes that could not be placed in the stack buffer,
+/// either because it lives in another space, below the initial stack pointer, or
+/// past the maximum payload length.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "pyo3", pyclass(get_all))]
+pub struct ControlledLocation {
+    pub space: String,
+    pub address: u64,
+    pub bytes: Vec<u8>,
+}
+
+/// The concrete byte layout of a chain: the contiguous buffer that must be placed at the
+/// initial stack pointer, plus any controlled locations that fall outside of it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "pyo3", pyclass(get_all))]
+pub struct Payload {
+    pub base_address: u64,
+    pub bytes: Vec<u8>,
+    pub external: Vec<ControlledLocation>,
+}
+
+impl Display for Payload {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Stack buffer at {:#x} ({} bytes):",
+            self.base_address,
+            self.bytes.len()
+        )?;
+        for (i, chunk) in self.bytes.chunks(16).enumerate() {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{b:02x}")).collect();
+            writeln!(f, "  +{:04x}: {}", i * 16, hex.join(" "))?;
+        }
+        for loc in &self.external {
+            let hex: Vec<String> = loc.bytes.iter().map(|b| format!("{b:02x}")).collect();
+            writeln!(
+                f,
+                "Outside buffer: {}[{:#x}] = {}",
+                loc.space,
+                loc.address,
+                hex.join(" ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Reconstructs the attacker-controlled memory of a solved chain relative to a stack pointer.
+///
+/// A byte is considered controlled if some gadget reads it before any earlier gadget in the
+/// chain has written it; its value is taken from the chain's initial state.
+#[derive(Debug, Clone)]
+pub struct PayloadBuilder {
+    stack_pointer: String,
+    filler: u8,
+    max_length: usize,
+}
+
+impl PayloadBuilder {
+    pub fn new<T: AsRef<str>>(stack_pointer: T) -> Self {
+        Self {
+            stack_pointer: stack_pointer.as_ref().to_string(),
+            filler: 0,
+            max_length: DEFAULT_MAX_PAYLOAD_LENGTH,
+        }
+    }
+
+    pub fn with_filler(mut self, filler: u8) -> Self {
+        self.filler = filler;
+        self
+    }
+
+    /// Replaces the filler byte with the lowest byte not in `bad_bytes`, if it is one of them.
+    pub fn with_bad_bytes(mut self, bad_bytes: &[u8]) -> Self {
+        if bad_bytes.contains(&self.filler)
+            && let Some(filler) = (0..=u8::MAX).find(|b| !bad_bytes.contains(b))
+        {
+            self.filler = filler;
+        }
+        self
+    }
+
+    pub fn with_max_length(mut self, max_length: usize) -> Self {
+        self.max_length = max_length;
+        self
+    }
+
+    pub fn build<T: ModelingContext>(
+        &self,
+        model: &AssignmentModel<T>,
+    ) -> Result<Payload, CrackersError> {
+        let initial = model
+            .initial_state()
+            .ok_or(CrackersError::EmptyAssignment)?;
+        let sp = model
+            .arch_info
+            .register(&self.stack_pointer)
+            .ok_or(CrackersError::UnknownRegister(self.stack_pointer.clone()))?;
+        let base_address = eval_u64(model, &initial.read_varnode(sp)?)?;
+
+        let mut written: HashSet<(usize, u64)> = HashSet::new();
+        let mut controlled: BTreeMap<(usize, u64), u8> = BTreeMap::new();
+        for gadget in &model.gadgets {
+            for vn in gadget.get_inputs() {
+                if let ResolvedVarnode::Indirect(i) = vn {
+                    let ptr = eval_u64(model, &i.pointer)?;
+                    for offset in 0..i.access_size_bytes as u64 {
+                        let key = (i.pointer_space_idx, ptr.wrapping_add(offset));
+                        if written.contains(&key) || controlled.contains_key(&key) {
+                            continue;
+                        }
+                        let byte = initial.read_varnode(&VarNode {
+                            space_index: key.0,
+                            offset: key.1,
+                            size: 1,
+                        })?;
+                        controlled.insert(key, eval_u64(model, &byte)? as u8);
+                    }
+                }
+            }
+            for vn in gadget.get_outputs() {
+                if let ResolvedVarnode::Indirect(i) = vn {
+                    let ptr = eval_u64(model, &i.pointer)?;
+                    for offset in 0..i.access_size_bytes as u64 {
+                        written.insert((i.pointer_space_idx, ptr.wrapping_add(offset)));
+                    }
+                }
+            }
+        }
+        Ok(self.layout(&model.arch_info, base_address, controlled))
+    }
+
+    fn layout(
+        &self,
+        info: &SleighArchInfo,
+        base_address: u64,
+        controlled: BTreeMap<(usize, u64), u8>,
+    ) -> Payload {
+        let stack_space = info.default_code_space_index();
+        let mut bytes = Vec::new();
+        let mut external: Vec<ControlledLocation> = Vec::new();
+        let mut last: Option<(usize, u64)> = None;
+        for ((space, address), value) in controlled {
+            let offset = address.wrapping_sub(base_address);
+            if space == stack_space && address >= base_address && offset < self.max_length as u64 {
+                let offset = offset as usize;
+                if bytes.len() <= offset {
+                    bytes.resize(offset + 1, self.filler);
+                }
+                bytes[offset] = value;
+                continue;
+            }
+            match external.last_mut() {
+                Some(loc) if last == Some((space, address.wrapping_sub(1))) => {
+                    loc.bytes.push(value)
+                }
+                _ => external.push(ControlledLocation {
+                    space: info
+                        .get_space(space)
+                        .map(|s| s.name.clone())
+                        .unwrap_or_default(),
+                    address,
+                    bytes: vec![value],
+                }),
+            }
+            last = Some((space, address));
+        }
+        Payload {
+            base_address,
+            bytes,
+            external,
+        }
+    }
+}
+
+fn eval_u64<T: ModelingContext>(model: &AssignmentModel<T>, bv: &BV) -> Result<u64, CrackersError> {
+    model
+        .model()
+        .eval(bv, true)
+        .and_then(|v| v.as_u64())
+        .ok_or(CrackersError::ModelParsingError)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use jingle::sleigh::{SleighArchInfo, SleighEndianness, SpaceInfo, SpaceType};
+
+    use crate::synthesis::assignment_model::payload::{ControlledLocation, PayloadBuilder};
+
+    fn make_info() -> SleighArchInfo {
+        let spaces = ["const", "ram"]
+            .into_iter()
+            .enumerate()
+            .map(|(index, name)| SpaceInfo {
+                name: name.to_string(),
+                index,
+                index_size_bytes: 8,
+                word_size_bytes: 1,
+                _type: SpaceType::IPTR_PROCESSOR,
+                endianness: SleighEndianness::Little,
+            });
+        SleighArchInfo::new("test".to_string(), std::iter::empty(), spaces, 1, vec![])
+    }
+
+    #[test]
+    fn test_layout() {
+        let info = make_info();
+        let controlled = BTreeMap::from([
+            ((1, 0xff), 0xaa),
+            ((1, 0x100), 0x01),
+            ((1, 0x101), 0x02),
+            ((1, 0x104), 0x03),
+            ((1, 0x110), 0x04),
+            ((1, 0x111), 0x05),
+        ]);
+        let payload = PayloadBuilder::new("RSP")
+            .with_filler(0x41)
+            .with_max_length(0x10)
+            .layout(&info, 0x100, controlled);
+        assert_eq!(payload.base_address, 0x100);
+        assert_eq!(payload.bytes, vec![0x01, 0x02, 0x41, 0x41, 0x03]);
+        assert_eq!(
+            payload.external,
+            vec![
+                ControlledLocation {
+                    space: "ram".to_string(),
+                    address: 0xff,
+                    bytes: vec![0xaa],
+                },
+                ControlledLocation {
+                    space: "ram".to_string(),
+                    address: 0x110,
+                    bytes: vec![0x04, 0x05],
+                },
+            ]
+        );
+    }
+}
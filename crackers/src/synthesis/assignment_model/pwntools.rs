@@ -0,0 +1,363 @@
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+
+use jingle::display::JingleDisplayable;
+use jingle::modeling::ModelingContext;
+use jingle::sleigh::{SleighEndianness, VarNode};
+use jingle::varnode::ResolvedVarnode;
+
+use crate::error::CrackersError;
+use crate::gadget::library::LibraryImage;
+use crate::synthesis::assignment_model::AssignmentModel;
+use crate::synthesis::assignment_model::payload::{Payload, PayloadBuilder};
+use crate::synthesis::report::bv_to_hex;
+
+/// Renders a solved chain as a standalone pwntools script.
+///
+/// Every loaded library gets an `ELF` object whose `address` is set to the base used during
+/// synthesis, and any word of the payload that points into a library is expressed relative to
+/// it, so that rebasing the chain only requires updating those assignments.
+#[derive(Debug, Clone)]
+pub struct PwntoolsExporter {
+    payload: PayloadBuilder,
+    libraries: Vec<LibraryImage>,
+}
+
+impl PwntoolsExporter {
+    pub fn new(payload: PayloadBuilder) -> Self {
+        Self {
+            payload,
+            libraries: vec![],
+        }
+    }
+
+    pub fn with_libraries(mut self, libraries: &[LibraryImage]) -> Self {
+        self.libraries = libraries.to_vec();
+        self
+    }
+
+    pub fn export<T: ModelingContext + Display>(
+        &self,
+        model: &AssignmentModel<T>,
+    ) -> Result<PwntoolsScript, CrackersError> {
+        let space = model
+            .arch_info
+            .get_space(model.arch_info.default_code_space_index())
+            .ok_or(CrackersError::ModelParsingError)?;
+        Ok(PwntoolsScript {
+            word_size: space.index_size_bytes as usize,
+            endianness: space.endianness,
+            libraries: library_names(&self.libraries)
+                .into_iter()
+                .zip(self.libraries.iter().cloned())
+                .collect(),
+            gadgets: model
+                .gadgets
+                .iter()
+                .map(|g| (g.get_address(), g.to_string()))
+                .collect(),
+            registers: initial_registers(model)?,
+            payload: self.payload.build(model)?,
+        })
+    }
+}
+
+/// A rendered pwntools script; use its [`Display`] implementation to get the source.
+#[derive(Debug, Clone)]
+pub struct PwntoolsScript {
+    word_size: usize,
+    endianness: SleighEndianness,
+    libraries: Vec<(String, LibraryImage)>,
+    gadgets: Vec<(u64, String)>,
+    registers: Vec<(String, String)>,
+    payload: Payload,
+}
+
+impl PwntoolsScript {
+    /// `address`, relative to the library it is in if there is one. Addresses below the base
+    /// of their library (e.g. if it was placed by hand) are given as is.
+    fn address_expr(&self, address: u64) -> String {
+        self.libraries
+            .iter()
+            .find(|(_, lib)| lib.contains(address))
+            .and_then(|(name, lib)| {
+                let offset = address.checked_sub(lib.base_address)?;
+                Some(format!("{name}.address + {offset:#x}"))
+            })
+            .unwrap_or(format!("{address:#x}"))
+    }
+}
+
+impl Display for PwntoolsScript {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "#!/usr/bin/env python3")?;
+        writeln!(f, "# Generated by crackers.")?;
+        writeln!(f, "#")?;
+        writeln!(f, "# Chain:")?;
+        for (i, (_, gadget)) in self.gadgets.iter().enumerate() {
+            writeln!(f, "#   gadget {i}:")?;
+            for line in gadget.lines() {
+                writeln!(f, "#     {line}")?;
+            }
+        }
+        writeln!(f, "from pwn import *")?;
+        writeln!(f)?;
+        let endian = match self.endianness {
+            SleighEndianness::Big => "big",
+            SleighEndianness::Little => "little",
+        };
+        writeln!(
+            f,
+            "context.update(bits={}, endian=\"{endian}\")",
+            self.word_size * 8
+        )?;
+        writeln!(f)?;
+
+        if !self.libraries.is_empty() {
+            writeln!(
+                f,
+                "# Base addresses assumed during synthesis; update these to the runtime bases."
+            )?;
+            for (name, lib) in &self.libraries {
+                writeln!(f, "{name} = ELF({:?}, checksec=False)", lib.path)?;
+                writeln!(f, "{name}.address = {:#x}", lib.base_address)?;
+            }
+            writeln!(f)?;
+        }
+
+        if let Some((entry, _)) = self.gadgets.first() {
+            writeln!(f, "# Redirect execution here to start the chain.")?;
+            writeln!(f, "entry = {}", self.address_expr(*entry))?;
+            writeln!(f)?;
+        }
+
+        writeln!(f, "# Register values the chain expects on entry.")?;
+        writeln!(f, "registers = {{")?;
+        for (name, value) in &self.registers {
+            writeln!(f, "    {name:?}: {value},")?;
+        }
+        writeln!(f, "}}")?;
+        writeln!(f)?;
+
+        if !self.payload.external.is_empty() {
+            writeln!(
+                f,
+                "# Controlled memory outside of the stack payload; these must be written separately."
+            )?;
+            writeln!(f, "memory = {{")?;
+            for loc in &self.payload.external {
+                writeln!(
+                    f,
+                    "    {:#x}: {},  # {}",
+                    loc.address,
+                    bytes_literal(&loc.bytes),
+                    loc.space
+                )?;
+            }
+            writeln!(f, "}}")?;
+            writeln!(f)?;
+        }
+
+        writeln!(
+            f,
+            "# Place this at the initial stack pointer ({:#x}).",
+            self.payload.base_address
+        )?;
+        writeln!(f, "payload = flat(")?;
+        writeln!(f, "    [")?;
+        let words = self.payload.bytes.chunks_exact(self.word_size);
+        let remainder = words.remainder();
+        for word in words {
+            let value = word_value(word, self.endianness);
+            match self.gadgets.iter().position(|(a, _)| *a == value) {
+                Some(i) => writeln!(f, "        {},  # gadget {i}", self.address_expr(value))?,
+                None => writeln!(f, "        {},", self.address_expr(value))?,
+            }
+        }
+        writeln!(f, "    ]")?;
+        writeln!(f, ")")?;
+        if !remainder.is_empty() {
+            writeln!(f, "payload += {}", bytes_literal(remainder))?;
+        }
+        Ok(())
+    }
+}
+
+/// Registers read by the chain before any gadget writes them, with their solved values.
+fn initial_registers<T: ModelingContext>(
+    model: &AssignmentModel<T>,
+) -> Result<Vec<(String, String)>, CrackersError> {
+    let info = &model.arch_info;
+    let initial = model
+        .initial_state()
+        .ok_or(CrackersError::EmptyAssignment)?;
+    let mut written: HashSet<VarNode> = HashSet::new();
+    let mut seen: HashSet<VarNode> = HashSet::new();
+    let mut registers = vec![];
+    for gadget in &model.gadgets {
+        for vn in gadget.get_inputs() {
+            if let ResolvedVarnode::Direct(d) = vn {
+                let is_register = info
+                    .get_space(d.space_index)
+                    .map(|s| s.name == "register")
+                    .unwrap_or(false);
+                if !is_register || written.contains(&d) || !seen.insert(d.clone()) {
+                    continue;
+                }
+                let value = model
+                    .model()
+                    .eval(&initial.read_varnode(&d)?, true)
+                    .and_then(|v| bv_to_hex(&v))
+                    .ok_or(CrackersError::ModelParsingError)?;
+                registers.push((format!("{}", d.display(info)), value));
+            }
+        }
+        for vn in gadget.get_outputs() {
+            if let ResolvedVarnode::Direct(d) = vn {
+                written.insert(d);
+            }
+        }
+    }
+    registers.sort();
+    Ok(registers)
+}
+
+/// Python identifiers for each library, derived from their file names.
+fn library_names(libraries: &[LibraryImage]) -> Vec<String> {
+    let mut used: HashSet<String> = HashSet::new();
+    libraries
+        .iter()
+        .map(|lib| {
+            let file_name = lib.path.rsplit(['/', '\\']).next().unwrap_or_default();
+            let stem = file_name.split('.').next().unwrap_or_default();
+            let mut base: String = stem
+                .chars()
+                .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+                .collect();
+            if base.is_empty() || base.starts_with(|c: char| c.is_ascii_digit()) {
+                base.insert_str(0, "lib_");
+            }
+            let mut name = base.clone();
+            let mut i = 2;
+            while !used.insert(name.clone()) {
+                name = format!("{base}_{i}");
+                i += 1;
+            }
+            name
+        })
+        .collect()
+}
+
+fn word_value(word: &[u8], endianness: SleighEndianness) -> u64 {
+    let fold = |acc: u64, b: &u8| (acc << 8) | *b as u64;
+    match endianness {
+        SleighEndianness::Big => word.iter().fold(0, fold),
+        SleighEndianness::Little => word.iter().rev().fold(0, fold),
+    }
+}
+
+fn bytes_literal(bytes: &[u8]) -> String {
+    let escaped: String = bytes.iter().map(|b| format!("\\x{b:02x}")).collect();
+    format!("b\"{escaped}\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use jingle::sleigh::SleighEndianness;
+
+    use crate::gadget::library::LibraryImage;
+    use crate::synthesis::assignment_model::payload::{ControlledLocation, Payload};
+    use crate::synthesis::assignment_model::pwntools::{PwntoolsScript, library_names};
+
+    #[test]
+    fn test_script() {
+        let libc = LibraryImage {
+            path: "libc.so.6".to_string(),
+            base_address: 0x10000,
+            ranges: vec![(0x8000, 0x20000)],
+        };
+        let words: Vec<u8> = [0x10040u64, 0x2f, 0x401000, 0x9000]
+            .iter()
+            .flat_map(|w| w.to_le_bytes())
+            .chain([0xaa])
+            .collect();
+        let script = PwntoolsScript {
+            word_size: 8,
+            endianness: SleighEndianness::Little,
+            libraries: vec![("libc".to_string(), libc)],
+            gadgets: vec![
+                (0x10020, "10020\tPOP RDI\n10021\tRET\n".to_string()),
+                (0x10040, "10040\tRET\n".to_string()),
+            ],
+            registers: vec![("RSP".to_string(), "0x7000".to_string())],
+            payload: Payload {
+                base_address: 0x7000,
+                bytes: words,
+                external: vec![ControlledLocation {
+                    space: "ram".to_string(),
+                    address: 0x5000,
+                    bytes: vec![0x2f, 0x62],
+                }],
+            },
+        };
+        let expected = r#"#!/usr/bin/env python3
+# Generated by crackers.
+#
+# Chain:
+#   gadget 0:
+#     10020	POP RDI
+#     10021	RET
+#   gadget 1:
+#     10040	RET
+from pwn import *
+
+context.update(bits=64, endian="little")
+
+# Base addresses assumed during synthesis; update these to the runtime bases.
+libc = ELF("libc.so.6", checksec=False)
+libc.address = 0x10000
+
+# Redirect execution here to start the chain.
+entry = libc.address + 0x20
+
+# Register values the chain expects on entry.
+registers = {
+    "RSP": 0x7000,
+}
+
+# Controlled memory outside of the stack payload; these must be written separately.
+memory = {
+    0x5000: b"\x2f\x62",  # ram
+}
+
+# Place this at the initial stack pointer (0x7000).
+payload = flat(
+    [
+        libc.address + 0x40,  # gadget 1
+        0x2f,
+        0x401000,
+        0x9000,
+    ]
+)
+payload += b"\xaa"
+"#;
+        // 0x9000 is in the range of libc, but below its base
+        assert_eq!(script.to_string(), expected);
+    }
+
+    #[test]
+    fn test_library_names() {
+        let image = |path: &str| LibraryImage {
+            path: path.to_string(),
+            base_address: 0,
+            ranges: vec![],
+        };
+        let names = library_names(&[
+            image("/tmp/vuln"),
+            image("libc.so.6"),
+            image("other/libc.so.6"),
+            image("7zip-helper.dll"),
+        ]);
+        assert_eq!(names, vec!["vuln", "libc", "libc_2", "lib_7zip_helper"]);
+    }
+}
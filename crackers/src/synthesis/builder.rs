@@ -1,4 +1,5 @@
 use std::sync::Arc;
+use std::time::Duration;
 
 use derive_builder::Builder;
 use jingle::modeling::{ModeledBlock, State};
@@ -7,20 +8,76 @@ use pyo3::pyclass;
 use serde::{Deserialize, Serialize};
 use z3::ast::Bool;
 
+use crate::config::constraint::PointerRange;
 use crate::error::CrackersError;
 use crate::gadget::library::GadgetLibrary;
 use crate::gadget::library::builder::GadgetLibraryConfig;
 use crate::reference_program::ReferenceProgram;
 use crate::synthesis::AssignmentSynthesis;
+use crate::synthesis::cancellation::CancellationToken;
 use crate::synthesis::combined::CombinedAssignmentSynthesis;
+use crate::synthesis::pcode_theory::DEFAULT_MINIMIZATION_BUDGET;
+use crate::synthesis::progress::ProgressCallback;
+use crate::synthesis::selection_strategy::objective::OptimizationObjective;
+use crate::synthesis::selection_strategy::optimization_problem::OptimizationProblem;
+use crate::synthesis::selection_strategy::portfolio::Portfolio;
+use crate::synthesis::selection_strategy::sat_problem::SatProblem;
+use crate::synthesis::selection_strategy::{Candidate, SelectionStrategy, StrategyBuilder};
 
-#[derive(Copy, Clone, Debug, Deserialize, Serialize)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 #[cfg_attr(feature = "pyo3", pyclass)]
 pub enum SynthesisSelectionStrategy {
     #[serde(rename = "sat")]
     SatStrategy,
     #[serde(rename = "optimize")]
     OptimizeStrategy,
+    /// Races several SAT and optimize problems; see [`Portfolio`].
+    #[serde(rename = "portfolio")]
+    PortfolioStrategy,
+}
+
+impl SynthesisSelectionStrategy {
+    /// Like [`build`](StrategyBuilder::build), seeding the solver with `seed` if given.
+    pub(crate) fn build_seeded(
+        &self,
+        candidates: &[Vec<Candidate<'_>>],
+        seed: Option<u32>,
+        cancellation: &CancellationToken,
+    ) -> Result<Box<dyn SelectionStrategy>, CrackersError> {
+        Ok(match self {
+            SynthesisSelectionStrategy::SatStrategy => {
+                let prob = SatProblem::initialize(candidates);
+                match seed {
+                    Some(seed) => Box::new(prob.with_seed(seed)),
+                    None => Box::new(prob),
+                }
+            }
+            SynthesisSelectionStrategy::OptimizeStrategy => {
+                let costs: Vec<Vec<usize>> = candidates
+                    .iter()
+                    .map(|slot| slot.iter().map(|c| c.cost).collect())
+                    .collect();
+                let prob = OptimizationProblem::with_costs(&costs);
+                match seed {
+                    Some(seed) => Box::new(prob.with_seed(seed)),
+                    None => Box::new(prob),
+                }
+            }
+            SynthesisSelectionStrategy::PortfolioStrategy => {
+                return Portfolio::default().build(candidates, cancellation);
+            }
+        })
+    }
+}
+
+impl StrategyBuilder for SynthesisSelectionStrategy {
+    fn build(
+        &self,
+        candidates: &[Vec<Candidate<'_>>],
+        cancellation: &CancellationToken,
+    ) -> Result<Box<dyn SelectionStrategy>, CrackersError> {
+        self.build_seeded(candidates, None, cancellation)
+    }
 }
 
 pub type StateConstraintGenerator =
@@ -38,10 +95,21 @@ pub struct SynthesisParams {
     pub seed: i64,
     #[builder(default)]
     pub combine_instructions: bool,
-    pub selection_strategy: SynthesisSelectionStrategy,
+    /// Creates the outer problem choosing among candidates; usually a
+    /// [`SynthesisSelectionStrategy`].
+    #[builder(setter(custom))]
+    pub selection_strategy: Arc<dyn StrategyBuilder>,
+    /// Terms of the cost of candidates, minimized by the optimize strategy and passed to custom
+    /// strategies. When empty, a candidate costs its number of instructions.
+    #[builder(default)]
+    pub objectives: Vec<OptimizationObjective>,
     #[builder(setter(custom))]
     pub gadget_library: Arc<GadgetLibrary>,
     pub candidates_per_slot: usize,
+    /// Number of times a slot that runs out of candidates may be given another
+    /// `candidates_per_slot` gadgets from the library. Zero disables expansion.
+    #[builder(default)]
+    pub candidate_expansions: usize,
     pub parallel: usize,
     pub reference_program: ReferenceProgram,
     #[builder(default)]
@@ -50,6 +118,44 @@ pub struct SynthesisParams {
     pub postconditions: Vec<Arc<StateConstraintGenerator>>,
     #[builder(default)]
     pub pointer_invariants: Vec<Arc<TransitionConstraintGenerator>>,
+    /// Address ranges the chain may write to, used by the
+    /// [`OutOfRangeWrites`](crate::synthesis::selection_strategy::objective::ObjectiveKind::OutOfRangeWrites)
+    /// objective.
+    #[builder(default)]
+    pub pointer_write_ranges: Vec<PointerRange>,
+    /// Bytes forbidden in the attacker-controlled memory read by the chain.
+    #[builder(default)]
+    pub bad_bytes: Vec<u8>,
+    /// Number of reference program partitions to search at once when combining instructions.
+    /// The `parallel` workers are split evenly between them.
+    #[builder(default = "1")]
+    pub concurrent_partitions: usize,
+    /// Whether to check each candidate against its step alone before the search, ruling out
+    /// the incompatible ones up front. The checks run on `parallel` threads.
+    #[builder(default = "true")]
+    pub prefilter_candidates: bool,
+    /// Receives the progress events of the synthesis.
+    #[builder(default)]
+    pub progress_callback: Option<Arc<ProgressCallback>>,
+    /// Stops the synthesis when cancelled.
+    #[builder(default)]
+    pub cancellation: CancellationToken,
+    /// Wall-clock budget for the whole synthesis run.
+    #[builder(default)]
+    pub timeout: Option<Duration>,
+    /// Timeout for each theory check of a candidate assignment.
+    #[builder(default)]
+    pub theory_timeout: Option<Duration>,
+    /// Extra solver calls each refuted theory check may make to narrow its conflicts to
+    /// restrictions on the first or last slot and to shrink them. Zero learns each unsat core
+    /// as is. Conflicts independent of the first are found regardless.
+    #[builder(default = "DEFAULT_MINIMIZATION_BUDGET")]
+    pub conflict_minimization: usize,
+    /// How much longer to keep searching for cheaper chains, under the configured objectives,
+    /// once a chain is found. The cheapest chain found in that time is returned. This suits the
+    /// portfolio strategy, whose members find chains of differing cost.
+    #[builder(default)]
+    pub improvement_timeout: Option<Duration>,
 }
 
 impl SynthesisParamsBuilder {
@@ -57,6 +163,11 @@ impl SynthesisParamsBuilder {
         self.gadget_library = Some(gadget_library.into());
         self
     }
+
+    pub fn selection_strategy<T: StrategyBuilder + 'static>(&mut self, strategy: T) -> &mut Self {
+        self.selection_strategy = Some(Arc::new(strategy));
+        self
+    }
 }
 
 impl SynthesisParams {
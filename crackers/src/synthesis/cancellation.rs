@@ -0,0 +1,140 @@
+//! Cooperative cancellation of running syntheses.
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::sync::{Arc, Mutex, Weak};
+
+use z3::Context;
+
+/// A cloneable handle that stops a running synthesis.
+///
+/// Cancelling interrupts the outer solver and every theory worker's Z3 context, after which the
+/// synthesis returns [`CrackersError::Cancelled`](crate::error::CrackersError::Cancelled).
+/// Cancellation is permanent: a cancelled token cancels every synthesis it is later used with.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    inner: Arc<CancellationState>,
+}
+
+#[derive(Debug, Default)]
+struct CancellationState {
+    cancelled: AtomicBool,
+    next_id: AtomicUsize,
+    /// Channels that interrupt a Z3 context when sent to.
+    listeners: Mutex<Vec<(usize, Sender<()>)>>,
+    /// Tokens that are cancelled along with this one.
+    children: Mutex<Vec<Weak<CancellationState>>>,
+}
+
+/// Keeps a listener registered with a [`CancellationToken`] until dropped.
+pub(crate) struct CancellationRegistration {
+    token: CancellationToken,
+    id: usize,
+}
+
+impl Drop for CancellationRegistration {
+    fn drop(&mut self) {
+        let mut listeners = self.token.inner.listeners.lock().unwrap();
+        listeners.retain(|(id, _)| *id != self.id);
+    }
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.inner.cancelled.store(true, Ordering::SeqCst);
+        for (_, listener) in self.inner.listeners.lock().unwrap().iter() {
+            let _ = listener.send(());
+        }
+        for child in self.inner.children.lock().unwrap().drain(..) {
+            if let Some(inner) = child.upgrade() {
+                CancellationToken { inner }.cancel();
+            }
+        }
+    }
+
+    /// A new token that is cancelled whenever this one is, but that can also be cancelled on
+    /// its own.
+    pub fn child(&self) -> CancellationToken {
+        let child = CancellationToken::new();
+        let mut children = self.inner.children.lock().unwrap();
+        if self.is_cancelled() {
+            child.cancel();
+        } else {
+            children.retain(|c| c.strong_count() > 0);
+            children.push(Arc::downgrade(&child.inner));
+        }
+        child
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.inner.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Sends to `interrupt` on cancellation, until the returned registration is dropped. If the
+    /// token is already cancelled, this sends immediately.
+    pub(crate) fn register(&self, interrupt: Sender<()>) -> CancellationRegistration {
+        let id = self.inner.next_id.fetch_add(1, Ordering::SeqCst);
+        let mut listeners = self.inner.listeners.lock().unwrap();
+        if self.is_cancelled() {
+            let _ = interrupt.send(());
+        }
+        listeners.push((id, interrupt));
+        CancellationRegistration {
+            token: self.clone(),
+            id,
+        }
+    }
+
+    /// Runs `f`, interrupting the calling thread's Z3 context if the token is cancelled
+    /// meanwhile.
+    pub(crate) fn interrupting<R>(&self, f: impl FnOnce() -> R) -> R {
+        let z3 = Context::thread_local();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::scope(|s| {
+            let handle = z3.handle();
+            s.spawn(move || {
+                for _ in receiver {
+                    handle.interrupt();
+                }
+            });
+            let registration = self.register(sender);
+            let result = f();
+            drop(registration);
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::synthesis::cancellation::CancellationToken;
+
+    #[test]
+    fn test_cancel_notifies_listeners() {
+        let token = CancellationToken::new();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let registration = token.clone().register(sender);
+        token.cancel();
+        assert!(receiver.try_recv().is_ok());
+        drop(registration);
+        assert!(token.inner.listeners.lock().unwrap().is_empty());
+
+        // registering with an already cancelled token interrupts right away
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let _registration = token.register(sender);
+        assert!(receiver.try_recv().is_ok());
+        assert!(token.child().is_cancelled());
+
+        let parent = CancellationToken::new();
+        let child = parent.child();
+        child.cancel();
+        assert!(!parent.is_cancelled());
+        let child = parent.child();
+        parent.cancel();
+        assert!(child.is_cancelled());
+    }
+}
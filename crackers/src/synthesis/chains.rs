@@ -0,0 +1,212 @@
+//! Enumeration of several distinct chains for one specification.
+//!
+//! Each chain that is found is excluded from the outer problem, along with every assignment
+//! within the requested minimum distance of it, before searching for the next one.
+
+use std::time::Instant;
+
+use tracing::{Level, event};
+
+use crate::error::CrackersError;
+use crate::reference_program::ReferenceProgram;
+use crate::synthesis::builder::SynthesisParams;
+use crate::synthesis::refutations::{Refutations, ordered_partitions};
+use crate::synthesis::{AssignmentSynthesis, DecisionResult, SynthesisProgress};
+
+/// An iterator over the chains found by an [`AssignmentSynthesis`].
+///
+/// Every chain is yielded as a [`DecisionResult::AssignmentFound`]. If the search ends before
+/// `limit` chains were found, the last item is the [`DecisionResult::Unsat`] or
+/// [`DecisionResult::Timeout`] that ended it.
+pub struct Chains {
+    synthesis: AssignmentSynthesis,
+    remaining: usize,
+    done: bool,
+}
+
+impl Chains {
+    pub(crate) fn new(synthesis: AssignmentSynthesis, limit: usize) -> Self {
+        Self {
+            synthesis,
+            remaining: limit,
+            done: false,
+        }
+    }
+
+    pub fn progress(&self) -> &SynthesisProgress {
+        self.synthesis.progress()
+    }
+}
+
+impl Iterator for Chains {
+    type Item = Result<DecisionResult, CrackersError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.remaining == 0 {
+            return None;
+        }
+        match self.synthesis.decide_next() {
+            Ok(DecisionResult::AssignmentFound(a)) => {
+                self.remaining -= 1;
+                Some(Ok(DecisionResult::AssignmentFound(a)))
+            }
+            r => {
+                self.done = true;
+                Some(r.map(DecisionResult::incomplete_as_timeout))
+            }
+        }
+    }
+}
+
+/// An iterator over the chains found by a
+/// [`CombinedAssignmentSynthesis`](crate::synthesis::combined::CombinedAssignmentSynthesis).
+///
+/// Partitions of the reference program are searched smallest first, and each one is exhausted
+/// before moving on to the next. Partitions sharing steps with an earlier chain are preferred,
+/// and what was learned about a step in one partition carries over to the others. Items follow the same rules as for [`Chains`].
+pub struct CombinedChains {
+    base_config: SynthesisParams,
+    partitions: Vec<ReferenceProgram>,
+    refutations: Refutations,
+    current: Option<AssignmentSynthesis>,
+    remaining: usize,
+    min_distance: usize,
+    deadline: Option<Instant>,
+    /// Progress of the partitions that are no longer being searched.
+    finished: SynthesisProgress,
+    last: Option<DecisionResult>,
+    /// The first error a partition failed with. Without a chain, it is returned instead of
+    /// `last`, as the failed partition was never ruled out.
+    error: Option<CrackersError>,
+    found: bool,
+    done: bool,
+}
+
+impl CombinedChains {
+    pub(crate) fn new(base_config: SynthesisParams, limit: usize, min_distance: usize) -> Self {
+        let partitions = ordered_partitions(&base_config.reference_program);
+        // the budget covers all partitions, not each one
+        let deadline = base_config.timeout.map(|t| Instant::now() + t);
+        Self {
+            base_config,
+            partitions,
+            refutations: Refutations::default(),
+            current: None,
+            remaining: limit,
+            min_distance,
+            deadline,
+            finished: SynthesisProgress::default(),
+            last: None,
+            error: None,
+            found: false,
+            done: false,
+        }
+    }
+
+    pub fn progress(&self) -> SynthesisProgress {
+        let mut progress = self.finished.clone();
+        if let Some(current) = &self.current {
+            progress.merge(current.progress());
+        }
+        progress
+    }
+
+    fn finish_partition(&mut self) {
+        if let Some(current) = self.current.take() {
+            self.finished.merge(current.progress());
+        }
+    }
+
+    /// Moves on to the next partition that has candidates for every slot. Returns `false` once
+    /// there are none left.
+    fn next_partition(&mut self) -> bool {
+        while let Some(instructions) = self.refutations.next_partition(&mut self.partitions) {
+            event!(Level::INFO, "Attempting Synthesis of:\n{}", instructions);
+            event!(
+                Level::DEBUG,
+                "Initial memory valuation:\n{:?}",
+                instructions.initial_memory()
+            );
+            let mut new_config = self.base_config.clone();
+            new_config.reference_program = instructions;
+            match AssignmentSynthesis::new_with_refutations(&new_config, self.refutations.clone()) {
+                Ok(synth) => {
+                    self.current = Some(
+                        synth
+                            .with_deadline(self.deadline)
+                            .with_min_distance(self.min_distance),
+                    );
+                    return true;
+                }
+                Err(_) => event!(Level::WARN, "Failed to find gadgets for partition"),
+            }
+        }
+        false
+    }
+}
+
+impl Iterator for CombinedChains {
+    type Item = Result<DecisionResult, CrackersError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done || self.remaining == 0 {
+            return None;
+        }
+        loop {
+            if self.current.is_none() {
+                if self.base_config.cancellation.is_cancelled() {
+                    self.done = true;
+                    return Some(Err(CrackersError::Cancelled));
+                }
+                if self.deadline.is_some_and(|d| Instant::now() >= d) {
+                    self.done = true;
+                    return Some(Ok(DecisionResult::Timeout(self.progress())));
+                }
+                if !self.next_partition() {
+                    self.done = true;
+                    if !self.found
+                        && let Some(e) = self.error.take()
+                    {
+                        return Some(Err(e));
+                    }
+                    return match self.last.take() {
+                        Some(mut last) => {
+                            last.set_statistics(self.progress());
+                            Some(Ok(last.incomplete_as_timeout()))
+                        }
+                        None if self.found => None,
+                        // Only an empty specification can possibly result in this
+                        None => Some(Err(CrackersError::EmptySpecification)),
+                    };
+                }
+            }
+            let synth = self.current.as_mut()?;
+            match synth.decide_next() {
+                Ok(DecisionResult::AssignmentFound(mut a)) => {
+                    self.remaining -= 1;
+                    self.found = true;
+                    a.statistics = self.progress();
+                    return Some(Ok(DecisionResult::AssignmentFound(a)));
+                }
+                Ok(DecisionResult::Unsat(e)) => {
+                    event!(Level::WARN, "{:?}", e);
+                    self.last = Some(DecisionResult::Unsat(e));
+                    self.finish_partition();
+                }
+                Ok(DecisionResult::Timeout(_)) => {
+                    self.done = true;
+                    return Some(Ok(DecisionResult::Timeout(self.progress())));
+                }
+                Err(CrackersError::Cancelled) => {
+                    self.done = true;
+                    return Some(Err(CrackersError::Cancelled));
+                }
+                Err(e) => {
+                    event!(Level::ERROR, "{:?}", e);
+                    self.error.get_or_insert(e);
+                    self.finish_partition();
+                }
+            }
+        }
+    }
+}
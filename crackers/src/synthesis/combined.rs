@@ -1,108 +1,96 @@
+use std::time::Instant;
+
 use tracing::{Level, event};
 
 use crate::error::CrackersError;
-use crate::reference_program::ReferenceProgram;
 use crate::synthesis::builder::SynthesisParams;
-use crate::synthesis::{AssignmentSynthesis, DecisionResult};
+use crate::synthesis::cancellation::CancellationToken;
+use crate::synthesis::chains::CombinedChains;
+use crate::synthesis::refutations::{Refutations, ordered_partitions};
+use crate::synthesis::scheduler::PartitionScheduler;
+use crate::synthesis::{AssignmentSynthesis, DecisionResult, SynthesisProgress};
 
 pub struct CombinedAssignmentSynthesis {
     pub(crate) base_config: SynthesisParams,
 }
 
 impl CombinedAssignmentSynthesis {
+    /// A handle that stops this synthesis from another thread.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.base_config.cancellation.clone()
+    }
+
+    /// Searches for a chain. With more than one concurrent partition configured, several
+    /// partitions are searched at once and the first chain found by any of them is returned.
     pub fn decide(&mut self) -> Result<DecisionResult, CrackersError> {
-        let mut ordering: Vec<ReferenceProgram> =
-            self.base_config.reference_program.partitions().collect();
-        // let mut blacklist = HashSet::new();
-        // todo: gross hack to avoid rewriting the partitioning algorithm to be breadth-first
-        ordering.sort_by(|a, b| a.len().partial_cmp(&b.len()).unwrap());
-        let iter = ordering.into_iter();
-        let mut last: Option<_> = None;
-        for instructions in iter {
-            // todo: filter for instruction combinations that have already been ruled out?
-            // if instructions.iter().any(|i| blacklist.contains(i)) {
-            //     continue;
-            // }
-            event!(Level::INFO, "Attempting Synthesis of:\n{}", instructions);
-            event!(
-                Level::DEBUG,
-                "Initial memory valuation:\n{:?}",
-                instructions.initial_memory()
-            );
-            let mut new_config = self.base_config.clone();
-            new_config.reference_program = instructions.clone();
-            let synth = AssignmentSynthesis::new(&new_config);
-            if let Ok(mut synth) = synth {
-                // this one constructed, let's try it
-                match synth.decide() {
-                    Ok(result) => {
-                        match result {
-                            DecisionResult::AssignmentFound(a) => {
-                                return Ok(DecisionResult::AssignmentFound(a));
-                            }
-                            DecisionResult::Unsat(e) => {
-                                // todo: add in bad combos here
-                                event!(Level::WARN, "{:?}", e);
-                                // e.indexes.iter().for_each(|i|{blacklist.insert(new_config.instructions[*i].clone());});
-                                last = Some(DecisionResult::Unsat(e))
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        event!(Level::ERROR, "{:?}", e)
-                    }
-                }
-            } else {
-                event!(Level::WARN, "Failed to find gadgets for partition")
-            }
+        if self.base_config.concurrent_partitions > 1 {
+            return PartitionScheduler::new(&self.base_config).decide();
         }
-        // Only an empty specification can possibly result in this being `None`
-        last.ok_or(CrackersError::EmptySpecification)
+        self.chains(1, 1)
+            .next()
+            .unwrap_or(Err(CrackersError::EmptySpecification))
+    }
+
+    /// Enumerates up to `limit` distinct chains, each differing from all previous ones of the
+    /// same partition in at least `min_distance` slots. See [`CombinedChains`]. Partitions are
+    /// always searched one at a time here.
+    pub fn chains(&self, limit: usize, min_distance: usize) -> CombinedChains {
+        CombinedChains::new(self.base_config.clone(), limit, min_distance)
     }
 
     // gross but I don't feel like rewriting this right now
     pub fn decide_single_threaded(&mut self) -> Result<DecisionResult, CrackersError> {
-        let mut ordering: Vec<ReferenceProgram> =
-            self.base_config.reference_program.partitions().collect();
-        // let mut blacklist = HashSet::new();
-        // todo: gross hack to avoid rewriting the partitioning algorithm to be breadth-first
-        ordering.sort_by(|a, b| a.len().partial_cmp(&b.len()).unwrap());
-        let iter = ordering.into_iter();
+        let mut partitions = ordered_partitions(&self.base_config.reference_program);
+        let refutations = Refutations::default();
         let mut last: Option<_> = None;
-        for instructions in iter {
-            // todo: filter for instruction combinations that have already been ruled out?
-            // if instructions.iter().any(|i| blacklist.contains(i)) {
-            //     continue;
-            // }
+        let mut error = None;
+        // the budget covers all partitions, not each one
+        let deadline = self.base_config.timeout.map(|t| Instant::now() + t);
+        let mut progress = SynthesisProgress::default();
+        while let Some(instructions) = refutations.next_partition(&mut partitions) {
+            if deadline.is_some_and(|d| Instant::now() >= d) {
+                return Ok(DecisionResult::Timeout(progress));
+            }
             let mut new_config = self.base_config.clone();
             new_config.reference_program = instructions;
-            let synth = AssignmentSynthesis::new(&new_config);
-            if let Ok(mut synth) = synth {
+            let synth = AssignmentSynthesis::new_with_refutations(&new_config, refutations.clone());
+            if let Ok(synth) = synth {
+                let mut synth = synth.with_deadline(deadline);
                 // this one constructed, let's try it
-                match synth.decide_single_threaded() {
-                    Ok(result) => {
-                        match result {
-                            DecisionResult::AssignmentFound(a) => {
-                                return Ok(DecisionResult::AssignmentFound(a));
-                            }
-                            DecisionResult::Unsat(e) => {
-                                // todo: add in bad combos here
-                                event!(Level::WARN, "{:?}", e);
-                                // e.indexes.iter().for_each(|i|{blacklist.insert(new_config.instructions[*i].clone());});
-                                last = Some(DecisionResult::Unsat(e))
-                            }
+                let result = synth.improve(AssignmentSynthesis::next_single_threaded);
+                progress.merge(synth.progress());
+                match result {
+                    Ok(result) => match result {
+                        DecisionResult::AssignmentFound(mut a) => {
+                            a.statistics = progress;
+                            return Ok(DecisionResult::AssignmentFound(a));
                         }
-                    }
+                        DecisionResult::Unsat(e) => {
+                            event!(Level::WARN, "{:?}", e);
+                            last = Some(DecisionResult::Unsat(e))
+                        }
+                        DecisionResult::Timeout(_) => {
+                            return Ok(DecisionResult::Timeout(progress));
+                        }
+                    },
+                    Err(CrackersError::Cancelled) => return Err(CrackersError::Cancelled),
                     Err(e) => {
-                        event!(Level::ERROR, "{:?}", e)
+                        event!(Level::ERROR, "{:?}", e);
+                        error.get_or_insert(e);
                     }
                 }
             } else {
                 event!(Level::WARN, "Failed to find gadgets for partition")
             }
         }
+        // a partition that failed was never ruled out
+        if let Some(e) = error {
+            return Err(e);
+        }
         // Only an empty specification can possibly result in this being `None`
-        last.ok_or(CrackersError::EmptySpecification)
+        let mut last = last.ok_or(CrackersError::EmptySpecification)?;
+        last.set_statistics(progress);
+        Ok(last.incomplete_as_timeout())
     }
     pub fn new(base_config: SynthesisParams) -> Self {
         Self { base_config }
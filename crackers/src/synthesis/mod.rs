@@ -1,35 +1,59 @@
 use jingle::modeling::ModeledInstruction;
+#[cfg(feature = "pyo3")]
+use pyo3::pyclass;
+use serde::{Deserialize, Serialize};
 use std::cmp::Ordering;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
 use std::sync::Arc;
+use std::sync::mpsc::{RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
 use tracing::{Level, event, instrument};
-use z3::Context;
+use z3::{Context, Params};
 
 use crate::error::CrackersError;
-use crate::error::CrackersError::EmptySpecification;
+use crate::error::CrackersError::{
+    BooleanAssignmentTimeout, Cancelled, EmptySpecification, TheoryTimeout, UnsimulatedOperation,
+    WorkersExited,
+};
+use crate::gadget::Gadget;
 use crate::gadget::candidates::{CandidateBuilder, Candidates};
 use crate::gadget::library::GadgetLibrary;
 use crate::reference_program::ReferenceProgram;
 use crate::synthesis::assignment_model::builder::AssignmentModelBuilder;
 use crate::synthesis::builder::{
-    StateConstraintGenerator, SynthesisParams, SynthesisSelectionStrategy,
-    TransitionConstraintGenerator,
+    StateConstraintGenerator, SynthesisParams, TransitionConstraintGenerator,
 };
+use crate::synthesis::cancellation::CancellationToken;
+use crate::synthesis::chains::Chains;
 use crate::synthesis::pcode_theory::builder::PcodeTheoryBuilder;
+use crate::synthesis::pcode_theory::conflict_clause::ConflictClause;
 use crate::synthesis::pcode_theory::theory_worker::TheoryWorker;
+use crate::synthesis::prefilter::Prefilter;
+use crate::synthesis::progress::{ProgressCallback, StageCounts, SynthesisEvent};
+use crate::synthesis::refutations::Refutations;
 use crate::synthesis::selection_strategy::AssignmentResult::{Failure, Success};
-use crate::synthesis::selection_strategy::OuterProblem::{OptimizeProb, SatProb};
-use crate::synthesis::selection_strategy::optimization_problem::OptimizationProblem;
-use crate::synthesis::selection_strategy::sat_problem::SatProblem;
-use crate::synthesis::selection_strategy::{OuterProblem, SelectionFailure, SelectionStrategy};
+use crate::synthesis::selection_strategy::objective::CostModel;
+use crate::synthesis::selection_strategy::{
+    AssignmentResult, Candidate, SelectionFailure, SelectionStrategy, describe,
+};
 use crate::synthesis::slot_assignments::SlotAssignments;
 
 pub mod assignment_model;
 pub mod builder;
+pub mod cancellation;
+pub mod chains;
 mod combined;
 pub(crate) mod partition_iterator;
 pub mod pcode_theory;
+mod prefilter;
+pub mod progress;
+mod refutations;
+pub mod report;
+mod scheduler;
 pub mod selection_strategy;
 pub mod slot_assignments;
+pub mod validation;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Decision {
@@ -44,25 +68,238 @@ impl PartialOrd for Decision {
 }
 
 #[derive(Debug)]
+#[allow(clippy::large_enum_variant)]
 pub enum DecisionResult {
     AssignmentFound(AssignmentModelBuilder),
     Unsat(SelectionFailure),
+    /// The synthesis deadline passed before a chain was found or ruled out, or the search ran
+    /// out of assignments after skipping some whose theory check timed out.
+    Timeout(SynthesisProgress),
+}
+
+impl DecisionResult {
+    /// Statistics of the search that produced this result.
+    pub fn statistics(&self) -> &SynthesisProgress {
+        match self {
+            DecisionResult::AssignmentFound(a) => &a.statistics,
+            DecisionResult::Unsat(f) => &f.statistics,
+            DecisionResult::Timeout(p) => p,
+        }
+    }
+
+    /// Reports an UNSAT result as a timeout if any theory check timed out, since the assignments
+    /// skipped because of it were never ruled out.
+    pub(crate) fn incomplete_as_timeout(self) -> Self {
+        match self {
+            DecisionResult::Unsat(failure) if failure.statistics.theory_timeouts > 0 => {
+                event!(
+                    Level::WARN,
+                    "No chain found, but {} assignments were skipped after their theory check timed out",
+                    failure.statistics.theory_timeouts
+                );
+                DecisionResult::Timeout(failure.statistics)
+            }
+            result => result,
+        }
+    }
+
+    fn set_statistics(&mut self, statistics: SynthesisProgress) {
+        match self {
+            DecisionResult::AssignmentFound(a) => a.statistics = statistics,
+            DecisionResult::Unsat(f) => f.statistics = statistics,
+            DecisionResult::Timeout(p) => *p = statistics,
+        }
+    }
+}
+
+/// How much of the search a synthesis run got through.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "pyo3", pyclass(get_all))]
+pub struct SynthesisProgress {
+    /// Reference program partitions that synthesis was attempted on.
+    pub partitions_attempted: usize,
+    /// Candidate assignments proposed by the outer problem.
+    #[serde(default)]
+    pub assignments_issued: usize,
+    /// Candidate assignments whose theory check completed.
+    pub assignments_checked: usize,
+    /// Conflicts learned from refuted assignments.
+    pub conflicts_learned: usize,
+    /// Learned conflicts, by the theory stages they came from.
+    #[serde(default)]
+    pub conflicts_by_stage: StageCounts,
+    /// Total number of decisions in the learned conflicts.
+    #[serde(default)]
+    pub conflict_decisions: usize,
+    /// Number of decisions in the largest learned conflict.
+    #[serde(default)]
+    pub largest_conflict: usize,
+    /// Candidate assignments skipped because their theory check timed out.
+    pub theory_timeouts: usize,
+    /// Chains that passed their theory check.
+    #[serde(default)]
+    pub chains_found: usize,
+    /// Number of candidate gadgets for each slot of the most recently attempted partition.
+    #[serde(default)]
+    pub candidates_per_slot: Vec<usize>,
+    /// Times a slot was given more candidates after running out.
+    #[serde(default)]
+    pub candidate_expansions: usize,
+    /// Candidates ruled out by the prefilter before the search.
+    #[serde(default)]
+    pub candidates_prefiltered: usize,
+    /// Number of candidates ruled out by the prefilter for each slot of the most recently
+    /// attempted partition.
+    #[serde(default)]
+    pub prefiltered_per_slot: Vec<usize>,
+}
+
+impl SynthesisProgress {
+    /// Updates the statistics with an event of the synthesis.
+    pub fn record(&mut self, event: &SynthesisEvent) {
+        match event {
+            SynthesisEvent::PartitionStarted {
+                candidates_per_slot,
+            } => {
+                self.partitions_attempted += 1;
+                self.candidates_per_slot = candidates_per_slot.clone();
+                self.prefiltered_per_slot = vec![0; candidates_per_slot.len()];
+            }
+            SynthesisEvent::AssignmentIssued => self.assignments_issued += 1,
+            SynthesisEvent::AssignmentRefuted => self.assignments_checked += 1,
+            SynthesisEvent::ConflictLearned { stages, size } => {
+                self.conflicts_learned += 1;
+                for stage in stages {
+                    self.conflicts_by_stage.record(*stage);
+                }
+                self.conflict_decisions += size;
+                self.largest_conflict = self.largest_conflict.max(*size);
+            }
+            SynthesisEvent::TheoryTimeout => self.theory_timeouts += 1,
+            SynthesisEvent::CandidatesExpanded { index, candidates } => {
+                self.candidate_expansions += 1;
+                if let Some(count) = self.candidates_per_slot.get_mut(*index) {
+                    *count = *candidates;
+                }
+            }
+            SynthesisEvent::CandidatesPrefiltered { index, removed } => {
+                self.candidates_prefiltered += removed;
+                if let Some(count) = self.prefiltered_per_slot.get_mut(*index) {
+                    *count += removed;
+                }
+            }
+            SynthesisEvent::ChainFound => {
+                self.assignments_checked += 1;
+                self.chains_found += 1;
+            }
+        }
+    }
+
+    pub(crate) fn merge(&mut self, other: &SynthesisProgress) {
+        self.partitions_attempted += other.partitions_attempted;
+        self.assignments_issued += other.assignments_issued;
+        self.assignments_checked += other.assignments_checked;
+        self.conflicts_learned += other.conflicts_learned;
+        self.conflicts_by_stage.merge(&other.conflicts_by_stage);
+        self.conflict_decisions += other.conflict_decisions;
+        self.largest_conflict = self.largest_conflict.max(other.largest_conflict);
+        self.theory_timeouts += other.theory_timeouts;
+        self.chains_found += other.chains_found;
+        self.candidate_expansions += other.candidate_expansions;
+        self.candidates_prefiltered += other.candidates_prefiltered;
+        if !other.candidates_per_slot.is_empty() {
+            self.candidates_per_slot = other.candidates_per_slot.clone();
+            self.prefiltered_per_slot = other.prefiltered_per_slot.clone();
+        }
+    }
+}
+
+impl Display for SynthesisProgress {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "partition {}, {} assignments issued, {} checked, {} conflicts",
+            self.partitions_attempted,
+            self.assignments_issued,
+            self.assignments_checked,
+            self.conflicts_learned
+        )?;
+        if self.conflicts_learned > 0 {
+            write!(
+                f,
+                " (mean size {:.1})",
+                self.conflict_decisions as f64 / self.conflicts_learned as f64
+            )?;
+        }
+        if self.theory_timeouts > 0 {
+            write!(f, ", {} timeouts", self.theory_timeouts)?;
+        }
+        Ok(())
+    }
+}
+
+/// Z3 parameters limiting each check to `timeout`. Z3 treats a timeout of zero as "no
+/// timeout", so this is at least a millisecond.
+pub(crate) fn solver_timeout(timeout: Duration) -> Params {
+    let mut params = Params::new();
+    let ms = timeout.as_millis().clamp(1, u32::MAX as u128) as u32;
+    params.set_u32("timeout", ms);
+    params
+}
+
+/// Adds `seed`, if any, to solver parameters.
+pub(crate) fn seeded(mut params: Params, seed: Option<u32>) -> Params {
+    if let Some(seed) = seed {
+        params.set_u32("random_seed", seed);
+    }
+    params
 }
 
 pub struct AssignmentSynthesis {
-    outer_problem: OuterProblem,
+    outer_problem: Box<dyn SelectionStrategy>,
+    cost_model: CostModel,
     library: Arc<GadgetLibrary>,
     candidates: Candidates,
     pointer_invariants: Vec<Arc<TransitionConstraintGenerator>>,
     preconditions: Vec<Arc<StateConstraintGenerator>>,
     postconditions: Vec<Arc<StateConstraintGenerator>>,
     candidates_per_slot: usize,
+    candidate_expansions: usize,
+    prefilter_candidates: bool,
+    /// How many times the candidates of each slot were expanded.
+    expansions: Vec<usize>,
+    seed: i64,
     instructions: ReferenceProgram,
     parallel: usize,
+    theory_timeout: Option<Duration>,
+    conflict_minimization: usize,
+    improvement_timeout: Option<Duration>,
+    bad_bytes: Vec<u8>,
+    cancellation: CancellationToken,
+    deadline: Option<Instant>,
+    progress: SynthesisProgress,
+    progress_callback: Option<Arc<ProgressCallback>>,
+    min_distance: usize,
+    refutations: Refutations,
+    /// Assignments that were handed to a worker but never checked, because another worker
+    /// found a chain first. They are checked before asking the outer problem for more.
+    pending: Vec<SlotAssignments>,
+    /// Candidates whose class was ruled out by a conflict involving their addresses. The other
+    /// members of their classes are tried before the search reports UNSAT.
+    address_refuted: HashSet<Decision>,
 }
 
 impl AssignmentSynthesis {
     pub fn new(builder: &SynthesisParams) -> Result<Self, CrackersError> {
+        Self::new_with_refutations(builder, Refutations::default())
+    }
+
+    /// Like [`new`](Self::new), but starts from (and adds to) facts learned by the syntheses of
+    /// other partitions of the same reference program.
+    pub(crate) fn new_with_refutations(
+        builder: &SynthesisParams,
+        refutations: Refutations,
+    ) -> Result<Self, CrackersError> {
         let instrs = &builder.reference_program;
         if instrs.is_empty() {
             return Err(EmptySpecification);
@@ -76,40 +313,447 @@ impl AssignmentSynthesis {
 
         let candidates = CandidateBuilder::default()
             .with_random_sample_size(builder.candidates_per_slot)
-            .build(builder.gadget_library.get_random_candidates_for_trace(
+            .build_resumable(builder.gadget_library.get_random_candidates_for_trace_from(
                 arch_info,
                 modeled_instrs.as_slice(),
                 builder.seed,
-            ))?;
-        let outer_problem = match builder.selection_strategy {
-            SynthesisSelectionStrategy::SatStrategy => {
-                SatProb(SatProblem::initialize(&candidates.candidates))
-            }
-            SynthesisSelectionStrategy::OptimizeStrategy => {
-                OptimizeProb(OptimizationProblem::initialize(&candidates.candidates))
-            }
-        };
-        Ok(AssignmentSynthesis {
+                0,
+            ))
+            .inspect_err(|e| {
+                if let UnsimulatedOperation { index } = e {
+                    refutations.record_unsimulated(instrs, *index);
+                }
+            })?;
+        let cost_model = CostModel::new(
+            &builder.objectives,
+            arch_info.clone(),
+            builder.gadget_library.images(),
+            &builder.pointer_write_ranges,
+        );
+        let costs = (0..candidates.candidates.len())
+            .map(|index| cost_model.class_costs(&candidates, index, 0))
+            .collect::<Result<Vec<_>, _>>()?;
+        let described: Vec<Vec<Candidate>> = candidates
+            .candidates
+            .iter()
+            .zip(&costs)
+            .map(|(slot, costs)| describe(slot, costs))
+            .collect();
+        let outer_problem = builder
+            .selection_strategy
+            .build(&described, &builder.cancellation)?;
+        let candidates_per_slot = candidates.candidates.iter().map(Vec::len).collect();
+        let mut synth = AssignmentSynthesis {
             outer_problem,
+            cost_model,
             candidates,
             library: builder.gadget_library.clone(),
             pointer_invariants: builder.pointer_invariants.clone(),
             preconditions: builder.preconditions.clone(),
             postconditions: builder.postconditions.clone(),
             candidates_per_slot: builder.candidates_per_slot,
+            candidate_expansions: builder.candidate_expansions,
+            prefilter_candidates: builder.prefilter_candidates,
+            expansions: vec![0; instrs.len()],
+            seed: builder.seed,
             instructions: builder.reference_program.clone(),
             parallel: builder.parallel,
-        })
+            theory_timeout: builder.theory_timeout,
+            conflict_minimization: builder.conflict_minimization,
+            improvement_timeout: builder.improvement_timeout,
+            bad_bytes: builder.bad_bytes.clone(),
+            cancellation: builder.cancellation.clone(),
+            deadline: builder.timeout.map(|t| Instant::now() + t),
+            progress: SynthesisProgress::default(),
+            progress_callback: builder.progress_callback.clone(),
+            min_distance: 1,
+            refutations,
+            pending: vec![],
+            address_refuted: HashSet::new(),
+        };
+        for index in 0..instrs.len() {
+            synth.block_refuted(index, 0);
+        }
+        synth.emit(SynthesisEvent::PartitionStarted {
+            candidates_per_slot,
+        });
+        let slots: Vec<(usize, usize)> = (0..instrs.len()).map(|index| (index, 0)).collect();
+        synth.prefilter(&slots)?;
+        Ok(synth)
+    }
+
+    /// Enumerates up to `limit` distinct chains, each differing from all previous ones in at
+    /// least `min_distance` slots. See [`Chains`].
+    pub fn chains(self, limit: usize, min_distance: usize) -> Chains {
+        Chains::new(self.with_min_distance(min_distance), limit)
+    }
+
+    pub(crate) fn with_min_distance(mut self, min_distance: usize) -> Self {
+        self.min_distance = min_distance.max(1);
+        self
+    }
+
+    /// Overrides the deadline derived from the configured timeout, so that several syntheses
+    /// can share one budget.
+    pub(crate) fn with_deadline(mut self, deadline: Option<Instant>) -> Self {
+        self.deadline = deadline;
+        self
     }
 
+    /// A handle that stops this synthesis from another thread.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.clone()
+    }
+
+    pub fn progress(&self) -> &SynthesisProgress {
+        &self.progress
+    }
+
+    fn emit(&mut self, event: SynthesisEvent) {
+        self.progress.record(&event);
+        if let Some(callback) = &self.progress_callback {
+            callback(&event);
+        }
+    }
+
+    fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|d| d.saturating_duration_since(Instant::now()))
+    }
+
+    /// Asks the outer problem for the next assignment, bounded by the remaining budget.
+    /// Returns `None` once the deadline has passed.
+    fn next_assignment(&mut self) -> Result<Option<AssignmentResult>, CrackersError> {
+        if self.cancellation.is_cancelled() {
+            return Err(Cancelled);
+        }
+        if let Some(remaining) = self.remaining() {
+            if remaining.is_zero() {
+                return Ok(None);
+            }
+            self.outer_problem.set_timeout(remaining);
+        }
+        if let Some(a) = self.pending.pop() {
+            return Ok(Some(Success(a)));
+        }
+        match self.outer_problem.get_assignments() {
+            Ok(Success(a)) => {
+                self.emit(SynthesisEvent::AssignmentIssued);
+                Ok(Some(Success(a)))
+            }
+            Ok(a) => Ok(Some(a)),
+            Err(BooleanAssignmentTimeout) if self.cancellation.is_cancelled() => Err(Cancelled),
+            Err(BooleanAssignmentTimeout) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Records a found chain, so that later searches only return chains at least
+    /// `min_distance` away from it. The outer problem already excludes the chain itself.
+    fn record_chain(&mut self, chain: &SlotAssignments) {
+        self.refutations.record_satisfiable(&self.instructions);
+        if self.min_distance > 1 {
+            self.outer_problem
+                .block_assignment(chain, self.min_distance);
+        }
+        let min_distance = self.min_distance;
+        self.pending.retain(|p| p.distance(chain) >= min_distance);
+    }
+
+    /// Excludes the candidates of the `index`th slot, starting at `start`, that were already
+    /// refuted for its step by the synthesis of another partition.
+    fn block_refuted(&mut self, index: usize, start: usize) {
+        let refuted = self.refutations.refuted_addresses(&self.instructions);
+        for (choice, gadget) in self.candidates.candidates[index]
+            .iter()
+            .enumerate()
+            .skip(start)
+        {
+            if let Some(&class) = refuted[index].get(&gadget.address()) {
+                let decision = Decision { index, choice };
+                self.outer_problem.add_theory_clause(&decision.into());
+                if !class {
+                    self.address_refuted.insert(decision);
+                }
+            }
+        }
+    }
+
+    /// Rules out the candidates of each `(index, start)` slot, starting at `start`, that the
+    /// [`Prefilter`] finds incompatible with its step, if prefiltering is enabled.
+    fn prefilter(&mut self, slots: &[(usize, usize)]) -> Result<(), CrackersError> {
+        if !self.prefilter_candidates {
+            return Ok(());
+        }
+        let refuted = self.refutations.refuted_addresses(&self.instructions);
+        let mut decisions = vec![];
+        for &(index, start) in slots {
+            for (choice, gadget) in self.candidates.candidates[index]
+                .iter()
+                .enumerate()
+                .skip(start)
+            {
+                // already ruled out by `block_refuted`
+                if !refuted[index].contains_key(&gadget.address()) {
+                    decisions.push(Decision { index, choice });
+                }
+            }
+        }
+        let arch_info = self.library.arch_info();
+        let incompatible = Prefilter {
+            arch_info: &arch_info,
+            program: &self.instructions,
+            pointer_invariants: &self.pointer_invariants,
+            preconditions: &self.preconditions,
+            postconditions: &self.postconditions,
+            timeout: self.theory_timeout,
+            cancellation: &self.cancellation,
+        }
+        .incompatible(&self.candidates, &decisions, self.parallel)?;
+        let mut removed = vec![0; self.instructions.len()];
+        for (decision, stage) in incompatible {
+            let mut conflict = ConflictClause::from(decision);
+            conflict.stages = vec![stage];
+            self.refutations
+                .record_conflict(&self.instructions, &self.candidates, &conflict);
+            self.mark_address_refuted(&conflict);
+            self.outer_problem.add_theory_clause(&conflict);
+            removed[decision.index] += 1;
+        }
+        for &(index, start) in slots {
+            event!(
+                Level::INFO,
+                "Prefilter ruled out {} of {} candidates for slot {}",
+                removed[index],
+                self.candidates.candidates[index].len() - start,
+                index
+            );
+            self.emit(SynthesisEvent::CandidatesPrefiltered {
+                index,
+                removed: removed[index],
+            });
+        }
+        Ok(())
+    }
+
+    /// Gives the slots implicated in `failure` more candidates from the library, as long as
+    /// it has more and the expansion budget of the slot allows. Returns whether any slot
+    /// was expanded.
+    fn expand_candidates(&mut self, failure: &SelectionFailure) -> Result<bool, CrackersError> {
+        let library = self.library.clone();
+        let mut expanded = false;
+        for &index in &failure.indices {
+            if self.expansions[index] >= self.candidate_expansions {
+                continue;
+            }
+            let Some(start) = self.candidates.cursor(index) else {
+                continue;
+            };
+            self.expansions[index] += 1;
+            let step = self.instructions.steps()[index].model(library.arch_info())?;
+            let before = self.candidates.candidates[index].len();
+            let added = self.candidates.extend_slot(
+                index,
+                library.get_random_candidates_for_trace_from(
+                    library.arch_info(),
+                    &[step],
+                    self.seed,
+                    start,
+                ),
+                self.candidates_per_slot,
+            );
+            if added == 0 {
+                continue;
+            }
+            event!(
+                Level::INFO,
+                "Slot {} ran out of candidates, added {} more",
+                index,
+                added
+            );
+            let costs = self
+                .cost_model
+                .class_costs(&self.candidates, index, before)?;
+            let new = &self.candidates.candidates[index][before..];
+            self.outer_problem
+                .extend_slot(index, &describe(new, &costs));
+            self.block_refuted(index, before);
+            self.emit(SynthesisEvent::CandidatesExpanded {
+                index,
+                candidates: before + added,
+            });
+            self.prefilter(&[(index, before)])?;
+            expanded = true;
+        }
+        Ok(expanded)
+    }
+
+    /// Makes the slots implicated in `failure` try the other members of their candidates'
+    /// classes that were ruled out by conflicts involving the candidates' addresses, as
+    /// [equivalent](Gadget::equivalent) gadgets at other addresses might not conflict. Returns
+    /// whether any slot was given more candidates.
+    fn split_classes(&mut self, failure: &SelectionFailure) -> Result<bool, CrackersError> {
+        let mut split = false;
+        for &index in &failure.indices {
+            let mut choices: Vec<usize> = self
+                .address_refuted
+                .iter()
+                .filter(|d| d.index == index)
+                .map(|d| d.choice)
+                .collect();
+            if choices.is_empty() {
+                continue;
+            }
+            choices.sort();
+            self.address_refuted.retain(|d| d.index != index);
+            let before = self.candidates.candidates[index].len();
+            let added: usize = choices
+                .into_iter()
+                .map(|choice| self.candidates.split_class(index, choice))
+                .sum();
+            if added == 0 {
+                continue;
+            }
+            event!(
+                Level::INFO,
+                "Trying {} gadgets equivalent to candidates refuted at their address for slot {}",
+                added,
+                index
+            );
+            let costs = self
+                .cost_model
+                .class_costs(&self.candidates, index, before)?;
+            let new = &self.candidates.candidates[index][before..];
+            self.outer_problem
+                .extend_slot(index, &describe(new, &costs));
+            self.block_refuted(index, before);
+            self.prefilter(&[(index, before)])?;
+            split = true;
+        }
+        Ok(split)
+    }
+
+    /// Records the candidates of `conflict` whose classes it rules out only because of their
+    /// addresses, so that their other members can be tried later.
+    fn mark_address_refuted(&mut self, conflict: &ConflictClause) {
+        if !conflict.depends_on_address() {
+            return;
+        }
+        for decision in conflict.decisions() {
+            if !self
+                .candidates
+                .equivalents(decision.index, decision.choice)
+                .is_empty()
+            {
+                self.address_refuted.insert(*decision);
+            }
+        }
+    }
+
+    /// Rules out the assignments refuted by a theory check.
+    fn learn(&mut self, conflicts: &[ConflictClause]) {
+        self.emit(SynthesisEvent::AssignmentRefuted);
+        for conflict in conflicts {
+            self.emit(conflict_learned(conflict));
+            self.refutations
+                .record_conflict(&self.instructions, &self.candidates, conflict);
+            self.mark_address_refuted(conflict);
+            self.outer_problem.add_theory_clause(conflict);
+        }
+    }
+
+    fn unsat(&self, mut failure: SelectionFailure) -> DecisionResult {
+        failure.statistics = self.progress.clone();
+        DecisionResult::Unsat(failure)
+    }
+
+    fn timed_out(&self) -> DecisionResult {
+        event!(
+            Level::WARN,
+            "Synthesis deadline reached after checking {} assignments",
+            self.progress.assignments_checked
+        );
+        DecisionResult::Timeout(self.progress.clone())
+    }
+
+    /// Runs `search`, and if it finds a chain and an improvement timeout is set, keeps running it
+    /// until that timeout for cheaper chains. Returns the cheapest chain found.
+    ///
+    /// Unlike [`decide`](Self::decide), this reports running out of assignments as UNSAT even if
+    /// some theory checks timed out, so that callers searching several partitions can move on
+    /// and decide once all of them are done.
+    pub(crate) fn improve(
+        &mut self,
+        search: fn(&mut Self) -> Result<DecisionResult, CrackersError>,
+    ) -> Result<DecisionResult, CrackersError> {
+        let Some(budget) = self.improvement_timeout else {
+            return search(self);
+        };
+        let found = match search(self)? {
+            DecisionResult::AssignmentFound(found) => found,
+            result => return Ok(result),
+        };
+        let mut best_cost = self.chain_cost(&found)?;
+        let mut best = found;
+        let deadline = self.deadline;
+        let improvement_deadline = Instant::now() + budget;
+        self.deadline =
+            Some(deadline.map_or(improvement_deadline, |d| d.min(improvement_deadline)));
+        let outcome = loop {
+            match search(self) {
+                Ok(DecisionResult::AssignmentFound(a)) => {
+                    let cost = self.chain_cost(&a)?;
+                    if cost < best_cost {
+                        event!(Level::INFO, "Found a cheaper chain (cost {})", cost);
+                        best_cost = cost;
+                        best = a;
+                    }
+                }
+                Ok(_) => break Ok(()),
+                Err(e) => break Err(e),
+            }
+        };
+        self.deadline = deadline;
+        outcome?;
+        best.statistics = self.progress.clone();
+        Ok(DecisionResult::AssignmentFound(best))
+    }
+
+    fn chain_cost(&self, chain: &AssignmentModelBuilder) -> Result<usize, CrackersError> {
+        Ok(self.cost_model.costs(&chain.gadgets)?.iter().sum())
+    }
+
+    /// Picks the cheapest member of each chosen class for the model. Should they not satisfy
+    /// constraints involving their addresses, the model falls back to the gadgets that were
+    /// checked.
     fn make_model_builder(&self, slot_assignments: SlotAssignments) -> AssignmentModelBuilder {
+        let checked = slot_assignments.interpret_from_library(&self.candidates);
+        let gadgets: Vec<Gadget> = slot_assignments
+            .to_decisions()
+            .iter()
+            .zip(&checked)
+            .map(|(d, g)| {
+                self.cost_model
+                    .cheapest(&self.candidates, d.index, d.choice)
+                    .map_or(g, |(cheapest, _)| cheapest)
+                    .clone()
+            })
+            .collect();
+        let same = gadgets
+            .iter()
+            .zip(&checked)
+            .all(|(a, b)| a.address() == b.address());
         AssignmentModelBuilder {
             templates: self.instructions.clone(),
-            gadgets: slot_assignments.interpret_from_library(&self.candidates),
+            gadgets,
+            fallback: (!same).then_some(checked),
             preconditions: self.preconditions.clone(),
             postconditions: self.postconditions.clone(),
             pointer_invariants: self.pointer_invariants.clone(),
             arch_info: self.library.arch_info(),
+            libraries: self.library.images().to_vec(),
+            bad_bytes: self.bad_bytes.clone(),
+            statistics: self.progress.clone(),
         }
     }
 
@@ -120,32 +764,88 @@ impl AssignmentSynthesis {
             .with_postconditions(&self.postconditions)
             .with_max_candidates(self.candidates_per_slot)
             .with_templates(self.instructions.clone())
+            .with_timeout(self.theory_timeout)
+            .with_bad_bytes(&self.bad_bytes)
+            .with_minimization_budget(self.conflict_minimization)
     }
 
     pub fn decide_single_threaded(&mut self) -> Result<DecisionResult, CrackersError> {
-        let theory_builder = self.make_pcode_theory_builder();
-        let theory = theory_builder.build()?;
+        self.improve(Self::next_single_threaded)
+            .map(DecisionResult::incomplete_as_timeout)
+    }
+
+    pub(crate) fn next_single_threaded(&mut self) -> Result<DecisionResult, CrackersError> {
         loop {
-            let assignment = self.outer_problem.get_assignments()?;
-            match assignment {
-                Success(a) => {
-                    let theory_result = theory.check_assignment(&a)?;
-                    match theory_result {
-                        None => {
-                            // success
-                            return Ok(DecisionResult::AssignmentFound(self.make_model_builder(a)));
+            match self.search_single_threaded()? {
+                DecisionResult::Unsat(failure) if self.split_classes(&failure)? => continue,
+                DecisionResult::Unsat(failure) if self.expand_candidates(&failure)? => continue,
+                result => return Ok(result),
+            }
+        }
+    }
+
+    fn search_single_threaded(&mut self) -> Result<DecisionResult, CrackersError> {
+        let theory_builder = self.make_pcode_theory_builder();
+        let mut theory = theory_builder.build()?;
+        let cancellation = self.cancellation.clone();
+        cancellation.interrupting(|| {
+            loop {
+                let Some(assignment) = self.next_assignment()? else {
+                    return Ok(self.timed_out());
+                };
+                match assignment {
+                    Success(a) => {
+                        // there are no workers to interrupt here, so the theory check itself must
+                        // not outlive the deadline
+                        if let Some(remaining) = self.remaining() {
+                            theory.set_timeout(Some(
+                                self.theory_timeout.map_or(remaining, |t| t.min(remaining)),
+                            ));
                         }
-                        Some(conflict) => {
-                            self.outer_problem.add_theory_clauses(&conflict);
+                        let theory_result = match theory.check_assignment(&a) {
+                            Err(TheoryTimeout) => {
+                                self.emit(SynthesisEvent::TheoryTimeout);
+                                continue;
+                            }
+                            r => r?,
+                        };
+                        match theory_result {
+                            None => {
+                                // success
+                                self.emit(SynthesisEvent::ChainFound);
+                                self.record_chain(&a);
+                                return Ok(DecisionResult::AssignmentFound(
+                                    self.make_model_builder(a),
+                                ));
+                            }
+                            Some(conflicts) => self.learn(&conflicts),
                         }
                     }
+                    Failure(d) => return Ok(self.unsat(d)),
                 }
-                Failure(d) => return Ok(DecisionResult::Unsat(d)),
             }
-        }
+        })
     }
     #[instrument(skip_all)]
     pub fn decide(&mut self) -> Result<DecisionResult, CrackersError> {
+        self.improve(Self::decide_next)
+            .map(DecisionResult::incomplete_as_timeout)
+    }
+
+    /// Like [`decide`](Self::decide), but returns the first chain found even when an
+    /// improvement timeout is set.
+    pub(crate) fn decide_next(&mut self) -> Result<DecisionResult, CrackersError> {
+        loop {
+            match self.search()? {
+                // the workers were built for the old candidates, so they are started afresh
+                DecisionResult::Unsat(failure) if self.split_classes(&failure)? => continue,
+                DecisionResult::Unsat(failure) if self.expand_candidates(&failure)? => continue,
+                result => return Ok(result),
+            }
+        }
+    }
+
+    fn search(&mut self) -> Result<DecisionResult, CrackersError> {
         let mut req_channels = vec![];
         let mut kill_senders = vec![];
         let library = self.library.clone();
@@ -154,124 +854,408 @@ impl AssignmentSynthesis {
             .with_preconditions(&self.preconditions)
             .with_postconditions(&self.postconditions)
             .with_max_candidates(self.candidates_per_slot)
-            .with_templates(self.instructions.clone());
+            .with_templates(self.instructions.clone())
+            .with_timeout(self.theory_timeout)
+            .with_bad_bytes(&self.bad_bytes)
+            .with_minimization_budget(self.conflict_minimization);
         let (resp_sender, resp_receiver) = std::sync::mpsc::channel();
-        std::thread::scope(|s| {
-            for idx in 0..self.parallel {
-                let t = theory_builder.clone();
-                let r = resp_sender.clone();
-                let (req_sender, req_receiver) = std::sync::mpsc::channel();
-                let (kill_sender, kill_receiver) = std::sync::mpsc::channel();
-                kill_senders.push(kill_sender);
-                req_channels.push(req_sender);
-                s.spawn(move || {
-                    let z3 = Context::thread_local();
-                    std::thread::scope(|inner| {
-                        let handle = z3.handle();
-                        inner.spawn(move || {
-                            for _ in kill_receiver {
-                                handle.interrupt();
-                            }
+        let mut in_flight: Vec<Option<SlotAssignments>> = vec![None; self.parallel];
+        // whether the assignment in flight on each worker is a retry
+        let mut retrying = vec![false; self.parallel];
+        let mut retries: Vec<Retry> = vec![];
+        let mut idle: Vec<usize> = (0..self.parallel).collect();
+        // workers that haven't failed; a worker whose check fails other than by timing out is
+        // retired, and its assignment retried on one of the others
+        let mut live = self.parallel;
+        // set once the outer problem has run out of assignments; the search is only over once
+        // every assignment it handed out has been checked
+        let mut exhausted: Option<SelectionFailure> = None;
+        let cancellation = self.cancellation.clone();
+        cancellation.interrupting(|| {
+            std::thread::scope(|s| {
+                for idx in 0..self.parallel {
+                    let t = theory_builder.clone();
+                    let r = resp_sender.clone();
+                    let (req_sender, req_receiver) = std::sync::mpsc::channel();
+                    let (kill_sender, kill_receiver) = std::sync::mpsc::channel();
+                    let cancel_sender = kill_sender.clone();
+                    kill_senders.push(kill_sender);
+                    req_channels.push(Some(req_sender));
+                    let cancellation = &cancellation;
+                    s.spawn(move || {
+                        let z3 = Context::thread_local();
+                        std::thread::scope(|inner| {
+                            let handle = z3.handle();
+                            inner.spawn(move || {
+                                for _ in kill_receiver {
+                                    handle.interrupt();
+                                }
+                            });
+                            let registration = cancellation.register(cancel_sender);
+                            let worker = TheoryWorker::new(idx, r, req_receiver, t);
+                            event!(Level::TRACE, "Created worker {}", idx);
+                            worker.run();
+                            drop(registration);
                         });
-                        let worker = TheoryWorker::new(idx, r, req_receiver, t).unwrap();
-                        event!(Level::TRACE, "Created worker {}", idx);
-                        worker.run();
-                        drop(worker);
                     });
-                });
-            }
-            drop(resp_sender);
-            for (i, x) in req_channels.iter().enumerate() {
-                event!(
-                    Level::TRACE,
-                    "Asking outer procedure for initial assignments"
-                );
-                if let Ok(assignment) = self.outer_problem.get_assignments() {
-                    match assignment {
-                        Success(assignment) => {
-                            event!(Level::TRACE, "Sending {:?} to worker {}", &assignment, i);
-                            x.send(assignment).unwrap();
+                }
+                drop(resp_sender);
+
+                loop {
+                    // hand out work to every idle worker
+                    let mut still_idle = vec![];
+                    for idx in idle.drain(..) {
+                        if live == 1 {
+                            // there is no other worker left to retry these on
+                            let before = retries.len();
+                            retries.retain(|r| r.failed_on != idx);
+                            for _ in retries.len()..before {
+                                self.emit(SynthesisEvent::TheoryTimeout);
+                            }
+                        }
+                        if let Some(a) = take_retry(&mut retries, idx, &req_channels) {
+                            retrying[idx] = true;
+                            req_channels[idx].as_ref().unwrap().send(a.clone()).unwrap();
+                            in_flight[idx] = Some(a);
+                            continue;
+                        }
+                        if exhausted.is_some() {
+                            still_idle.push(idx);
+                            continue;
                         }
-                        Failure(a) => {
-                            req_channels.clear();
-                            for x in &kill_senders {
-                                x.send(()).unwrap();
+                        event!(Level::TRACE, "Asking outer procedure for assignment");
+                        match self.next_assignment() {
+                            Ok(Some(Success(a))) => {
+                                event!(Level::TRACE, "Sending {:?} to worker {}", &a, idx);
+                                req_channels[idx].as_ref().unwrap().send(a.clone()).unwrap();
+                                in_flight[idx] = Some(a);
+                            }
+                            Ok(Some(Failure(a))) => {
+                                exhausted = Some(a);
+                                still_idle.push(idx);
+                            }
+                            Ok(None) => {
+                                stop_workers(&mut req_channels, &mut kill_senders);
+                                return Ok(self.timed_out());
+                            }
+                            Err(e) => {
+                                stop_workers(&mut req_channels, &mut kill_senders);
+                                return Err(e);
                             }
-                            kill_senders.clear();
-                            return Ok(DecisionResult::Unsat(a));
                         }
                     }
-                }
-            }
-            event!(Level::TRACE, "Done sending initial jobs");
+                    idle = still_idle;
+                    if let Some(failure) = &exhausted
+                        && retries.is_empty()
+                        && in_flight.iter().all(Option::is_none)
+                    {
+                        stop_workers(&mut req_channels, &mut kill_senders);
+                        return Ok(self.unsat(failure.clone()));
+                    }
 
-            for response in resp_receiver {
-                event!(
-                    Level::TRACE,
-                    "Received response from worker {}",
-                    response.idx
-                );
+                    let response = match self.remaining() {
+                        Some(remaining) => resp_receiver.recv_timeout(remaining),
+                        None => resp_receiver
+                            .recv()
+                            .map_err(|_| RecvTimeoutError::Disconnected),
+                    };
+                    let response = match response {
+                        Ok(response) => response,
+                        Err(RecvTimeoutError::Timeout) => {
+                            stop_workers(&mut req_channels, &mut kill_senders);
+                            return Ok(self.timed_out());
+                        }
+                        Err(RecvTimeoutError::Disconnected) => {
+                            stop_workers(&mut req_channels, &mut kill_senders);
+                            return Err(WorkersExited);
+                        }
+                    };
+                    let idx = response.idx;
+                    event!(Level::TRACE, "Received response from worker {}", idx);
+                    in_flight[idx] = None;
+                    let was_retry = std::mem::take(&mut retrying[idx]);
 
-                match response.theory_result {
-                    Ok(r) => {
-                        match r {
-                            None => {
-                                event!(
-                                    Level::INFO,
-                                    "Theory returned SAT for {:?}!",
-                                    response.assignment
-                                );
-                                req_channels.clear();
-                                for x in &kill_senders {
-                                    x.send(()).unwrap();
-                                }
-                                kill_senders.clear();
-                                return Ok(DecisionResult::AssignmentFound(
-                                    self.make_model_builder(response.assignment),
-                                ));
-                            }
-                            Some(c) => {
+                    match response.theory_result {
+                        Ok(None) => {
+                            event!(
+                                Level::INFO,
+                                "Theory returned SAT for {:?}!",
+                                response.assignment
+                            );
+                            stop_workers(&mut req_channels, &mut kill_senders);
+                            self.emit(SynthesisEvent::ChainFound);
+                            self.pending
+                                .extend(in_flight.iter_mut().filter_map(Option::take));
+                            self.pending.extend(retries.drain(..).map(|r| r.assignment));
+                            self.record_chain(&response.assignment);
+                            return Ok(DecisionResult::AssignmentFound(
+                                self.make_model_builder(response.assignment),
+                            ));
+                        }
+                        Ok(Some(conflicts)) => {
+                            for c in &conflicts {
                                 event!(
                                     Level::TRACE,
-                                    "Worker {} found conflicts: {}",
-                                    response.idx,
-                                    response.assignment.display_conflict(&c)
+                                    "Worker {} found conflict: {}",
+                                    idx,
+                                    response.assignment.display_conflict(c)
                                 );
-                                self.outer_problem.add_theory_clauses(&c);
-                                let new_assignment = self.outer_problem.get_assignments()?;
-                                match new_assignment {
-                                    Failure(a) => {
-                                        // drop the senders
-                                        req_channels.clear();
-                                        for x in &kill_senders {
-                                            x.send(()).unwrap();
-                                        }
-                                        kill_senders.clear();
-                                        return Ok(DecisionResult::Unsat(a));
-                                    }
-                                    Success(a) => {
-                                        req_channels[response.idx].send(a).unwrap();
-                                    }
-                                }
                             }
+                            self.learn(&conflicts);
+                        }
+                        Err(TheoryTimeout) if !was_retry && live > 1 => {
+                            event!(
+                                Level::DEBUG,
+                                "Worker {} timed out on {}, retrying on another worker",
+                                idx,
+                                response.assignment
+                            );
+                            retries.push(Retry {
+                                assignment: response.assignment,
+                                failed_on: idx,
+                            });
+                        }
+                        Err(TheoryTimeout) => {
+                            // the outer problem already excludes this assignment, so it is simply
+                            // skipped
+                            event!(
+                                Level::DEBUG,
+                                "Worker {} timed out on {}",
+                                idx,
+                                response.assignment
+                            );
+                            self.emit(SynthesisEvent::TheoryTimeout);
+                        }
+                        Err(e) => {
+                            event!(
+                                Level::ERROR,
+                                "Worker {} failed on {}, retiring it: {}",
+                                idx,
+                                response.assignment,
+                                e
+                            );
+                            req_channels[idx] = None;
+                            live -= 1;
+                            if live == 0 {
+                                stop_workers(&mut req_channels, &mut kill_senders);
+                                return Err(e);
+                            }
+                            retries.push(Retry {
+                                assignment: response.assignment,
+                                failed_on: idx,
+                            });
+                            continue;
                         }
                     }
-                    Err(e) => {
-                        event!(
-                            Level::ERROR,
-                            "Worker {} returned error: {}",
-                            response.idx,
-                            e
-                        );
-                        std::process::exit(-1);
-                    }
+                    idle.push(idx);
                 }
+            })
+        })
+    }
+}
+
+/// An assignment whose check failed on a worker, to be repeated on a different one.
+struct Retry {
+    assignment: SlotAssignments,
+    failed_on: usize,
+}
+
+/// Takes a retry that `worker` may run: one that failed on another worker, or on a worker that
+/// has since been retired.
+fn take_retry(
+    retries: &mut Vec<Retry>,
+    worker: usize,
+    req_channels: &[Option<Sender<SlotAssignments>>],
+) -> Option<SlotAssignments> {
+    let pos = retries
+        .iter()
+        .position(|r| r.failed_on != worker || req_channels[r.failed_on].is_none())?;
+    Some(retries.swap_remove(pos).assignment)
+}
+
+fn conflict_learned(conflict: &ConflictClause) -> SynthesisEvent {
+    SynthesisEvent::ConflictLearned {
+        stages: conflict.stages.clone(),
+        size: conflict.decisions().len(),
+    }
+}
+
+/// Drops the request channels, so that idle workers exit, and interrupts any in-flight checks.
+fn stop_workers(
+    req_channels: &mut Vec<Option<Sender<SlotAssignments>>>,
+    kill_senders: &mut Vec<Sender<()>>,
+) {
+    req_channels.clear();
+    for x in kill_senders.iter() {
+        x.send(()).unwrap();
+    }
+    kill_senders.clear();
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::time::Duration;
+
+    use z3::ast::{BV, Bool};
+
+    use crate::error::CrackersError;
+    use crate::gadget::library::GadgetLibrary;
+    use crate::reference_program::ReferenceProgram;
+    use crate::reference_program::step::Step;
+    use crate::synthesis::builder::{
+        StateConstraintGenerator, SynthesisParams, SynthesisParamsBuilder,
+        SynthesisSelectionStrategy,
+    };
+    use crate::synthesis::progress::SynthesisEvent;
+    use crate::synthesis::selection_strategy::SelectionFailure;
+    use crate::synthesis::{AssignmentSynthesis, DecisionResult, SynthesisProgress};
+    use crate::test_util::{arch_info, copy, gadget, instruction, register};
+
+    /// A one-step synthesis with a single candidate, which satisfies the step and whose chain
+    /// is decided by `precondition`, checked on `parallel` workers.
+    fn single_candidate(
+        precondition: Arc<StateConstraintGenerator>,
+        parallel: usize,
+    ) -> SynthesisParams {
+        SynthesisParamsBuilder::default()
+            .seed(0)
+            .selection_strategy(SynthesisSelectionStrategy::SatStrategy)
+            .gadget_library(GadgetLibrary::from_gadgets(
+                arch_info(),
+                vec![gadget(0x1000, vec![copy(1, 0)])],
+            ))
+            .candidates_per_slot(1)
+            .parallel(parallel)
+            .reference_program(ReferenceProgram::from_steps(vec![Step::from_instr(
+                instruction(0x400000, vec![copy(1, 0)]),
+            )]))
+            .preconditions(vec![precondition])
+            .prefilter_candidates(false)
+            .theory_timeout(Some(Duration::from_millis(50)))
+            .build()
+            .unwrap()
+    }
+
+    /// A precondition that is only hard the first time it is asserted: it asks for the two
+    /// halves of `RBX` to factor a 64-bit semiprime, which the solver can't do before the theory
+    /// timeout. Later checks find it trivially satisfied.
+    fn hard_once() -> Arc<StateConstraintGenerator> {
+        let calls = AtomicUsize::new(0);
+        Arc::new(move |state, _| {
+            if calls.fetch_add(1, Ordering::SeqCst) > 0 {
+                return Ok(Bool::from_bool(true));
             }
-            event!(
-                Level::ERROR,
-                "Outer SAT returned UNSAT! No solution found! :("
-            );
-            unreachable!()
+            let rbx = state.read_varnode(&register(8))?;
+            let (low, high) = (rbx.extract(31, 0), rbx.extract(63, 32));
+            let product = low.zero_ext(32) * high.zero_ext(32);
+            let semiprime = BV::from_u64(9790765170742681277, 64);
+            Ok(product.eq(semiprime) & low.ne(BV::from_u64(1, 32)) & high.ne(BV::from_u64(1, 32)))
         })
     }
+
+    /// A precondition that fails to build on its first `failures` calls, as a theory might on a
+    /// single worker.
+    fn failing(failures: usize) -> Arc<StateConstraintGenerator> {
+        let calls = AtomicUsize::new(0);
+        Arc::new(move |_, _| {
+            if calls.fetch_add(1, Ordering::SeqCst) < failures {
+                return Err(CrackersError::UnknownRegister("RDX".to_string()));
+            }
+            Ok(Bool::from_bool(true))
+        })
+    }
+
+    #[test]
+    fn test_timeout_retry() {
+        // the check that timed out is repeated on the other worker, which finds the chain
+        let mut synthesis = AssignmentSynthesis::new(&single_candidate(hard_once(), 2)).unwrap();
+        let result = synthesis.decide().unwrap();
+        assert!(matches!(result, DecisionResult::AssignmentFound(_)));
+        assert_eq!(result.statistics().theory_timeouts, 0);
+
+        // a lone worker has no one to hand it to, so the candidate is skipped
+        let mut synthesis = AssignmentSynthesis::new(&single_candidate(hard_once(), 1)).unwrap();
+        let result = synthesis.decide().unwrap();
+        assert!(matches!(result, DecisionResult::Timeout(_)));
+        assert_eq!(result.statistics().theory_timeouts, 1);
+    }
+
+    #[test]
+    fn test_retired_worker() {
+        // the worker that failed is retired, and the other one checks its assignment
+        let mut synthesis = AssignmentSynthesis::new(&single_candidate(failing(1), 2)).unwrap();
+        assert!(matches!(
+            synthesis.decide().unwrap(),
+            DecisionResult::AssignmentFound(_)
+        ));
+
+        // once every worker has failed, the error is returned
+        let mut synthesis = AssignmentSynthesis::new(&single_candidate(failing(2), 2)).unwrap();
+        assert!(matches!(
+            synthesis.decide(),
+            Err(CrackersError::UnknownRegister(_))
+        ));
+    }
+
+    #[test]
+    fn test_address_refuted_class() {
+        let gadgets = [
+            gadget(0x1000, vec![copy(1, 0)]),
+            gadget(0x2000, vec![copy(1, 0)]),
+        ];
+        assert!(gadgets[0].equivalent(&gadgets[1]));
+        // whichever of the two stands for their class, the one the precondition accepts is found,
+        // whether the prefilter or the theory rules out the other
+        for (accepted, prefilter) in [
+            (0x1000, true),
+            (0x2000, true),
+            (0x1000, false),
+            (0x2000, false),
+        ] {
+            let precondition: Arc<StateConstraintGenerator> =
+                Arc::new(move |_, address| Ok(Bool::from_bool(address == accepted)));
+            let params = SynthesisParamsBuilder::default()
+                .seed(0)
+                .selection_strategy(SynthesisSelectionStrategy::SatStrategy)
+                .gadget_library(GadgetLibrary::from_gadgets(arch_info(), gadgets.to_vec()))
+                .candidates_per_slot(4)
+                .parallel(1)
+                .reference_program(ReferenceProgram::from_steps(vec![Step::from_instr(
+                    instruction(0x400000, vec![copy(1, 0)]),
+                )]))
+                .preconditions(vec![precondition])
+                .prefilter_candidates(prefilter)
+                .build()
+                .unwrap();
+            let mut synthesis = AssignmentSynthesis::new(&params).unwrap();
+            match synthesis.decide_single_threaded().unwrap() {
+                DecisionResult::AssignmentFound(a) => {
+                    assert_eq!(a.gadgets[0].address(), accepted)
+                }
+                r => panic!("expected a chain at {accepted:#x}, got {r:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_incomplete_as_timeout() {
+        let failure = |statistics| {
+            DecisionResult::Unsat(SelectionFailure {
+                indices: vec![0],
+                statistics,
+            })
+        };
+        let complete = failure(SynthesisProgress::default()).incomplete_as_timeout();
+        assert!(matches!(complete, DecisionResult::Unsat(_)));
+
+        // a skipped assignment was never refuted, so the search did not prove UNSAT
+        let mut statistics = SynthesisProgress::default();
+        statistics.record(&SynthesisEvent::TheoryTimeout);
+        match failure(statistics.clone()).incomplete_as_timeout() {
+            DecisionResult::Timeout(progress) => assert_eq!(progress, statistics),
+            r => panic!("expected a timeout, got {r:?}"),
+        }
+    }
 }
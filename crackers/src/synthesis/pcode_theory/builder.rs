@@ -1,13 +1,14 @@
 use std::borrow::Borrow;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::error::CrackersError;
 use crate::gadget::candidates::Candidates;
 use crate::gadget::library::GadgetLibrary;
 use crate::reference_program::ReferenceProgram;
 use crate::synthesis::builder::{StateConstraintGenerator, TransitionConstraintGenerator};
-use crate::synthesis::pcode_theory::PcodeTheory;
 use crate::synthesis::pcode_theory::pcode_assignment::PcodeAssignment;
+use crate::synthesis::pcode_theory::{DEFAULT_MINIMIZATION_BUDGET, PcodeTheory};
 use crate::synthesis::slot_assignments::SlotAssignments;
 use jingle::modeling::{ModeledBlock, ModeledInstruction};
 use jingle::sleigh::SleighArchInfo;
@@ -16,11 +17,16 @@ use jingle::sleigh::SleighArchInfo;
 pub struct PcodeTheoryBuilder<'lib> {
     reference_program: ReferenceProgram,
     library: &'lib GadgetLibrary,
-    candidates: Candidates,
+    /// The unmodeled candidate gadgets, shared by the theories built from clones of this builder.
+    /// Each theory models those its checks choose in its own z3 context; models are not shared.
+    candidates: Arc<Candidates>,
     preconditions: Vec<Arc<StateConstraintGenerator>>,
     postconditions: Vec<Arc<StateConstraintGenerator>>,
     pointer_invariants: Vec<Arc<TransitionConstraintGenerator>>,
     candidates_per_slot: usize,
+    timeout: Option<Duration>,
+    bad_bytes: Vec<u8>,
+    minimization_budget: usize,
 }
 
 impl<'lib> PcodeTheoryBuilder<'lib> {
@@ -29,25 +35,30 @@ impl<'lib> PcodeTheoryBuilder<'lib> {
         Self {
             reference_program: Default::default(),
             library,
-            candidates,
+            candidates: Arc::new(candidates),
             preconditions: vec![],
             postconditions: vec![],
             pointer_invariants: vec![],
             candidates_per_slot: 200,
+            timeout: None,
+            bad_bytes: vec![],
+            minimization_budget: DEFAULT_MINIMIZATION_BUDGET,
         }
     }
     pub fn build(self) -> Result<PcodeTheory<ModeledInstruction>, CrackersError> {
         let modeled_templates = self.model_instructions(self.library.arch_info())?;
-        let gadget_candidates = self.candidates.model(self.library.arch_info())?;
-        let t = PcodeTheory::new(
+        let mut t = PcodeTheory::new(
             self.library.arch_info(),
             modeled_templates,
             self.reference_program.initial_memory().clone(),
-            gadget_candidates,
+            self.candidates,
             self.preconditions,
             self.postconditions,
             self.pointer_invariants,
         )?;
+        t.set_timeout(self.timeout);
+        t.set_bad_bytes(self.bad_bytes);
+        t.set_minimization_budget(self.minimization_budget);
         Ok(t)
     }
 
@@ -72,6 +83,7 @@ impl<'lib> PcodeTheoryBuilder<'lib> {
             self.preconditions.clone(),
             self.postconditions.clone(),
             self.pointer_invariants.clone(),
+            self.bad_bytes.clone(),
         ))
     }
 
@@ -103,6 +115,21 @@ impl<'lib> PcodeTheoryBuilder<'lib> {
         self
     }
 
+    pub fn with_timeout(mut self, timeout: Option<Duration>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn with_bad_bytes(mut self, bad_bytes: &[u8]) -> Self {
+        self.bad_bytes = bad_bytes.to_vec();
+        self
+    }
+
+    pub fn with_minimization_budget(mut self, budget: usize) -> Self {
+        self.minimization_budget = budget;
+        self
+    }
+
     fn model_instructions<T: Borrow<SleighArchInfo>>(
         &self,
         info: T,
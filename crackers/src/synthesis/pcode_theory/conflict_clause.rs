@@ -1,12 +1,15 @@
 use std::collections::HashSet;
 
 use crate::synthesis::Decision;
+use crate::synthesis::pcode_theory::theory_constraint::TheoryStage;
 
 #[derive(Debug, Clone)]
 pub struct ConflictClause {
     decisions: Vec<Decision>,
     pub precondition: bool,
     pub postcondition: bool,
+    /// The theory stages whose constraints this clause was derived from.
+    pub stages: Vec<TheoryStage>,
 }
 
 impl ConflictClause {
@@ -14,17 +17,24 @@ impl ConflictClause {
         let mut decisions = HashSet::new();
         let mut precondition = false;
         let mut postcondition = false;
+        let mut stages = Vec::new();
         for x in clauses {
             for decision in &x.decisions {
                 decisions.insert(*decision);
             }
             precondition |= x.precondition;
             postcondition |= x.postcondition;
+            for stage in &x.stages {
+                if !stages.contains(stage) {
+                    stages.push(*stage);
+                }
+            }
         }
         Self {
             decisions: decisions.into_iter().collect(),
             precondition,
             postcondition,
+            stages,
         }
     }
 
@@ -35,6 +45,23 @@ impl ConflictClause {
     pub fn includes_index(&self, d: usize) -> bool {
         self.decisions.iter().any(|i| i.index == d)
     }
+
+    /// Whether this conflict rules out a gadget as the first of any chain of `slots` gadgets,
+    /// because it can't satisfy the preconditions, or as the last, because it can't reach the
+    /// postconditions.
+    pub fn is_slot_restriction(&self, slots: usize) -> bool {
+        let only = |index: usize| {
+            !self.decisions.is_empty() && self.decisions.iter().all(|d| d.index == index)
+        };
+        (self.precondition && only(0)) || (self.postcondition && only(slots.saturating_sub(1)))
+    }
+
+    /// Whether this conflict may hold only because of the addresses of the chosen gadgets, so
+    /// that gadgets [equivalent](crate::gadget::Gadget::equivalent) to them are not refuted
+    /// with them.
+    pub fn depends_on_address(&self) -> bool {
+        self.stages.is_empty() || self.stages.iter().any(TheoryStage::depends_on_address)
+    }
 }
 
 impl<'a, T: Iterator<Item = &'a Decision>> From<T> for ConflictClause {
@@ -43,6 +70,7 @@ impl<'a, T: Iterator<Item = &'a Decision>> From<T> for ConflictClause {
             decisions: value.cloned().collect(),
             precondition: false,
             postcondition: false,
+            stages: vec![],
         }
     }
 }
@@ -52,6 +80,7 @@ impl From<Decision> for ConflictClause {
             decisions: vec![value],
             precondition: false,
             postcondition: false,
+            stages: vec![],
         }
     }
 }
@@ -1,42 +1,75 @@
 use std::borrow::Borrow;
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 
 use jingle::modeling::{ModeledBlock, ModelingContext, State};
 use jingle::sleigh::SleighArchInfo;
 use tracing::{Level, event};
 use z3::ast::Bool;
-use z3::{SatResult, Solver};
+use z3::{Params, SatResult, Solver};
 
 use conflict_clause::ConflictClause;
 
 use crate::error::CrackersError;
 use crate::error::CrackersError::TheoryTimeout;
+use crate::gadget::candidates::Candidates;
 use crate::reference_program::valuation::MemoryValuation;
-use crate::synthesis::Decision;
 use crate::synthesis::builder::{StateConstraintGenerator, TransitionConstraintGenerator};
 use crate::synthesis::pcode_theory::pcode_assignment::{
-    assert_compatible_semantics, assert_concat, assert_state_constraints,
+    assert_compatible_semantics, assert_concat, assert_no_bad_bytes, assert_state_constraints,
 };
 use crate::synthesis::pcode_theory::theory_constraint::{
     ConjunctiveConstraint, TheoryStage, gen_conflict_clauses,
 };
 use crate::synthesis::slot_assignments::SlotAssignments;
+use crate::synthesis::{Decision, solver_timeout};
 
 pub mod builder;
 pub mod conflict_clause;
 pub mod pcode_assignment;
-mod theory_constraint;
+pub mod theory_constraint;
 pub mod theory_worker;
 
+/// Solver calls each refuted check may spend on narrowing and minimizing its conflicts, unless
+/// configured otherwise: enough to try narrowing one conflict to a slot restriction.
+pub const DEFAULT_MINIMIZATION_BUDGET: usize = 1;
+
+/// Checks assignments of gadgets against a reference program.
+///
+/// The solver is incremental: what doesn't depend on the assignment (the reference program and
+/// its initial memory) is asserted once, and each check asserts the rest in a scope of its own
+/// that is popped afterward. Candidates are modeled the first time a check chooses them, and
+/// their models, along with the encoding of their semantic compatibility with their step, are
+/// reused by every later check choosing them. Workers thus only pay for the candidates they
+/// actually see, instead of each modeling all of them up front.
+///
+/// The constraints of a check are guarded by fresh booleans passed as assumptions, so that a
+/// refuted check can find further unsat cores disjoint from the first, each of which becomes a
+/// conflict of its own, and spend a budget of more solver calls on narrowing and shrinking them.
 pub struct PcodeTheory<S: ModelingContext> {
     info: SleighArchInfo,
     solver: Solver,
     templates: Vec<S>,
-    initial_memory: MemoryValuation,
-    gadget_candidates: Vec<Vec<ModeledBlock>>,
+    candidates: Arc<Candidates>,
+    /// The candidates chosen by the checks so far.
+    chosen: HashMap<Decision, ChosenCandidate>,
     preconditions: Vec<Arc<StateConstraintGenerator>>,
     postconditions: Vec<Arc<StateConstraintGenerator>>,
     pointer_invariants: Vec<Arc<TransitionConstraintGenerator>>,
+    bad_bytes: Vec<u8>,
+    /// Solver calls each refuted check may spend on narrowing and minimizing its conflicts.
+    minimization_budget: usize,
+    /// Whether conflicts involving the preconditions or postconditions are narrowed to
+    /// restrictions on the first or last slot where possible.
+    slot_restrictions: bool,
+}
+
+/// A candidate chosen by some check, with its model and the encoding of
+/// [`assert_compatible_semantics`] for it.
+struct ChosenCandidate {
+    block: ModeledBlock,
+    semantics: Bool,
 }
 
 impl<S: ModelingContext> PcodeTheory<S> {
@@ -44,51 +77,142 @@ impl<S: ModelingContext> PcodeTheory<S> {
         info: R,
         templates: Vec<S>,
         initial_memory: MemoryValuation,
-        gadget_candidates: Vec<Vec<ModeledBlock>>,
+        candidates: Arc<Candidates>,
         preconditions: Vec<Arc<StateConstraintGenerator>>,
         postconditions: Vec<Arc<StateConstraintGenerator>>,
         pointer_invariants: Vec<Arc<TransitionConstraintGenerator>>,
     ) -> Result<Self, CrackersError> {
         let solver = Solver::new_for_logic("QF_ABV").unwrap();
+        solver.assert(&assert_concat(&templates)?);
+        if let Some(first) = templates.first() {
+            let mem_cnstr = initial_memory.to_constraint();
+            solver.assert(&mem_cnstr(first.get_original_state())?);
+        }
         Ok(Self {
             info: info.borrow().clone(),
             solver,
             templates,
-            initial_memory,
-            gadget_candidates,
+            candidates,
+            chosen: HashMap::new(),
             preconditions,
             postconditions,
             pointer_invariants,
+            bad_bytes: vec![],
+            minimization_budget: DEFAULT_MINIMIZATION_BUDGET,
+            slot_restrictions: true,
         })
     }
+
+    /// Limits the time spent checking a single assignment. When the limit is hit,
+    /// [`check_assignment`](Self::check_assignment) returns [`TheoryTimeout`].
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        let params = match timeout {
+            Some(timeout) => solver_timeout(timeout),
+            None => {
+                let mut params = Params::new();
+                params.set_u32("timeout", u32::MAX);
+                params
+            }
+        };
+        self.solver.set_params(&params);
+    }
+
+    /// Forbids `bad_bytes` in the attacker-controlled memory read by checked assignments.
+    pub fn set_bad_bytes(&mut self, bad_bytes: Vec<u8>) {
+        self.bad_bytes = bad_bytes;
+    }
+
+    /// Lets each refuted check make up to `budget` more solver calls to narrow its conflicts to
+    /// [slot restrictions](Self::set_slot_restrictions) and to shrink them. Zero reports each
+    /// unsat core as is. Defaults to [`DEFAULT_MINIMIZATION_BUDGET`].
+    ///
+    /// Finding further conflicts independent of the first is not limited by the budget: each
+    /// takes one solver call, and there are at most as many as the check has constraints.
+    pub fn set_minimization_budget(&mut self, budget: usize) {
+        self.minimization_budget = budget;
+    }
+
+    /// Whether to check the first (or last) gadget of a refuted assignment alone against the
+    /// preconditions (or postconditions) when the conflict involves them, so that one that can
+    /// never satisfy them is [ruled out of its slot](ConflictClause::is_slot_restriction) rather
+    /// than just out of the assignment. Each such check takes one call of the
+    /// [minimization budget](Self::set_minimization_budget), before any minimization. On by
+    /// default.
+    pub fn set_slot_restrictions(&mut self, enabled: bool) {
+        self.slot_restrictions = enabled;
+    }
+
+    /// Checks an assignment, returning `None` if it is valid, or else the conflicts refuting it.
+    /// There is at least one conflict, and no two share a constraint.
     pub fn check_assignment(
+        &mut self,
+        slot_assignments: &SlotAssignments,
+    ) -> Result<Option<Vec<ConflictClause>>, CrackersError> {
+        if let Err((decision, e)) = self.choose(slot_assignments) {
+            // this is up to the gadget alone, so there is no point in failing the whole check
+            event!(
+                Level::WARN,
+                "Could not model candidate {:?}, ruling it out: {}",
+                decision,
+                e
+            );
+            let mut conflict = ConflictClause::from(decision);
+            conflict.stages = vec![TheoryStage::CombinedSemantics];
+            return Ok(Some(vec![conflict]));
+        }
+        self.solver.push();
+        let result = self.check_in_scope(slot_assignments);
+        self.solver.pop(1);
+        result
+    }
+
+    /// Models the gadgets chosen by `slot_assignments` that no earlier check chose. Fails with
+    /// the first decision whose gadget can't be modeled.
+    fn choose(
+        &mut self,
+        slot_assignments: &SlotAssignments,
+    ) -> Result<(), (Decision, CrackersError)> {
+        for decision in slot_assignments.to_decisions() {
+            if self.chosen.contains_key(&decision) {
+                continue;
+            }
+            let model = || {
+                let block = self.candidates.candidates[decision.index][decision.choice]
+                    .model(&self.info)?;
+                let semantics = assert_compatible_semantics(
+                    &self.templates[decision.index],
+                    &block,
+                    &self.pointer_invariants,
+                )?;
+                Ok((block, semantics))
+            };
+            let (block, semantics) = model().map_err(|e| (decision, e))?;
+            self.chosen
+                .insert(decision, ChosenCandidate { block, semantics });
+        }
+        Ok(())
+    }
+
+    fn check_in_scope(
         &self,
         slot_assignments: &SlotAssignments,
-    ) -> Result<Option<ConflictClause>, CrackersError> {
-        event!(Level::TRACE, "Resetting solver");
-        let gadgets: Vec<ModeledBlock> = slot_assignments
-            .choices()
+    ) -> Result<Option<Vec<ConflictClause>>, CrackersError> {
+        let chosen: Vec<&ChosenCandidate> = slot_assignments
+            .to_decisions()
             .iter()
-            .enumerate()
-            .map(|(i, c)| self.gadget_candidates[i][*c].clone())
+            .map(|d| &self.chosen[d])
             .collect();
+        let gadgets: Vec<&ModeledBlock> = chosen.iter().map(|c| &c.block).collect();
 
-        self.solver.reset();
         event!(Level::TRACE, "Evaluating combined semantics");
         let final_state = State::new(&self.info);
-        self.solver.assert(&assert_concat(&self.templates)?);
         let mut assertions: Vec<ConjunctiveConstraint> = Vec::new();
-        let mem_cnstr = self.initial_memory.to_constraint();
-        self.solver
-            .assert(&mem_cnstr(self.templates[0].get_original_state())?);
         for (index, x) in gadgets.windows(2).enumerate() {
             let branch = Bool::fresh_const("b");
             let concat = Bool::fresh_const("m");
-            self.solver
-                .assert_and_track(&x[0].assert_concat(&x[1])?, &concat);
+            self.track(&x[0].assert_concat(x[1])?, &concat);
 
-            self.solver
-                .assert_and_track(&x[0].can_branch_to_address(x[1].get_address())?, &branch);
+            self.track(&x[0].can_branch_to_address(x[1].get_address())?, &branch);
             assertions.push(ConjunctiveConstraint::new(
                 &[Decision {
                     index,
@@ -113,8 +237,7 @@ impl<S: ModelingContext> PcodeTheory<S> {
             .zip(slot_assignments.choices().last())
         {
             let concat = Bool::fresh_const("m");
-            self.solver
-                .assert_and_track(&g.get_final_state()._eq(&final_state)?, &concat);
+            self.track(&g.get_final_state()._eq(&final_state)?, &concat);
             assertions.push(ConjunctiveConstraint::new(
                 &[Decision {
                     index,
@@ -124,12 +247,9 @@ impl<S: ModelingContext> PcodeTheory<S> {
                 TheoryStage::Consistency,
             ))
         }
-        for (index, (spec, g)) in self.templates.iter().zip(&gadgets).enumerate() {
+        for (index, c) in chosen.iter().enumerate() {
             let sem = Bool::fresh_const("c");
-            self.solver.assert_and_track(
-                &assert_compatible_semantics(spec, g, &self.pointer_invariants)?,
-                &sem,
-            );
+            self.track(&c.semantics, &sem);
             assertions.push(ConjunctiveConstraint::new(
                 &[Decision {
                     index,
@@ -139,21 +259,44 @@ impl<S: ModelingContext> PcodeTheory<S> {
                 TheoryStage::CombinedSemantics,
             ))
         }
+        if !self.bad_bytes.is_empty() {
+            let initial = gadgets[0].get_original_state();
+            for (index, g) in gadgets.iter().enumerate() {
+                let bad = Bool::fresh_const("bb");
+                self.track(&assert_no_bad_bytes(initial, g, &self.bad_bytes)?, &bad);
+                assertions.push(ConjunctiveConstraint::new(
+                    &[Decision {
+                        index,
+                        choice: slot_assignments.choice(index),
+                    }],
+                    bad,
+                    TheoryStage::BadBytes,
+                ))
+            }
+        }
         let first_addr = gadgets[0].get_address();
         let last_addr = gadgets[gadgets.len() - 1].get_address();
         let pre = self.assert_preconditions(gadgets[0].get_original_state(), first_addr)?;
         let post = self.assert_postconditions(&final_state, last_addr)?;
         let pre_bool = Bool::fresh_const("pre");
         let post_bool = Bool::fresh_const("post");
-        self.solver.assert_and_track(&pre, &pre_bool);
-        self.solver.assert_and_track(&post, &post_bool);
+        self.track(&pre, &pre_bool);
+        self.track(&post, &post_bool);
+        // the preconditions only concern the first gadget, and the postconditions the last
+        let last = gadgets.len() - 1;
         assertions.push(ConjunctiveConstraint::new(
-            &[],
+            &[Decision {
+                index: 0,
+                choice: slot_assignments.choice(0),
+            }],
             pre_bool,
             TheoryStage::Precondition,
         ));
         assertions.push(ConjunctiveConstraint::new(
-            &[],
+            &[Decision {
+                index: last,
+                choice: slot_assignments.choice(last),
+            }],
             post_bool,
             TheoryStage::Postcondition,
         ));
@@ -166,6 +309,11 @@ impl<S: ModelingContext> PcodeTheory<S> {
         self.collect_conflicts(&assertions, slot_assignments)
     }
 
+    /// Asserts `term` under the assumption `guard`.
+    fn track(&self, term: &Bool, guard: &Bool) {
+        self.solver.assert(guard.implies(term));
+    }
+
     fn assert_preconditions(&self, state: &State, addr: u64) -> Result<Bool, CrackersError> {
         assert_state_constraints(&self.preconditions, state, addr)
     }
@@ -178,29 +326,284 @@ impl<S: ModelingContext> PcodeTheory<S> {
         &self,
         assertions: &[ConjunctiveConstraint],
         assignments: &SlotAssignments,
-    ) -> Result<Option<ConflictClause>, CrackersError> {
-        let mut constraints = Vec::new();
-        match self.solver.check() {
+    ) -> Result<Option<Vec<ConflictClause>>, CrackersError> {
+        let Some(cores) = disjoint_cores(
+            &self.solver,
+            assertions,
+            self.minimization_budget,
+            self.slot_restrictions,
+        )?
+        else {
+            return Ok(None);
+        };
+        event!(Level::DEBUG, "Learned {} conflicts", cores.len());
+        let conflicts = cores
+            .into_iter()
+            .map(|core| {
+                let constraints: Vec<&ConjunctiveConstraint> =
+                    core.iter().map(|&i| &assertions[i]).collect();
+                gen_conflict_clauses(&constraints).unwrap_or(assignments.as_conflict_clause())
+            })
+            .collect();
+        Ok(Some(conflicts))
+    }
+}
+
+/// Checks `assertions` on `solver`. If they are unsatisfiable, returns the indices of the
+/// constraints in each of their unsat cores, each disjoint from those before it. Up to `budget`
+/// more solver calls are spent on narrowing them to [slot restrictions](slot_restriction), if
+/// `restrict` is set, and on minimizing them, in the order they are found.
+fn disjoint_cores(
+    solver: &Solver,
+    assertions: &[ConjunctiveConstraint],
+    mut budget: usize,
+    restrict: bool,
+) -> Result<Option<Vec<Vec<usize>>>, CrackersError> {
+    let mut remaining: Vec<usize> = (0..assertions.len()).collect();
+    match check_subset(solver, assertions, &remaining) {
+        SatResult::Unsat => {}
+        SatResult::Unknown => return Err(TheoryTimeout),
+        SatResult::Sat => return Ok(None),
+    }
+    let mut cores = vec![];
+    loop {
+        let mut core = unsat_core(solver, assertions);
+        if restrict
+            && let Some(restriction) =
+                slot_restriction(solver, assertions, &remaining, &core, &mut budget)
+        {
+            core = restriction;
+        }
+        minimize(solver, assertions, &mut core, &mut budget);
+        // look for another conflict among the constraints not involved in those found
+        remaining.retain(|i| !core.contains(i));
+        let done = core.is_empty() || remaining.is_empty();
+        cores.push(core);
+        if done || check_subset(solver, assertions, &remaining) != SatResult::Unsat {
+            break;
+        }
+    }
+    Ok(Some(cores))
+}
+
+/// If `core` involves the preconditions, checks whether the first gadget alone can ever satisfy
+/// them, and likewise for the postconditions and the last gadget, using only constraints of
+/// `remaining` and one solver call of `budget` per check. If one can't, returns the core of that
+/// check, from which the outer problem learns a restriction on that slot alone.
+fn slot_restriction(
+    solver: &Solver,
+    assertions: &[ConjunctiveConstraint],
+    remaining: &[usize],
+    core: &[usize],
+    budget: &mut usize,
+) -> Option<Vec<usize>> {
+    for &i in core {
+        let stage = assertions[i].stage();
+        if !matches!(
+            stage,
+            TheoryStage::Precondition | TheoryStage::Postcondition
+        ) {
+            continue;
+        }
+        let index = assertions[i].decisions[0].index;
+        let relevant: Vec<usize> = assertions
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| remaining.contains(j))
+            .filter(|(j, a)| {
+                let local = !a.decisions.is_empty() && a.decisions.iter().all(|d| d.index == index);
+                *j == i
+                    || local && a.stage() == TheoryStage::CombinedSemantics
+                    // the only consistency constraint of the last slot alone ties its final state
+                    // to the one the postconditions are asserted on
+                    || local
+                        && a.stage() == TheoryStage::Consistency
+                        && stage == TheoryStage::Postcondition
+            })
+            .map(|(j, _)| j)
+            .collect();
+        if core.iter().all(|j| relevant.contains(j)) {
+            // already as narrow as this gets
+            return None;
+        }
+        if *budget == 0 {
+            return None;
+        }
+        *budget -= 1;
+        if check_subset(solver, assertions, &relevant) == SatResult::Unsat {
+            event!(
+                Level::DEBUG,
+                "Learned a {:?} restriction on slot {}",
+                stage,
+                index
+            );
+            return Some(unsat_core(solver, assertions));
+        }
+    }
+    None
+}
+
+/// Checks the constraints at the given indices of `assertions`, and only those.
+fn check_subset(
+    solver: &Solver,
+    assertions: &[ConjunctiveConstraint],
+    indices: &[usize],
+) -> SatResult {
+    let assumptions: Vec<Bool> = indices
+        .iter()
+        .map(|&i| assertions[i].get_bool().clone())
+        .collect();
+    solver.check_assumptions(&assumptions)
+}
+
+/// The indices in `assertions` of the unsat core of the last check.
+fn unsat_core(solver: &Solver, assertions: &[ConjunctiveConstraint]) -> Vec<usize> {
+    let unsat_core = solver.get_unsat_core();
+    event!(Level::DEBUG, "Unsat core: {:?}", unsat_core);
+    let mut core = vec![];
+    for b in &unsat_core {
+        if let Some(i) = assertions.iter().position(|p| p.get_bool() == b) {
+            event!(Level::DEBUG, "{:?}: {:?}", b, assertions[i].decisions);
+            core.push(i)
+        } else {
+            event!(
+                Level::WARN,
+                "Unsat Core returned unrecognized variable: {:?}",
+                &unsat_core
+            );
+        }
+    }
+    core
+}
+
+/// Deletion-based core minimization: drops each constraint of `core` that the rest of it is
+/// still unsatisfiable without, spending one solver call of `budget` per attempt. Constraints
+/// without decisions are kept, since dropping them doesn't shrink the conflict.
+fn minimize(
+    solver: &Solver,
+    assertions: &[ConjunctiveConstraint],
+    core: &mut Vec<usize>,
+    budget: &mut usize,
+) {
+    let mut next = 0;
+    while next < core.len() && *budget > 0 {
+        if assertions[core[next]].decisions.is_empty() {
+            next += 1;
+            continue;
+        }
+        *budget -= 1;
+        let mut trial = core.clone();
+        trial.remove(next);
+        match check_subset(solver, assertions, &trial) {
             SatResult::Unsat => {
-                let unsat_core = self.solver.get_unsat_core();
-                event!(Level::DEBUG, "Unsat core: {:?}", unsat_core);
-                for b in &unsat_core {
-                    if let Some(m) = assertions.iter().find(|p| p.get_bool() == b) {
-                        event!(Level::DEBUG, "{:?}: {:?}", b, m.decisions);
-                        constraints.push(m)
-                    } else {
-                        event!(
-                            Level::WARN,
-                            "Unsat Core returned unrecognized variable: {:?}",
-                            &unsat_core
-                        );
-                    }
-                }
-                let clauses = gen_conflict_clauses(constraints.as_slice());
-                Ok(Some(clauses.unwrap_or(assignments.as_conflict_clause())))
+                // the new core may have dropped more than the one constraint
+                let smaller = unsat_core(solver, assertions);
+                core.retain(|i| smaller.contains(i));
             }
-            SatResult::Unknown => Err(TheoryTimeout),
-            SatResult::Sat => Ok(None),
+            SatResult::Sat => next += 1,
+            // out of time, so the core found so far will do
+            SatResult::Unknown => break,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use z3::Solver;
+    use z3::ast::Bool;
+
+    use crate::synthesis::Decision;
+    use crate::synthesis::pcode_theory::theory_constraint::{ConjunctiveConstraint, TheoryStage};
+    use crate::synthesis::pcode_theory::{disjoint_cores, slot_restriction};
+
+    fn constraint(solver: &Solver, index: usize, term: &Bool) -> ConjunctiveConstraint {
+        staged(solver, index, term, TheoryStage::CombinedSemantics)
+    }
+
+    fn staged(
+        solver: &Solver,
+        index: usize,
+        term: &Bool,
+        stage: TheoryStage,
+    ) -> ConjunctiveConstraint {
+        let guard = Bool::fresh_const("c");
+        solver.assert(guard.implies(term));
+        ConjunctiveConstraint::new(&[Decision { index, choice: 0 }], guard, stage)
+    }
+
+    #[test]
+    fn test_disjoint_cores() {
+        let solver = Solver::new();
+        let x = Bool::new_const("x");
+        let y = Bool::new_const("y");
+        let assertions = vec![
+            constraint(&solver, 0, &x),
+            constraint(&solver, 1, &y),
+            constraint(&solver, 2, &x.not()),
+            constraint(&solver, 3, &Bool::and(&[&x, &y])),
+            constraint(&solver, 4, &y.not()),
+        ];
+        // independent conflicts are found without any budget, but may not be minimal
+        let cores = disjoint_cores(&solver, &assertions, 0, false)
+            .unwrap()
+            .unwrap();
+        assert_eq!(cores.len(), 2);
+        assert!(!cores[0].iter().any(|i| cores[1].contains(i)));
+
+        let mut cores = disjoint_cores(&solver, &assertions, 20, false)
+            .unwrap()
+            .unwrap();
+        for core in &mut cores {
+            core.sort();
+        }
+        // both conflicts are minimal, and they don't share a constraint
+        assert_eq!(cores.len(), 2);
+        assert!(cores.iter().all(|core| core.len() == 2));
+        assert!(
+            cores
+                .iter()
+                .all(|core| core.contains(&2) || core.contains(&4))
+        );
+        assert!(!cores[0].iter().any(|i| cores[1].contains(i)));
+    }
+
+    #[test]
+    fn test_slot_restriction() {
+        let solver = Solver::new();
+        let x = Bool::new_const("x");
+        let y = Bool::new_const("y");
+        // the first gadget's semantics imply x, which the preconditions forbid; the core
+        // also involves the second slot through y
+        let assertions = vec![
+            constraint(&solver, 0, &Bool::and(&[&x, &y])),
+            constraint(&solver, 1, &y),
+            staged(
+                &solver,
+                0,
+                &Bool::or(&[&x.not(), &y.not()]),
+                TheoryStage::Precondition,
+            ),
+            staged(&solver, 1, &x, TheoryStage::Consistency),
+        ];
+        let all = [0, 1, 2, 3];
+        let mut budget = 2;
+        let mut restriction =
+            slot_restriction(&solver, &assertions, &all, &all, &mut budget).unwrap();
+        restriction.sort();
+        assert_eq!(restriction, vec![0, 2]);
+        assert_eq!(budget, 1);
+        // a core without the preconditions or postconditions is left alone
+        assert_eq!(
+            slot_restriction(&solver, &assertions, &all, &[0, 1], &mut budget),
+            None
+        );
+        assert_eq!(budget, 1);
+        // the check needs a solver call of the budget, and the first slot's semantics
+        for (remaining, mut budget) in [(&all[..], 0), (&[1, 2, 3][..], 1)] {
+            assert_eq!(
+                slot_restriction(&solver, &assertions, remaining, &all, &mut budget),
+                None
+            );
         }
     }
 }
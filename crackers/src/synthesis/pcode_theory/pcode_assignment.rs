@@ -1,7 +1,9 @@
 use jingle::modeling::{ModeledBlock, ModeledInstruction, ModelingContext, State};
 use jingle::sleigh::SleighArchInfo;
+use jingle::varnode::{ResolvedIndirectVarNode, ResolvedVarnode};
+use std::ops::Add;
 use std::sync::Arc;
-use z3::ast::Bool;
+use z3::ast::{BV, Bool};
 use z3::{SatResult, Solver};
 
 use crate::error::CrackersError;
@@ -16,6 +18,7 @@ pub struct PcodeAssignment {
     preconditions: Vec<Arc<StateConstraintGenerator>>,
     postconditions: Vec<Arc<StateConstraintGenerator>>,
     pointer_invariants: Vec<Arc<TransitionConstraintGenerator>>,
+    bad_bytes: Vec<u8>,
 }
 
 impl PcodeAssignment {
@@ -26,6 +29,7 @@ impl PcodeAssignment {
         preconditions: Vec<Arc<StateConstraintGenerator>>,
         postconditions: Vec<Arc<StateConstraintGenerator>>,
         pointer_invariants: Vec<Arc<TransitionConstraintGenerator>>,
+        bad_bytes: Vec<u8>,
     ) -> Self {
         Self {
             initial_spec_memory,
@@ -34,6 +38,7 @@ impl PcodeAssignment {
             preconditions,
             postconditions,
             pointer_invariants,
+            bad_bytes,
         }
     }
 
@@ -56,6 +61,12 @@ impl PcodeAssignment {
                 &self.pointer_invariants,
             )?);
         }
+        if !self.bad_bytes.is_empty() {
+            let initial = self.eval_trace[0].get_original_state();
+            for item in &self.eval_trace {
+                solver.assert(&assert_no_bad_bytes(initial, item, &self.bad_bytes)?);
+            }
+        }
         solver.assert(&assert_state_constraints(
             &self.preconditions,
             self.eval_trace.as_slice().get_original_state(),
@@ -126,3 +137,31 @@ pub fn assert_state_constraints(
     }
     Ok(Bool::and(&bools))
 }
+
+/// Asserts that no byte of memory read by `item`, taken from the `initial` state of the chain,
+/// is one of `bad_bytes`. Memory that the chain writes before `item` reads it is constrained
+/// too; its initial contents are irrelevant to the chain and free to be chosen.
+pub fn assert_no_bad_bytes(
+    initial: &State,
+    item: &ModeledBlock,
+    bad_bytes: &[u8],
+) -> Result<Bool, CrackersError> {
+    let mut bools = vec![];
+    for input in item.get_inputs() {
+        if let ResolvedVarnode::Indirect(i) = input {
+            for offset in 0..i.access_size_bytes {
+                let byte =
+                    initial.read_resolved(&ResolvedVarnode::Indirect(ResolvedIndirectVarNode {
+                        pointer_location: i.pointer_location.clone(),
+                        pointer: i.pointer.clone().add(offset as u64),
+                        access_size_bytes: 1,
+                        pointer_space_idx: i.pointer_space_idx,
+                    }))?;
+                for bad in bad_bytes {
+                    bools.push(byte.eq(BV::from_u64(*bad as u64, 8)).not());
+                }
+            }
+        }
+    }
+    Ok(Bool::and(&bools))
+}
@@ -10,6 +10,15 @@ pub enum TheoryStage {
     Branch,
     Precondition,
     Postcondition,
+    BadBytes,
+}
+
+impl TheoryStage {
+    /// Whether constraints of this stage involve the addresses of the chosen gadgets, rather than
+    /// just their p-code relative to those addresses.
+    pub fn depends_on_address(&self) -> bool {
+        !matches!(self, TheoryStage::CombinedSemantics)
+    }
 }
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ConjunctiveConstraint {
@@ -30,10 +39,15 @@ impl ConjunctiveConstraint {
         &self.boolean
     }
 
+    pub fn stage(&self) -> TheoryStage {
+        self.constraint_type
+    }
+
     pub fn gen_conflict_clause(&self) -> ConflictClause {
         let mut clause = ConflictClause::from(self.decisions.iter());
         clause.precondition = matches!(self.constraint_type, TheoryStage::Precondition);
         clause.postcondition = matches!(self.constraint_type, TheoryStage::Postcondition);
+        clause.stages = vec![self.constraint_type];
         clause
     }
 }
@@ -55,6 +69,19 @@ pub(crate) fn gen_conflict_clauses(
     if result.is_empty() {
         None
     } else if !semantics.is_empty() {
+        // the preconditions and postconditions add at most the first or last decision, and keep
+        // the conflict marked as one they are involved in
+        semantics.extend(
+            constraints
+                .iter()
+                .filter(|x| {
+                    matches!(
+                        x.constraint_type,
+                        TheoryStage::Precondition | TheoryStage::Postcondition
+                    )
+                })
+                .map(|x| x.gen_conflict_clause()),
+        );
         Some(ConflictClause::combine(semantics.as_slice()))
     } else {
         Some(ConflictClause::combine(result.as_slice()))
@@ -12,14 +12,16 @@ use crate::synthesis::slot_assignments::SlotAssignments;
 pub struct TheoryWorkerResponse {
     pub idx: usize,
     pub assignment: SlotAssignments,
-    pub theory_result: Result<Option<ConflictClause>, CrackersError>,
+    pub theory_result: Result<Option<Vec<ConflictClause>>, CrackersError>,
 }
 
 pub struct TheoryWorker {
     id: usize,
     sender: Sender<TheoryWorkerResponse>,
     receiver: Receiver<SlotAssignments>,
-    theory: PcodeTheory<ModeledInstruction>,
+    /// If the theory could not be built, the error is reported in response to the first
+    /// assignment this worker receives.
+    theory: Result<PcodeTheory<ModeledInstruction>, CrackersError>,
 }
 
 impl TheoryWorker {
@@ -28,44 +30,63 @@ impl TheoryWorker {
         sender: Sender<TheoryWorkerResponse>,
         receiver: Receiver<SlotAssignments>,
         builder: PcodeTheoryBuilder,
-    ) -> Result<Self, CrackersError> {
-        Ok(Self {
+    ) -> Self {
+        Self {
             id,
             sender,
             receiver,
-            theory: builder.build()?,
-        })
+            theory: builder.build(),
+        }
     }
 
-    pub fn run(&self) {
+    pub fn run(self) {
         event!(
             Level::TRACE,
             "Worker {} about to wait for messages",
             self.id
         );
+        let mut theory = match self.theory {
+            Ok(theory) => theory,
+            Err(e) => {
+                event!(Level::ERROR, "Worker {} failed to start: {}", self.id, e);
+                if let Ok(assignment) = self.receiver.recv() {
+                    let _ = self.sender.send(TheoryWorkerResponse {
+                        idx: self.id,
+                        assignment,
+                        theory_result: Err(e),
+                    });
+                }
+                return;
+            }
+        };
         for assignment in self.receiver.iter() {
-            self.evaluate(assignment)
+            Self::evaluate(self.id, &self.sender, &mut theory, assignment)
         }
         event!(Level::TRACE, "Worker {} exiting", self.id);
     }
 
-    #[instrument(skip(self), fields(self.id, assignment = %assignment))]
-    fn evaluate(&self, assignment: SlotAssignments) {
+    #[instrument(skip(sender, theory), fields(assignment = %assignment))]
+    fn evaluate(
+        id: usize,
+        sender: &Sender<TheoryWorkerResponse>,
+        theory: &mut PcodeTheory<ModeledInstruction>,
+        assignment: SlotAssignments,
+    ) {
         event!(
             Level::TRACE,
             "Worker {} received assignment: {:?}",
-            self.id,
+            id,
             assignment
         );
-        let r = self.theory.check_assignment(&assignment);
-        match self.sender.send(TheoryWorkerResponse {
-            idx: self.id,
+        let r = theory.check_assignment(&assignment);
+        match sender.send(TheoryWorkerResponse {
+            idx: id,
             assignment,
             theory_result: r,
         }) {
             Ok(_) => {}
             Err(_) => {
-                event!(Level::TRACE, "Exiting worker {}", self.id);
+                event!(Level::TRACE, "Exiting worker {}", id);
             }
         }
     }
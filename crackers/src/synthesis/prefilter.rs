@@ -0,0 +1,240 @@
+//! Per-candidate compatibility checks run before the outer search.
+//!
+//! Many assignments are refuted in the [`CombinedSemantics`](TheoryStage::CombinedSemantics)
+//! stage for reasons that involve a single slot: the gadget can't produce the effects of its
+//! step, or it violates the pointer invariants. The library only checks the former, and without
+//! the invariants, when it collects candidates. The prefilter checks each candidate against its
+//! step alone, together with the transition constraints (and the preconditions or
+//! postconditions for the first or last slot), so that the outer problem can rule out the
+//! incompatible ones from the start instead of learning them one theory check at a time.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use jingle::modeling::{ModeledBlock, ModeledInstruction, ModelingContext};
+use jingle::sleigh::SleighArchInfo;
+use z3::{SatResult, Solver};
+
+use crate::error::CrackersError;
+use crate::error::CrackersError::Cancelled;
+use crate::gadget::candidates::Candidates;
+use crate::reference_program::ReferenceProgram;
+use crate::synthesis::builder::{StateConstraintGenerator, TransitionConstraintGenerator};
+use crate::synthesis::cancellation::CancellationToken;
+use crate::synthesis::pcode_theory::pcode_assignment::{
+    assert_compatible_semantics, assert_state_constraints,
+};
+use crate::synthesis::pcode_theory::theory_constraint::TheoryStage;
+use crate::synthesis::{Decision, solver_timeout};
+
+/// The constraints candidates are checked against.
+pub(crate) struct Prefilter<'a> {
+    pub(crate) arch_info: &'a SleighArchInfo,
+    pub(crate) program: &'a ReferenceProgram,
+    pub(crate) pointer_invariants: &'a [Arc<TransitionConstraintGenerator>],
+    pub(crate) preconditions: &'a [Arc<StateConstraintGenerator>],
+    pub(crate) postconditions: &'a [Arc<StateConstraintGenerator>],
+    /// Limits each check; candidates whose check times out are kept.
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) cancellation: &'a CancellationToken,
+}
+
+impl Prefilter<'_> {
+    /// Checks the given `decisions` on up to `parallel` threads, and returns those whose
+    /// gadget is incompatible with its step, with the stage that rules it out.
+    pub(crate) fn incompatible(
+        &self,
+        candidates: &Candidates,
+        decisions: &[Decision],
+        parallel: usize,
+    ) -> Result<Vec<(Decision, TheoryStage)>, CrackersError> {
+        let parallel = parallel.clamp(1, decisions.len().max(1));
+        std::thread::scope(|s| {
+            let handles: Vec<_> = (0..parallel)
+                .map(|t| {
+                    let share: Vec<Decision> = decisions
+                        .iter()
+                        .skip(t)
+                        .step_by(parallel)
+                        .copied()
+                        .collect();
+                    s.spawn(move || self.check_all(candidates, &share))
+                })
+                .collect();
+            let mut incompatible = vec![];
+            for handle in handles {
+                incompatible.extend(handle.join().expect("prefilter thread panicked")?);
+            }
+            incompatible.sort_by_key(|(d, _)| (d.index, d.choice));
+            Ok(incompatible)
+        })
+    }
+
+    fn check_all(
+        &self,
+        candidates: &Candidates,
+        decisions: &[Decision],
+    ) -> Result<Vec<(Decision, TheoryStage)>, CrackersError> {
+        let solver = Solver::new_for_logic("QF_ABV").unwrap();
+        if let Some(timeout) = self.timeout {
+            solver.set_params(&solver_timeout(timeout));
+        }
+        let mut steps: Vec<Option<ModeledInstruction>> = vec![None; self.program.len()];
+        let mut incompatible = vec![];
+        for decision in decisions {
+            if self.cancellation.is_cancelled() {
+                return Err(Cancelled);
+            }
+            let step = match &steps[decision.index] {
+                Some(step) => step,
+                None => steps[decision.index]
+                    .insert(self.program.steps()[decision.index].model(self.arch_info)?),
+            };
+            let gadget =
+                candidates.candidates[decision.index][decision.choice].model(self.arch_info)?;
+            if let Some(stage) = self.check(&solver, step, &gadget, decision.index)? {
+                incompatible.push((*decision, stage));
+            }
+        }
+        Ok(incompatible)
+    }
+
+    /// The stage ruling out `gadget` for the `index`th step, if any.
+    fn check(
+        &self,
+        solver: &Solver,
+        step: &ModeledInstruction,
+        gadget: &ModeledBlock,
+        index: usize,
+    ) -> Result<Option<TheoryStage>, CrackersError> {
+        let mut stages = vec![(
+            TheoryStage::CombinedSemantics,
+            assert_compatible_semantics(step, gadget, self.pointer_invariants)?,
+        )];
+        if index == 0 && !self.preconditions.is_empty() {
+            stages.push((
+                TheoryStage::Precondition,
+                assert_state_constraints(
+                    self.preconditions,
+                    gadget.get_original_state(),
+                    gadget.get_address(),
+                )?,
+            ));
+        }
+        if index + 1 == self.program.len() && !self.postconditions.is_empty() {
+            stages.push((
+                TheoryStage::Postcondition,
+                assert_state_constraints(
+                    self.postconditions,
+                    gadget.get_final_state(),
+                    gadget.get_address(),
+                )?,
+            ));
+        }
+        // each stage is checked on top of the ones before it, so that a refutation is blamed on
+        // the first stage that causes it
+        solver.push();
+        let mut refuted = None;
+        for (stage, constraint) in stages {
+            solver.assert(&constraint);
+            if solver.check() == SatResult::Unsat {
+                refuted = Some(stage);
+                break;
+            }
+        }
+        solver.pop(1);
+        Ok(refuted)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use z3::ast::{BV, Bool};
+
+    use crate::gadget::Gadget;
+    use crate::gadget::candidates::CandidateBuilder;
+    use crate::reference_program::ReferenceProgram;
+    use crate::reference_program::step::Step;
+    use crate::synthesis::Decision;
+    use crate::synthesis::builder::StateConstraintGenerator;
+    use crate::synthesis::cancellation::CancellationToken;
+    use crate::synthesis::pcode_theory::theory_constraint::TheoryStage;
+    use crate::synthesis::prefilter::Prefilter;
+    use crate::test_util::{arch_info, copy, gadget, instruction, register};
+
+    #[test]
+    fn test_incompatible() {
+        // set RAX to 1, then RCX to 2
+        let program = ReferenceProgram::from_steps(vec![
+            Step::from_instr(instruction(0x400000, vec![copy(1, 0)])),
+            Step::from_instr(instruction(0x400001, vec![copy(2, 16)])),
+        ]);
+        let first = [
+            gadget(0x1000, vec![copy(1, 0)]),
+            // ruled out by the precondition
+            gadget(0x1100, vec![copy(1, 0), copy(5, 8)]),
+            // ruled out by its semantics, which are checked first
+            gadget(0x1200, vec![copy(3, 0)]),
+        ];
+        let last = [
+            gadget(0x2000, vec![copy(2, 16)]),
+            // ruled out by the postcondition
+            gadget(0x2100, vec![copy(2, 16), copy(7, 0)]),
+            gadget(0x2200, vec![copy(4, 16)]),
+        ];
+        let slots: Vec<Vec<Option<&Gadget>>> = first
+            .iter()
+            .map(|g| vec![Some(g), None])
+            .chain(last.iter().map(|g| vec![None, Some(g)]))
+            .collect();
+        let candidates = CandidateBuilder::default()
+            .with_random_sample_size(3)
+            .build(slots.into_iter())
+            .unwrap();
+
+        let precondition: Arc<StateConstraintGenerator> =
+            Arc::new(|_, addr| Ok(Bool::from_bool(addr != 0x1100 && addr != 0x1200)));
+        let postcondition: Arc<StateConstraintGenerator> = Arc::new(|state, _| {
+            let rax = state.read_varnode(&register(0))?;
+            Ok(rax.eq(BV::from_u64(7, 64)).not())
+        });
+        let arch_info = arch_info();
+        let cancellation = CancellationToken::new();
+        let prefilter = Prefilter {
+            arch_info: &arch_info,
+            program: &program,
+            pointer_invariants: &[],
+            preconditions: &[precondition],
+            postconditions: &[postcondition],
+            timeout: None,
+            cancellation: &cancellation,
+        };
+        // out of order, so that each thread's share is too
+        let decisions: Vec<Decision> = [(1, 2), (0, 1), (1, 0), (0, 2), (1, 1), (0, 0)]
+            .into_iter()
+            .map(|(index, choice)| Decision { index, choice })
+            .collect();
+        for parallel in [1, 2, 4] {
+            let incompatible: Vec<(usize, u64, TheoryStage)> = prefilter
+                .incompatible(&candidates, &decisions, parallel)
+                .unwrap()
+                .into_iter()
+                .map(|(d, stage)| {
+                    let address = candidates.candidates[d.index][d.choice].address();
+                    (d.index, address, stage)
+                })
+                .collect();
+            assert_eq!(
+                incompatible,
+                vec![
+                    (0, 0x1100, TheoryStage::Precondition),
+                    (0, 0x1200, TheoryStage::CombinedSemantics),
+                    (1, 0x2100, TheoryStage::Postcondition),
+                    (1, 0x2200, TheoryStage::CombinedSemantics),
+                ]
+            );
+        }
+    }
+}
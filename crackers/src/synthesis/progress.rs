@@ -0,0 +1,129 @@
+//! Structured progress reporting for running syntheses.
+//!
+//! A synthesis emits a [`SynthesisEvent`] to its [`ProgressCallback`] (if one is set in
+//! [`SynthesisParams`](crate::synthesis::builder::SynthesisParams)) whenever its search makes
+//! progress. Callers that want running totals can feed the events into a
+//! [`SynthesisProgress`](crate::synthesis::SynthesisProgress) with
+//! [`record`](crate::synthesis::SynthesisProgress::record).
+
+#[cfg(feature = "pyo3")]
+use pyo3::pyclass;
+use serde::{Deserialize, Serialize};
+
+use crate::synthesis::pcode_theory::theory_constraint::TheoryStage;
+
+/// Something that happened during a synthesis run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SynthesisEvent {
+    /// Synthesis of a partition of the reference program started, with this many candidate
+    /// gadgets for each slot.
+    PartitionStarted { candidates_per_slot: Vec<usize> },
+    /// The outer problem proposed an assignment for a theory check.
+    AssignmentIssued,
+    /// A theory check refuted an assignment. It is followed by a
+    /// [`ConflictLearned`](Self::ConflictLearned) for each conflict learned from the check.
+    AssignmentRefuted,
+    /// A conflict was learned from a refuted assignment. `stages` are the stages of the theory
+    /// whose constraints make up the conflict, which mentions `size` decisions.
+    ConflictLearned {
+        stages: Vec<TheoryStage>,
+        size: usize,
+    },
+    /// A theory check timed out, so its assignment was skipped.
+    TheoryTimeout,
+    /// The `index`th slot ran out of candidates and was given more from the library, for a
+    /// total of `candidates`.
+    CandidatesExpanded { index: usize, candidates: usize },
+    /// Before the search, `removed` candidates of the `index`th slot were found incompatible
+    /// with its step and ruled out.
+    CandidatesPrefiltered { index: usize, removed: usize },
+    /// An assignment passed its theory check.
+    ChainFound,
+}
+
+/// Receives the [`SynthesisEvent`]s of a synthesis. Called from the thread running the search,
+/// so it should return quickly.
+pub type ProgressCallback = dyn Fn(&SynthesisEvent) + Send + Sync + 'static;
+
+/// Learned conflicts, counted by the theory stages they came from. A conflict may involve
+/// several stages.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "pyo3", pyclass(get_all))]
+pub struct StageCounts {
+    pub combined_semantics: usize,
+    pub consistency: usize,
+    pub branch: usize,
+    pub precondition: usize,
+    pub postcondition: usize,
+    pub bad_bytes: usize,
+}
+
+impl StageCounts {
+    pub fn record(&mut self, stage: TheoryStage) {
+        let count = match stage {
+            TheoryStage::CombinedSemantics => &mut self.combined_semantics,
+            TheoryStage::Consistency => &mut self.consistency,
+            TheoryStage::Branch => &mut self.branch,
+            TheoryStage::Precondition => &mut self.precondition,
+            TheoryStage::Postcondition => &mut self.postcondition,
+            TheoryStage::BadBytes => &mut self.bad_bytes,
+        };
+        *count += 1;
+    }
+
+    pub(crate) fn merge(&mut self, other: &StageCounts) {
+        self.combined_semantics += other.combined_semantics;
+        self.consistency += other.consistency;
+        self.branch += other.branch;
+        self.precondition += other.precondition;
+        self.postcondition += other.postcondition;
+        self.bad_bytes += other.bad_bytes;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::synthesis::SynthesisProgress;
+    use crate::synthesis::pcode_theory::theory_constraint::TheoryStage;
+    use crate::synthesis::progress::SynthesisEvent;
+
+    #[test]
+    fn test_record() {
+        let mut progress = SynthesisProgress::default();
+        progress.record(&SynthesisEvent::PartitionStarted {
+            candidates_per_slot: vec![3, 4],
+        });
+        progress.record(&SynthesisEvent::CandidatesPrefiltered {
+            index: 1,
+            removed: 2,
+        });
+        progress.record(&SynthesisEvent::AssignmentIssued);
+        progress.record(&SynthesisEvent::AssignmentRefuted);
+        progress.record(&SynthesisEvent::ConflictLearned {
+            stages: vec![TheoryStage::Branch, TheoryStage::Precondition],
+            size: 2,
+        });
+        progress.record(&SynthesisEvent::ConflictLearned {
+            stages: vec![TheoryStage::CombinedSemantics],
+            size: 1,
+        });
+        progress.record(&SynthesisEvent::AssignmentIssued);
+        progress.record(&SynthesisEvent::ChainFound);
+        assert_eq!(progress.partitions_attempted, 1);
+        assert_eq!(progress.candidates_per_slot, vec![3, 4]);
+        assert_eq!(progress.prefiltered_per_slot, vec![0, 2]);
+        assert_eq!(progress.assignments_issued, 2);
+        assert_eq!(progress.assignments_checked, 2);
+        assert_eq!(progress.conflicts_by_stage.branch, 1);
+        assert_eq!(progress.conflicts_by_stage.precondition, 1);
+        assert_eq!(progress.conflicts_learned, 2);
+        assert_eq!(progress.conflict_decisions, 3);
+        assert_eq!(progress.largest_conflict, 2);
+        assert_eq!(progress.chains_found, 1);
+
+        let mut total = progress.clone();
+        total.merge(&progress);
+        assert_eq!(total.partitions_attempted, 2);
+        assert_eq!(total.largest_conflict, 2);
+    }
+}
@@ -0,0 +1,292 @@
+//! Facts learned while searching one partition of a reference program that also hold in the
+//! other partitions.
+//!
+//! Partitions of a reference program group its instructions into steps in different ways, but
+//! many steps (i.e. the same instructions at the same position in the program) appear in
+//! several of them. Some of what a synthesis learns about a step does not depend on the rest of
+//! its partition:
+//!
+//! * a step for which the library has no candidate gadget can't be synthesized at all;
+//! * a gadget refuted for a step by its semantics alone stays refuted for that step, as does one
+//!   that can't satisfy the preconditions (or postconditions) in the first (or last) step;
+//! * a step that is part of a found chain is known to be satisfiable.
+//!
+//! [`Refutations`] collects these facts, keyed by step contents, so that partitions searched
+//! later skip what is already known to be impossible and start with the most promising ones.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+
+use tracing::{Level, event};
+
+use crate::gadget::candidates::Candidates;
+use crate::reference_program::ReferenceProgram;
+use crate::synthesis::pcode_theory::conflict_clause::ConflictClause;
+use crate::synthesis::pcode_theory::theory_constraint::TheoryStage;
+
+/// Identifies a step by its instructions and their position in the reference program.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct StepKey {
+    offset: usize,
+    instructions: Vec<(u64, String)>,
+}
+
+#[derive(Debug, Default)]
+struct RefutationState {
+    unsimulated: HashSet<StepKey>,
+    /// For each step, the addresses of the gadgets refuted for it, mapped to whether the
+    /// refutation holds for the whole class of the gadget.
+    refuted: HashMap<StepKey, HashMap<u64, bool>>,
+    satisfiable: HashSet<StepKey>,
+}
+
+/// A cache of learned facts shared by all partitions of one combined synthesis. Cloning it
+/// yields a handle to the same cache.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Refutations {
+    state: Arc<Mutex<RefutationState>>,
+}
+
+fn step_keys(program: &ReferenceProgram) -> Vec<StepKey> {
+    let mut offset = 0;
+    program
+        .steps()
+        .iter()
+        .map(|step| {
+            let key = StepKey {
+                offset,
+                instructions: step
+                    .instructions()
+                    .iter()
+                    .map(|i| (i.address, i.disassembly.to_string()))
+                    .collect(),
+            };
+            offset += step.instructions().len();
+            key
+        })
+        .collect()
+}
+
+impl Refutations {
+    /// Records that the library has no candidates for the `index`th step of `program`.
+    pub(crate) fn record_unsimulated(&self, program: &ReferenceProgram, index: usize) {
+        if let Some(key) = step_keys(program).into_iter().nth(index) {
+            self.state.lock().unwrap().unsimulated.insert(key);
+        }
+    }
+
+    /// Records the gadget chosen by `conflict` as refuted for its step of `program`, if
+    /// `conflict` shows that the gadget's semantics alone are incompatible with the step, or that
+    /// the gadget can't satisfy the preconditions or postconditions in that step.
+    pub(crate) fn record_conflict(
+        &self,
+        program: &ReferenceProgram,
+        candidates: &Candidates,
+        conflict: &ConflictClause,
+    ) {
+        let [decision] = conflict.decisions() else {
+            return;
+        };
+        // restrictions from the preconditions or postconditions carry over too: a step at the
+        // same offset with the same instructions is first (or last) in every partition
+        let local = conflict.stages.iter().all(|stage| {
+            matches!(
+                stage,
+                TheoryStage::CombinedSemantics
+                    | TheoryStage::Precondition
+                    | TheoryStage::Postcondition
+            )
+        });
+        if conflict.stages.is_empty() || !local {
+            return;
+        }
+        if let Some(key) = step_keys(program).into_iter().nth(decision.index) {
+            let address = candidates.candidates[decision.index][decision.choice].address();
+            let class = !conflict.depends_on_address();
+            let mut state = self.state.lock().unwrap();
+            let refuted = state.refuted.entry(key).or_default();
+            *refuted.entry(address).or_default() |= class;
+        }
+    }
+
+    /// Records every step of `program` as satisfiable.
+    pub(crate) fn record_satisfiable(&self, program: &ReferenceProgram) {
+        let mut state = self.state.lock().unwrap();
+        state.satisfiable.extend(step_keys(program));
+    }
+
+    /// For each step of `program`, the addresses of the gadgets refuted for it, mapped to whether
+    /// the refutation holds for the whole class of the gadget.
+    pub(crate) fn refuted_addresses(&self, program: &ReferenceProgram) -> Vec<HashMap<u64, bool>> {
+        let state = self.state.lock().unwrap();
+        step_keys(program)
+            .iter()
+            .map(|key| state.refuted.get(key).cloned().unwrap_or_default())
+            .collect()
+    }
+
+    /// Removes and returns the next partition worth searching. Partitions with a step that
+    /// can't be synthesized are dropped; of the rest, the first one with the fewest steps not
+    /// yet known to be satisfiable is chosen.
+    pub(crate) fn next_partition(
+        &self,
+        partitions: &mut Vec<ReferenceProgram>,
+    ) -> Option<ReferenceProgram> {
+        let state = self.state.lock().unwrap();
+        partitions.retain(|p| {
+            let viable = !step_keys(p).iter().any(|k| state.unsimulated.contains(k));
+            if !viable {
+                event!(
+                    Level::INFO,
+                    "Skipping partition with a step that has no candidates:\n{}",
+                    p
+                );
+            }
+            viable
+        });
+        let unknown = |p: &ReferenceProgram| {
+            step_keys(p)
+                .iter()
+                .filter(|k| !state.satisfiable.contains(k))
+                .count()
+        };
+        let (index, _) = partitions
+            .iter()
+            .enumerate()
+            .min_by_key(|(_, p)| unknown(p))?;
+        Some(partitions.remove(index))
+    }
+}
+
+/// The partitions of `program`, smallest first.
+pub(crate) fn ordered_partitions(program: &ReferenceProgram) -> Vec<ReferenceProgram> {
+    let mut ordering: Vec<ReferenceProgram> = program.partitions().collect();
+    // todo: gross hack to avoid rewriting the partitioning algorithm to be breadth-first
+    ordering.sort_by_key(ReferenceProgram::len);
+    ordering
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::gadget::candidates::CandidateBuilder;
+    use crate::reference_program::ReferenceProgram;
+    use crate::reference_program::step::Step;
+    use crate::synthesis::Decision;
+    use crate::synthesis::pcode_theory::conflict_clause::ConflictClause;
+    use crate::synthesis::pcode_theory::theory_constraint::TheoryStage;
+    use crate::synthesis::refutations::{Refutations, ordered_partitions};
+    use crate::test_util::{copy, gadget, instruction};
+
+    /// A program of one instruction per step, at consecutive addresses.
+    fn program(instructions: usize) -> ReferenceProgram {
+        ReferenceProgram::from_steps(
+            (0..instructions as u64)
+                .map(|i| Step::from_instr(instruction(0x400000 + i, vec![copy(i, 0)])))
+                .collect(),
+        )
+    }
+
+    /// The address of the first instruction of each step of `program`.
+    fn starts(program: &ReferenceProgram) -> Vec<u64> {
+        program
+            .steps()
+            .iter()
+            .map(|s| s.instructions()[0].address)
+            .collect()
+    }
+
+    #[test]
+    fn test_unsimulated_partition_dropped() {
+        let refutations = Refutations::default();
+        let mut partitions = ordered_partitions(&program(2));
+        // no gadget implements both instructions at once
+        refutations.record_unsimulated(&partitions[0], 0);
+        let next = refutations.next_partition(&mut partitions).unwrap();
+        assert_eq!(starts(&next), vec![0x400000, 0x400001]);
+        assert!(refutations.next_partition(&mut partitions).is_none());
+    }
+
+    #[test]
+    fn test_satisfiable_partitions_first() {
+        let refutations = Refutations::default();
+        let mut partitions = ordered_partitions(&program(3));
+        // a chain was found for the partition of one instruction per step
+        refutations.record_satisfiable(&program(3));
+        let order: Vec<_> = std::iter::from_fn(|| refutations.next_partition(&mut partitions))
+            .map(|p| starts(&p))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                vec![0x400000, 0x400001, 0x400002],
+                // the rest have one unknown step each, and keep their order
+                vec![0x400000],
+                vec![0x400000, 0x400002],
+                vec![0x400000, 0x400001],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_record_conflict() {
+        let candidates = CandidateBuilder::default()
+            .with_random_sample_size(2)
+            .build(
+                [
+                    gadget(0x1000, vec![copy(1, 0)]),
+                    gadget(0x2000, vec![copy(2, 0)]),
+                ]
+                .iter()
+                .map(|g| vec![Some(g), Some(g)]),
+            )
+            .unwrap();
+        let conflict = |decisions: &[(usize, usize)], stages: Vec<TheoryStage>| {
+            let decisions: Vec<Decision> = decisions
+                .iter()
+                .map(|&(index, choice)| Decision { index, choice })
+                .collect();
+            let mut conflict = ConflictClause::from(decisions.iter());
+            conflict.stages = stages;
+            conflict
+        };
+        let refutations = Refutations::default();
+        let split = program(2);
+        refutations.record_conflict(
+            &split,
+            &candidates,
+            &conflict(&[(0, 0)], vec![TheoryStage::CombinedSemantics]),
+        );
+        refutations.record_conflict(
+            &split,
+            &candidates,
+            &conflict(&[(1, 1)], vec![TheoryStage::Postcondition]),
+        );
+        // conflicts spanning slots, or involving how slots connect, are not about one step
+        refutations.record_conflict(
+            &split,
+            &candidates,
+            &conflict(&[(0, 1), (1, 0)], vec![TheoryStage::CombinedSemantics]),
+        );
+        refutations.record_conflict(
+            &split,
+            &candidates,
+            &conflict(&[(0, 1)], vec![TheoryStage::Branch]),
+        );
+        // refutations by semantics alone hold for the whole class
+        assert_eq!(
+            refutations.refuted_addresses(&split),
+            vec![
+                HashMap::from([(0x1000, true)]),
+                HashMap::from([(0x2000, false)])
+            ]
+        );
+        // the combined step is a different step, which nothing is known about
+        let combined = ordered_partitions(&split).remove(0);
+        assert_eq!(
+            refutations.refuted_addresses(&combined),
+            vec![HashMap::new()]
+        );
+    }
+}
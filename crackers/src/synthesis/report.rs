@@ -0,0 +1,317 @@
+use jingle::display::JingleDisplayable;
+use jingle::modeling::{ModelingContext, State};
+use jingle::sleigh::{Instruction, SpaceType};
+use jingle::varnode::ResolvedVarnode;
+use serde::{Deserialize, Serialize};
+use z3::ast::{Ast, BV};
+
+use crate::synthesis::SynthesisProgress;
+use crate::synthesis::assignment_model::AssignmentModel;
+use crate::synthesis::assignment_model::builder::AssignmentModelBuilder;
+use crate::synthesis::selection_strategy::SelectionFailure;
+
+/// Version of the [`SynthesisReport`] document format. This is bumped whenever a field is
+/// removed or changes meaning; new fields may be added without a bump.
+pub const REPORT_FORMAT_VERSION: u32 = 1;
+
+/// A machine-readable summary of a synthesis run, intended to be serialized (e.g. to JSON)
+/// and consumed by other tools.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SynthesisReport {
+    pub version: u32,
+    #[serde(flatten)]
+    pub result: ReportResult,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub enum ReportResult {
+    Sat {
+        slots: Vec<SlotReport>,
+        #[serde(default)]
+        statistics: SynthesisProgress,
+    },
+    Unsat {
+        failed_slots: Vec<usize>,
+        #[serde(default)]
+        statistics: SynthesisProgress,
+    },
+    Timeout(SynthesisProgress),
+    Error {
+        message: String,
+    },
+}
+
+/// A single slot of a found chain: the reference step it implements and the gadget chosen for it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SlotReport {
+    pub index: usize,
+    pub step: Vec<InstructionReport>,
+    pub gadget_address: u64,
+    pub gadget: Vec<InstructionReport>,
+    pub inputs: Vec<LocationValue>,
+    pub outputs: Vec<LocationValue>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstructionReport {
+    pub address: u64,
+    pub disassembly: String,
+}
+
+/// A location read or written by a gadget and its value in the model. Values are hex strings
+/// so that locations wider than 64 bits are represented faithfully.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LocationValue {
+    pub location: String,
+    pub value: Option<String>,
+}
+
+impl SynthesisReport {
+    pub fn new(result: ReportResult) -> Self {
+        Self {
+            version: REPORT_FORMAT_VERSION,
+            result,
+        }
+    }
+
+    pub fn from_model<T: ModelingContext>(
+        builder: &AssignmentModelBuilder,
+        model: &AssignmentModel<T>,
+    ) -> Self {
+        let slots = builder
+            .gadgets
+            .iter()
+            .zip(&model.gadgets)
+            .enumerate()
+            .map(|(index, (gadget, modeled))| SlotReport {
+                index,
+                step: builder
+                    .templates
+                    .steps()
+                    .get(index)
+                    .map(|s| {
+                        s.instructions()
+                            .iter()
+                            .map(InstructionReport::from)
+                            .collect()
+                    })
+                    .unwrap_or_default(),
+                gadget_address: gadget.address(),
+                gadget: gadget
+                    .instructions
+                    .iter()
+                    .map(InstructionReport::from)
+                    .collect(),
+                inputs: evaluate_locations(
+                    model,
+                    modeled.get_inputs(),
+                    modeled.get_original_state(),
+                ),
+                outputs: evaluate_locations(
+                    model,
+                    modeled.get_outputs(),
+                    modeled.get_final_state(),
+                ),
+            })
+            .collect();
+        Self::new(ReportResult::Sat {
+            slots,
+            statistics: builder.statistics.clone(),
+        })
+    }
+
+    pub fn unsat(failure: &SelectionFailure) -> Self {
+        Self::new(ReportResult::Unsat {
+            failed_slots: failure.indices.clone(),
+            statistics: failure.statistics.clone(),
+        })
+    }
+
+    pub fn timeout(progress: &SynthesisProgress) -> Self {
+        Self::new(ReportResult::Timeout(progress.clone()))
+    }
+
+    pub fn error<T: ToString>(message: T) -> Self {
+        Self::new(ReportResult::Error {
+            message: message.to_string(),
+        })
+    }
+}
+
+impl From<&Instruction> for InstructionReport {
+    fn from(value: &Instruction) -> Self {
+        Self {
+            address: value.address,
+            disassembly: value.disassembly.to_string(),
+        }
+    }
+}
+
+fn evaluate_locations<T: ModelingContext, I: IntoIterator<Item = ResolvedVarnode>>(
+    model: &AssignmentModel<T>,
+    vns: I,
+    state: &State,
+) -> Vec<LocationValue> {
+    let info = &model.arch_info;
+    let mut values: Vec<LocationValue> = vns
+        .into_iter()
+        .filter_map(|vn| {
+            let location = match &vn {
+                ResolvedVarnode::Direct(d) => {
+                    let space = info.get_space(d.space_index)?;
+                    if space._type != SpaceType::IPTR_PROCESSOR {
+                        return None;
+                    }
+                    format!("{}", d.display(info))
+                }
+                ResolvedVarnode::Indirect(i) => {
+                    let space = info.get_space(i.pointer_space_idx)?;
+                    let pointer = model
+                        .model()
+                        .eval(&i.pointer, true)
+                        .and_then(|p| p.as_u64())
+                        .map(|p| format!("{p:#x}"))
+                        .unwrap_or("?".to_string());
+                    format!("{}[{}]:{:x}", space.name, pointer, i.access_size_bytes)
+                }
+            };
+            let value = state
+                .read_resolved(&vn)
+                .ok()
+                .and_then(|bv| model.model().eval(&bv, true))
+                .and_then(|bv| bv_to_hex(&bv));
+            Some(LocationValue { location, value })
+        })
+        .collect();
+    values.sort_by(|a, b| a.location.cmp(&b.location));
+    values.dedup();
+    values
+}
+
+pub(crate) fn bv_to_hex(bv: &BV) -> Option<String> {
+    let size = bv.get_size();
+    let mut digits = String::new();
+    let mut hi = size;
+    while hi > 0 {
+        let lo = hi.saturating_sub(64);
+        let chunk = bv.extract(hi - 1, lo).simplify().as_u64()?;
+        let width = (hi - lo).div_ceil(4) as usize;
+        digits.push_str(&format!("{chunk:0width$x}"));
+        hi = lo;
+    }
+    Some(format!("0x{digits}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use z3::ast::BV;
+
+    use crate::synthesis::SynthesisProgress;
+    use crate::synthesis::report::{
+        InstructionReport, LocationValue, REPORT_FORMAT_VERSION, ReportResult, SlotReport,
+        SynthesisReport, bv_to_hex,
+    };
+    use crate::synthesis::selection_strategy::SelectionFailure;
+
+    fn slot() -> SlotReport {
+        SlotReport {
+            index: 0,
+            step: vec![InstructionReport {
+                address: 0,
+                disassembly: "POP RDI".to_string(),
+            }],
+            gadget_address: 0x401000,
+            gadget: vec![
+                InstructionReport {
+                    address: 0x401000,
+                    disassembly: "POP RDI".to_string(),
+                },
+                InstructionReport {
+                    address: 0x401001,
+                    disassembly: "RET".to_string(),
+                },
+            ],
+            inputs: vec![LocationValue {
+                location: "RSP".to_string(),
+                value: Some("0x0000000000007000".to_string()),
+            }],
+            outputs: vec![LocationValue {
+                location: "RDI".to_string(),
+                value: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let statistics = SynthesisProgress {
+            assignments_checked: 3,
+            ..Default::default()
+        };
+        let reports = [
+            SynthesisReport::new(ReportResult::Sat {
+                slots: vec![slot()],
+                statistics: statistics.clone(),
+            }),
+            SynthesisReport::unsat(&SelectionFailure {
+                indices: vec![1, 2],
+                statistics: statistics.clone(),
+            }),
+            SynthesisReport::timeout(&statistics),
+            SynthesisReport::error("no library"),
+        ];
+        for report in reports {
+            let json = serde_json::to_string(&report).unwrap();
+            let parsed: SynthesisReport = serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed, report);
+        }
+    }
+
+    #[test]
+    fn test_format() {
+        let report = SynthesisReport::unsat(&SelectionFailure {
+            indices: vec![1, 2],
+            statistics: SynthesisProgress::default(),
+        });
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["version"], REPORT_FORMAT_VERSION);
+        assert_eq!(json["status"], "unsat");
+        assert_eq!(json["failed_slots"], serde_json::json!([1, 2]));
+        assert!(json["statistics"].is_object());
+
+        let error = serde_json::to_value(SynthesisReport::error("no library")).unwrap();
+        assert_eq!(
+            error,
+            serde_json::json!({
+                "version": REPORT_FORMAT_VERSION,
+                "status": "error",
+                "message": "no library",
+            })
+        );
+
+        // statistics are optional, so reports written before they were added still parse
+        let old = r#"{"version": 1, "status": "sat", "slots": []}"#;
+        let parsed: SynthesisReport = serde_json::from_str(old).unwrap();
+        assert_eq!(
+            parsed.result,
+            ReportResult::Sat {
+                slots: vec![],
+                statistics: SynthesisProgress::default(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_bv_to_hex() {
+        assert_eq!(bv_to_hex(&BV::from_u64(0xab, 8)).unwrap(), "0xab");
+        // digits are padded to the width of the value
+        assert_eq!(bv_to_hex(&BV::from_u64(0x1, 12)).unwrap(), "0x001");
+        let wide = BV::from_u64(0x1122, 64).concat(BV::from_u64(0x3344, 64));
+        assert_eq!(
+            bv_to_hex(&wide).unwrap(),
+            "0x00000000000011220000000000003344"
+        );
+        assert!(bv_to_hex(&BV::new_const("x", 8)).is_none());
+    }
+}
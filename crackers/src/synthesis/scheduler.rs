@@ -0,0 +1,313 @@
+//! Concurrent exploration of the partitions of a reference program.
+//!
+//! Most partitions are refuted quickly, but a single hard one can take much longer than all
+//! others combined. Rather than searching partitions one after another, the
+//! [`PartitionScheduler`] searches several at once, splitting the configured worker budget
+//! between them, and returns the first chain found by any of them. What the partitions learn
+//! about their steps is shared as they go.
+
+use std::sync::mpsc::Sender;
+use std::time::Instant;
+
+use tracing::{Level, event};
+
+use crate::error::CrackersError;
+use crate::reference_program::ReferenceProgram;
+use crate::synthesis::builder::SynthesisParams;
+use crate::synthesis::cancellation::CancellationToken;
+use crate::synthesis::refutations::{Refutations, ordered_partitions};
+use crate::synthesis::{AssignmentSynthesis, DecisionResult, SynthesisProgress};
+
+/// The outcome of searching one partition.
+struct PartitionOutcome {
+    slot: usize,
+    result: Result<DecisionResult, CrackersError>,
+    progress: SynthesisProgress,
+}
+
+pub(crate) struct PartitionScheduler {
+    base_config: SynthesisParams,
+    concurrency: usize,
+}
+
+impl PartitionScheduler {
+    pub(crate) fn new(base_config: &SynthesisParams) -> Self {
+        // every partition needs at least one worker of the shared budget
+        let concurrency = base_config
+            .concurrent_partitions
+            .min(base_config.parallel)
+            .max(1);
+        Self {
+            base_config: base_config.clone(),
+            concurrency,
+        }
+    }
+
+    /// The share of the worker budget of the partition searched in `slot`. Workers that do not
+    /// divide evenly go to the first slots.
+    fn workers(&self, slot: usize) -> usize {
+        let parallel = self.base_config.parallel.max(1);
+        parallel / self.concurrency + usize::from(slot < parallel % self.concurrency)
+    }
+
+    pub(crate) fn decide(&self) -> Result<DecisionResult, CrackersError> {
+        let mut partitions = ordered_partitions(&self.base_config.reference_program);
+        let refutations = Refutations::default();
+        // the budget covers all partitions, not each one
+        let deadline = self.base_config.timeout.map(|t| Instant::now() + t);
+        let cancellation = &self.base_config.cancellation;
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut running: Vec<Option<CancellationToken>> = vec![None; self.concurrency];
+        let mut progress = SynthesisProgress::default();
+        let mut last: Option<DecisionResult> = None;
+        // the first error a partition failed with; the failed partition was never ruled out, so
+        // it is returned rather than `last`
+        let mut error: Option<CrackersError> = None;
+        let mut outcome: Option<Result<DecisionResult, CrackersError>> = None;
+        std::thread::scope(|s| {
+            let start = |slot: usize, instructions: ReferenceProgram| {
+                let token = cancellation.child();
+                let mut config = self.base_config.clone();
+                config.reference_program = instructions;
+                config.parallel = self.workers(slot);
+                config.cancellation = token.clone();
+                let sender: Sender<PartitionOutcome> = sender.clone();
+                let refutations = refutations.clone();
+                s.spawn(move || search_partition(slot, config, refutations, deadline, sender));
+                token
+            };
+            for (slot, running) in running.iter_mut().enumerate() {
+                let Some(instructions) = refutations.next_partition(&mut partitions) else {
+                    break;
+                };
+                *running = Some(start(slot, instructions));
+            }
+            while running.iter().any(Option::is_some) {
+                let Ok(finished) = receiver.recv() else {
+                    break;
+                };
+                running[finished.slot] = None;
+                progress.merge(&finished.progress);
+                match finished.result {
+                    Ok(DecisionResult::Unsat(e)) if outcome.is_none() => {
+                        event!(Level::WARN, "{:?}", e);
+                        last = Some(DecisionResult::Unsat(e));
+                    }
+                    // a partition with a step no gadget implements is skipped, as when searching
+                    // them one at a time
+                    Err(CrackersError::UnsimulatedOperation { .. }) if outcome.is_none() => {}
+                    // already logged by the partition's thread
+                    Err(e) if outcome.is_none() && !matches!(e, CrackersError::Cancelled) => {
+                        error.get_or_insert(e);
+                    }
+                    // the remaining partitions were cancelled in favor of an earlier outcome
+                    _ if outcome.is_some() => continue,
+                    result => {
+                        // a chain, a timeout, or a cancellation ends the whole search
+                        for token in running.iter().flatten() {
+                            token.cancel();
+                        }
+                        outcome = Some(result);
+                        continue;
+                    }
+                }
+                if let Some(instructions) = refutations.next_partition(&mut partitions) {
+                    running[finished.slot] = Some(start(finished.slot, instructions));
+                }
+            }
+        });
+        let mut result = match outcome {
+            Some(result) => result?,
+            None => match error {
+                Some(e) => return Err(e),
+                // Only an empty specification can possibly result in this being `None`
+                None => last.ok_or(CrackersError::EmptySpecification)?,
+            },
+        };
+        result.set_statistics(progress);
+        Ok(result.incomplete_as_timeout())
+    }
+}
+
+fn search_partition(
+    slot: usize,
+    config: SynthesisParams,
+    refutations: Refutations,
+    deadline: Option<Instant>,
+    sender: Sender<PartitionOutcome>,
+) {
+    event!(
+        Level::INFO,
+        "Attempting Synthesis of:\n{}",
+        config.reference_program
+    );
+    let (result, progress) = match AssignmentSynthesis::new_with_refutations(&config, refutations) {
+        Ok(synth) => {
+            let mut synth = synth.with_deadline(deadline);
+            let result = synth.improve(AssignmentSynthesis::decide_next);
+            if let Err(e) = &result
+                && !matches!(e, CrackersError::Cancelled)
+            {
+                event!(Level::ERROR, "{:?}", e);
+            }
+            (result, synth.progress().clone())
+        }
+        Err(e) => {
+            event!(Level::WARN, "Failed to find gadgets for partition");
+            (Err(e), SynthesisProgress::default())
+        }
+    };
+    let _ = sender.send(PartitionOutcome {
+        slot,
+        result,
+        progress,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+
+    use z3::ast::{BV, Bool};
+
+    use crate::error::CrackersError;
+    use crate::gadget::library::GadgetLibrary;
+    use crate::reference_program::ReferenceProgram;
+    use crate::reference_program::step::Step;
+    use crate::synthesis::DecisionResult;
+    use crate::synthesis::builder::{
+        StateConstraintGenerator, SynthesisParams, SynthesisParamsBuilder,
+        SynthesisSelectionStrategy,
+    };
+    use crate::synthesis::scheduler::PartitionScheduler;
+    use crate::test_util::{arch_info, copy, gadget, instruction, register};
+
+    /// A synthesis of a program setting `RAX` to 1 and then to 2, from gadgets doing each,
+    /// whose chains must satisfy `precondition`. Its partitions are the single step doing both,
+    /// whose only candidate is at 0x2000, and the two steps doing one each, whose chain starts
+    /// at 0x1000.
+    fn params(
+        precondition: Arc<StateConstraintGenerator>,
+        parallel: usize,
+        concurrent_partitions: usize,
+    ) -> SynthesisParams {
+        let steps = [(0x400000, 1), (0x400001, 2)]
+            .map(|(address, value)| Step::from_instr(instruction(address, vec![copy(value, 0)])));
+        let gadgets = vec![
+            gadget(0x1000, vec![copy(1, 0)]),
+            gadget(0x2000, vec![copy(2, 0)]),
+        ];
+        SynthesisParamsBuilder::default()
+            .seed(0)
+            .selection_strategy(SynthesisSelectionStrategy::SatStrategy)
+            .gadget_library(GadgetLibrary::from_gadgets(arch_info(), gadgets))
+            .candidates_per_slot(2)
+            .parallel(parallel)
+            .concurrent_partitions(concurrent_partitions)
+            .reference_program(ReferenceProgram::from_steps(steps.to_vec()))
+            .preconditions(vec![precondition])
+            .prefilter_candidates(false)
+            .build()
+            .unwrap()
+    }
+
+    /// A precondition that can't be decided in reasonable time for chains starting at one of
+    /// `addresses`: it asks for the two halves of `RBX` to factor a 64-bit semiprime.
+    fn hard_at(addresses: &'static [u64]) -> Arc<StateConstraintGenerator> {
+        Arc::new(move |state, addr| {
+            if !addresses.contains(&addr) {
+                return Ok(Bool::from_bool(true));
+            }
+            let rbx = state.read_varnode(&register(8))?;
+            let (low, high) = (rbx.extract(31, 0), rbx.extract(63, 32));
+            let product = low.zero_ext(32) * high.zero_ext(32);
+            let semiprime = BV::from_u64(9790765170742681277, 64);
+            Ok(product.eq(semiprime) & low.ne(BV::from_u64(1, 32)) & high.ne(BV::from_u64(1, 32)))
+        })
+    }
+
+    #[test]
+    fn test_workers() {
+        let always =
+            || -> Arc<StateConstraintGenerator> { Arc::new(|_, _| Ok(Bool::from_bool(true))) };
+        let shares = |parallel, concurrent| {
+            let scheduler = PartitionScheduler::new(&params(always(), parallel, concurrent));
+            (0..scheduler.concurrency)
+                .map(|slot| scheduler.workers(slot))
+                .collect::<Vec<_>>()
+        };
+        // the remainder goes to the first slots
+        assert_eq!(shares(5, 2), vec![3, 2]);
+        assert_eq!(shares(7, 3), vec![3, 2, 2]);
+        // never more partitions than workers, and never none
+        assert_eq!(shares(2, 4), vec![1, 1]);
+        assert_eq!(shares(0, 2), vec![1]);
+    }
+
+    #[test]
+    fn test_cancels_on_chain() {
+        // the single-step partition is stuck on its only chain when the other finds one
+        let start = Instant::now();
+        let result = PartitionScheduler::new(&params(hard_at(&[0x2000]), 2, 2))
+            .decide()
+            .unwrap();
+        let DecisionResult::AssignmentFound(found) = result else {
+            panic!("expected a chain, got {result:?}");
+        };
+        assert_eq!(found.gadgets.len(), 2);
+        assert!(start.elapsed() < Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_cancels_on_timeout() {
+        // both partitions are stuck until the shared deadline
+        let mut params = params(hard_at(&[0x1000, 0x2000]), 2, 2);
+        params.timeout = Some(Duration::from_millis(200));
+        let start = Instant::now();
+        let result = PartitionScheduler::new(&params).decide().unwrap();
+        assert!(matches!(result, DecisionResult::Timeout(_)));
+        assert!(start.elapsed() < Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_error_over_unsat() {
+        // the single-step partition fails, and the other is ruled out
+        let failing: Arc<StateConstraintGenerator> = Arc::new(|_, addr| {
+            if addr == 0x2000 {
+                return Err(CrackersError::UnknownRegister("RDX".to_string()));
+            }
+            Ok(Bool::from_bool(false))
+        });
+        assert!(matches!(
+            PartitionScheduler::new(&params(failing, 2, 2)).decide(),
+            Err(CrackersError::UnknownRegister(_))
+        ));
+
+        // without the failure, the program can't be synthesized
+        let never =
+            || -> Arc<StateConstraintGenerator> { Arc::new(|_, _| Ok(Bool::from_bool(false))) };
+        assert!(matches!(
+            PartitionScheduler::new(&params(never(), 2, 2)).decide(),
+            Ok(DecisionResult::Unsat(_))
+        ));
+
+        // nor is it when the single step has no candidates, which is not an error
+        let mut params = params(never(), 2, 2);
+        let steps = [(0x400000, 0), (0x400001, 16)]
+            .map(|(address, output)| Step::from_instr(instruction(address, vec![copy(1, output)])));
+        params.reference_program = ReferenceProgram::from_steps(steps.to_vec());
+        params.gadget_library = Arc::new(GadgetLibrary::from_gadgets(
+            arch_info(),
+            vec![
+                gadget(0x1000, vec![copy(1, 0)]),
+                gadget(0x2000, vec![copy(1, 16)]),
+            ],
+        ));
+        assert!(matches!(
+            PartitionScheduler::new(&params).decide(),
+            Ok(DecisionResult::Unsat(_))
+        ));
+    }
+}
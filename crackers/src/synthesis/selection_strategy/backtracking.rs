@@ -0,0 +1,191 @@
+//! A [`SelectionStrategy`] that searches assignments by plain backtracking, without an SMT
+//! solver.
+//!
+//! It is meant as an example of a custom strategy rather than a replacement for the bundled
+//! ones: it tries the candidates of each slot cheapest first and prunes a partial assignment as
+//! soon as it makes all decisions of a learned clause, but it learns nothing beyond the clauses
+//! it is given. To use it, pass [`BacktrackingStrategy`] to
+//! [`SynthesisParamsBuilder::selection_strategy`](crate::synthesis::builder::SynthesisParamsBuilder::selection_strategy).
+
+use std::time::{Duration, Instant};
+
+use crate::error::CrackersError;
+use crate::error::CrackersError::BooleanAssignmentTimeout;
+use crate::synthesis::Decision;
+use crate::synthesis::cancellation::CancellationToken;
+use crate::synthesis::pcode_theory::conflict_clause::ConflictClause;
+use crate::synthesis::selection_strategy::AssignmentResult::{Failure, Success};
+use crate::synthesis::selection_strategy::{
+    AssignmentResult, Candidate, SelectionFailure, SelectionStrategy, StrategyBuilder,
+};
+use crate::synthesis::slot_assignments::SlotAssignments;
+
+/// Builds a [`BacktrackingProblem`] for each synthesis.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct BacktrackingStrategy;
+
+impl StrategyBuilder for BacktrackingStrategy {
+    fn build(
+        &self,
+        candidates: &[Vec<Candidate<'_>>],
+        _cancellation: &CancellationToken,
+    ) -> Result<Box<dyn SelectionStrategy>, CrackersError> {
+        let costs: Vec<Vec<usize>> = candidates
+            .iter()
+            .map(|slot| slot.iter().map(|c| c.cost).collect())
+            .collect();
+        Ok(Box::new(BacktrackingProblem::with_costs(&costs)))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BacktrackingProblem {
+    /// For each slot, its choices in the order they are tried.
+    order: Vec<Vec<usize>>,
+    costs: Vec<Vec<usize>>,
+    /// Learned clauses, each filed under the last slot it makes a decision for, so that it is
+    /// checked once that slot is assigned.
+    clauses: Vec<Vec<Vec<Decision>>>,
+    /// Blocked assignments, with the number of slots other assignments may share with them.
+    blocked: Vec<(Vec<usize>, usize)>,
+    /// Set when an empty clause is learned; no assignment satisfies it.
+    infeasible: bool,
+    timeout: Option<Duration>,
+}
+
+impl BacktrackingProblem {
+    /// Creates a problem whose choices have the given costs; cheaper choices are tried first.
+    pub fn with_costs(costs: &[Vec<usize>]) -> Self {
+        let mut prob = Self {
+            order: vec![vec![]; costs.len()],
+            costs: vec![vec![]; costs.len()],
+            clauses: vec![vec![]; costs.len()],
+            blocked: vec![],
+            infeasible: false,
+            timeout: None,
+        };
+        for (index, slot) in costs.iter().enumerate() {
+            prob.extend_slot_with_costs(index, slot);
+        }
+        prob
+    }
+
+    /// Like [`extend_slot`](SelectionStrategy::extend_slot), with the costs of the new choices.
+    pub fn extend_slot_with_costs(&mut self, index: usize, costs: &[usize]) {
+        let slot_costs = &mut self.costs[index];
+        slot_costs.extend_from_slice(costs);
+        let mut order: Vec<usize> = (0..slot_costs.len()).collect();
+        order.sort_by_key(|&choice| slot_costs[choice]);
+        self.order[index] = order;
+    }
+
+    fn add_clause(&mut self, decisions: &[Decision]) {
+        match decisions.iter().map(|d| d.index).max() {
+            Some(last) => self.clauses[last].push(decisions.to_vec()),
+            None => self.infeasible = true,
+        }
+    }
+
+    /// Whether the assignment of the first `choices.len()` slots is consistent with the
+    /// clauses and blocks, given that only its last slot was just assigned.
+    fn consistent(&self, choices: &[usize]) -> bool {
+        let last = choices.len() - 1;
+        let violates = |clause: &Vec<Decision>| clause.iter().all(|d| choices[d.index] == d.choice);
+        if self.clauses[last].iter().any(violates) {
+            return false;
+        }
+        self.blocked.iter().all(|(assignment, bound)| {
+            let same = choices
+                .iter()
+                .zip(assignment)
+                .filter(|(a, b)| a == b)
+                .count();
+            same <= *bound
+        })
+    }
+
+    /// Extends `choices` to a complete consistent assignment, if there is one.
+    fn search(
+        &self,
+        choices: &mut Vec<usize>,
+        deadline: Option<Instant>,
+    ) -> Result<bool, CrackersError> {
+        if choices.len() == self.order.len() {
+            return Ok(true);
+        }
+        if deadline.is_some_and(|d| Instant::now() >= d) {
+            return Err(BooleanAssignmentTimeout);
+        }
+        for &choice in &self.order[choices.len()] {
+            choices.push(choice);
+            if self.consistent(choices) && self.search(choices, deadline)? {
+                return Ok(true);
+            }
+            choices.pop();
+        }
+        Ok(false)
+    }
+}
+
+impl SelectionStrategy for BacktrackingProblem {
+    fn get_assignments(&mut self) -> Result<AssignmentResult, CrackersError> {
+        let deadline = self.timeout.map(|t| Instant::now() + t);
+        let mut choices = Vec::with_capacity(self.order.len());
+        if !self.infeasible && self.search(&mut choices, deadline)? {
+            let assignment = SlotAssignments::from(choices);
+            self.add_clause(&assignment.to_decisions());
+            return Ok(Success(assignment));
+        }
+        // backtracking doesn't tell which slots are to blame
+        Ok(Failure(SelectionFailure {
+            indices: (0..self.order.len()).collect(),
+            statistics: Default::default(),
+        }))
+    }
+
+    fn add_theory_clause(&mut self, clause: &ConflictClause) {
+        self.add_clause(clause.decisions());
+    }
+
+    fn extend_slot(&mut self, index: usize, candidates: &[Candidate<'_>]) {
+        let costs: Vec<usize> = candidates.iter().map(|c| c.cost).collect();
+        self.extend_slot_with_costs(index, &costs);
+    }
+
+    fn block_assignment(&mut self, assignment: &SlotAssignments, min_distance: usize) {
+        let bound = assignment.choices().len().saturating_sub(min_distance);
+        self.blocked.push((assignment.choices().to_vec(), bound));
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(timeout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::synthesis::Decision;
+    use crate::synthesis::pcode_theory::conflict_clause::ConflictClause;
+    use crate::synthesis::selection_strategy::backtracking::BacktrackingProblem;
+    use crate::synthesis::selection_strategy::{AssignmentResult, SelectionStrategy};
+
+    fn next(prob: &mut BacktrackingProblem) -> Option<Vec<usize>> {
+        match prob.get_assignments().unwrap() {
+            AssignmentResult::Success(a) => Some(a.choices().to_vec()),
+            AssignmentResult::Failure(_) => None,
+        }
+    }
+
+    #[test]
+    fn test_cheapest_first() {
+        let mut prob = BacktrackingProblem::with_costs(&[vec![2, 1], vec![1, 3]]);
+        assert_eq!(next(&mut prob), Some(vec![1, 0]));
+        prob.add_theory_clause(&ConflictClause::from(Decision {
+            index: 0,
+            choice: 1,
+        }));
+        assert_eq!(next(&mut prob), Some(vec![0, 0]));
+        assert_eq!(next(&mut prob), Some(vec![0, 1]));
+        assert_eq!(next(&mut prob), None);
+    }
+}
@@ -1,16 +1,22 @@
+use std::time::Duration;
+
 use jingle::modeling::{ModeledBlock, ModeledInstruction};
+use jingle::sleigh::PcodeOperation;
 #[cfg(feature = "pyo3")]
 use pyo3::pyclass;
 
 use crate::error::CrackersError;
 use crate::gadget::Gadget;
+use crate::synthesis::SynthesisProgress;
+use crate::synthesis::cancellation::CancellationToken;
 use crate::synthesis::pcode_theory::conflict_clause::ConflictClause;
-use crate::synthesis::selection_strategy::optimization_problem::OptimizationProblem;
-use crate::synthesis::selection_strategy::sat_problem::SatProblem;
 use crate::synthesis::slot_assignments::SlotAssignments;
 
 // mod optimization_problem;
+pub mod backtracking;
+pub mod objective;
 pub mod optimization_problem;
+pub mod portfolio;
 pub mod sat_problem;
 
 pub trait InstrLen {
@@ -52,39 +58,87 @@ pub enum AssignmentResult {
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
-#[cfg_attr(feature = "pyo3", pyclass)]
+#[cfg_attr(feature = "pyo3", pyclass(get_all))]
 pub struct SelectionFailure {
     pub indices: Vec<usize>,
+    /// Statistics of the search that ended in this failure.
+    pub statistics: SynthesisProgress,
+}
+/// A candidate gadget for a slot, as seen by a [`SelectionStrategy`].
+#[derive(Debug, Clone, Copy)]
+pub struct Candidate<'a> {
+    pub gadget: &'a Gadget,
+    /// Cost of the gadget under the configured
+    /// [objectives](crate::synthesis::builder::SynthesisParams::objectives); its number of
+    /// instructions by default.
+    pub cost: usize,
 }
-pub trait SelectionStrategy {
-    fn initialize<T: InstrLen>(choices: &[Vec<T>]) -> Self;
 
-    fn get_assignments(&mut self) -> Result<AssignmentResult, CrackersError>;
+impl Candidate<'_> {
+    pub fn address(&self) -> u64 {
+        self.gadget.address()
+    }
 
-    fn add_theory_clause(&mut self, clause: &ConflictClause);
+    pub fn ops(&self) -> impl Iterator<Item = &PcodeOperation> {
+        self.gadget.ops()
+    }
+}
 
-    fn derive_var_name(target_index: usize, gadget_index: usize) -> String {
-        format!("i{target_index}_g{gadget_index}")
+impl InstrLen for Candidate<'_> {
+    fn instr_len(&self) -> usize {
+        self.gadget.instr_len()
     }
 }
 
-pub enum OuterProblem {
-    SatProb(SatProblem),
-    OptimizeProb(OptimizationProblem),
+/// Pairs each of `gadgets` with its cost.
+pub(crate) fn describe<'a>(gadgets: &'a [Gadget], costs: &[usize]) -> Vec<Candidate<'a>> {
+    gadgets
+        .iter()
+        .zip(costs)
+        .map(|(gadget, &cost)| Candidate { gadget, cost })
+        .collect()
 }
 
-impl OuterProblem {
-    pub(crate) fn get_assignments(&mut self) -> Result<AssignmentResult, CrackersError> {
-        match self {
-            OuterProblem::SatProb(s) => s.get_assignments(),
-            OuterProblem::OptimizeProb(o) => o.get_assignments(),
-        }
-    }
+/// The outer problem of a synthesis: it proposes an assignment of candidates to slots, and
+/// learns from the theory why proposed assignments fail. Choices are identified by their
+/// position among the candidates of their slot.
+pub trait SelectionStrategy {
+    /// Returns an assignment not yet returned that satisfies every clause and block added so
+    /// far, or the slots to blame if there is none.
+    fn get_assignments(&mut self) -> Result<AssignmentResult, CrackersError>;
+
+    /// Excludes every assignment making all decisions of `clause`.
+    fn add_theory_clause(&mut self, clause: &ConflictClause);
+
+    /// Adds `candidates` as further choices for the `index`th slot, numbered after its
+    /// existing ones. Clauses and blocked assignments added so far still apply.
+    fn extend_slot(&mut self, index: usize, candidates: &[Candidate<'_>]);
 
-    pub(crate) fn add_theory_clauses(&mut self, clauses: &ConflictClause) {
-        match self {
-            OuterProblem::SatProb(s) => s.add_theory_clause(clauses),
-            OuterProblem::OptimizeProb(o) => o.add_theory_clause(clauses),
-        }
+    /// Excludes every assignment that chooses the same gadgets as `assignment` in more than
+    /// `len - min_distance` slots, i.e. every assignment within `min_distance` of it.
+    fn block_assignment(&mut self, assignment: &SlotAssignments, min_distance: usize);
+
+    /// Limits the time spent on each subsequent call to
+    /// [`get_assignments`](Self::get_assignments). When the limit is hit, it returns
+    /// [`BooleanAssignmentTimeout`](CrackersError::BooleanAssignmentTimeout).
+    fn set_timeout(&mut self, timeout: Duration);
+
+    fn derive_var_name(target_index: usize, gadget_index: usize) -> String
+    where
+        Self: Sized,
+    {
+        format!("i{target_index}_g{gadget_index}")
     }
 }
+
+/// Creates the [`SelectionStrategy`] of each synthesis from the initial candidates of its
+/// slots. Implement this to use a custom strategy; see [`backtracking`] for an example.
+pub trait StrategyBuilder: Send + Sync {
+    /// `cancellation` is cancelled along with the synthesis; strategies that solve on other
+    /// threads should stop them when it is.
+    fn build(
+        &self,
+        candidates: &[Vec<Candidate<'_>>],
+        cancellation: &CancellationToken,
+    ) -> Result<Box<dyn SelectionStrategy>, CrackersError>;
+}
@@ -0,0 +1,459 @@
+//! Costs of candidate gadgets, minimized by the
+//! [`OptimizeStrategy`](crate::synthesis::builder::SynthesisSelectionStrategy::OptimizeStrategy).
+//!
+//! Each [`OptimizationObjective`] assigns every gadget a cost; the cost of a gadget is the
+//! weighted sum of its costs under all configured objectives, and the optimize strategy prefers
+//! chains with the lowest total cost.
+
+use std::path::Path;
+
+use jingle::modeling::{ModeledBlock, ModelingContext};
+use jingle::sleigh::{SleighArchInfo, SpaceType};
+use jingle::varnode::ResolvedVarnode;
+#[cfg(feature = "pyo3")]
+use pyo3::{pyclass, pymethods};
+use serde::{Deserialize, Serialize};
+use z3::ast::{Ast, BV};
+use z3::{SatResult, Solver};
+
+use crate::config::constraint::PointerRange;
+use crate::error::CrackersError;
+use crate::gadget::Gadget;
+use crate::gadget::candidates::Candidates;
+use crate::gadget::library::LibraryImage;
+
+/// Stack pointer deltas larger than this are assumed to be artifacts of a symbolic stack
+/// pointer rather than real stack usage.
+const MAX_STACK_DELTA: u64 = 0x10000;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "pyo3", pyclass)]
+#[serde(rename_all = "snake_case")]
+pub enum ObjectiveKind {
+    /// Number of instructions in the gadget.
+    InstructionCount,
+    /// Number of payload bytes the gadget consumes: how far it moves the stack pointer, if
+    /// `stack_pointer` is set and that is constant, or else the number of bytes it reads
+    /// through pointers.
+    PayloadBytes,
+    /// Number of registers the gadget writes.
+    ClobberedRegisters,
+    /// Number of memory writes of the gadget whose target is not a constant address within
+    /// one of the declared pointer write ranges.
+    OutOfRangeWrites,
+    /// One for gadgets located in one of `libraries`, zero otherwise.
+    AvoidLibraries,
+}
+
+/// One term of the cost minimized by the optimize strategy.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "pyo3", pyclass(get_all, set_all))]
+pub struct OptimizationObjective {
+    pub kind: ObjectiveKind,
+    #[serde(default = "default_weight")]
+    pub weight: usize,
+    /// For [`ObjectiveKind::AvoidLibraries`], the libraries to avoid, by path or file name.
+    #[serde(default)]
+    pub libraries: Vec<String>,
+    /// For [`ObjectiveKind::PayloadBytes`], the name of the stack pointer register.
+    #[serde(default)]
+    pub stack_pointer: Option<String>,
+}
+
+fn default_weight() -> usize {
+    1
+}
+
+#[cfg(feature = "pyo3")]
+#[pymethods]
+impl OptimizationObjective {
+    #[new]
+    #[pyo3(signature = (kind, weight=1, libraries=None, stack_pointer=None))]
+    fn new(
+        kind: ObjectiveKind,
+        weight: usize,
+        libraries: Option<Vec<String>>,
+        stack_pointer: Option<String>,
+    ) -> Self {
+        Self {
+            kind,
+            weight,
+            libraries: libraries.unwrap_or_default(),
+            stack_pointer,
+        }
+    }
+}
+
+impl From<ObjectiveKind> for OptimizationObjective {
+    fn from(kind: ObjectiveKind) -> Self {
+        Self {
+            kind,
+            weight: default_weight(),
+            libraries: vec![],
+            stack_pointer: None,
+        }
+    }
+}
+
+impl OptimizationObjective {
+    pub fn with_weight(mut self, weight: usize) -> Self {
+        self.weight = weight;
+        self
+    }
+
+    pub fn with_libraries(mut self, libraries: Vec<String>) -> Self {
+        self.libraries = libraries;
+        self
+    }
+
+    pub fn with_stack_pointer<T: AsRef<str>>(mut self, stack_pointer: T) -> Self {
+        self.stack_pointer = Some(stack_pointer.as_ref().to_string());
+        self
+    }
+
+    fn needs_model(&self) -> bool {
+        !matches!(
+            self.kind,
+            ObjectiveKind::InstructionCount | ObjectiveKind::AvoidLibraries
+        )
+    }
+}
+
+/// Computes the cost of gadgets under a set of objectives.
+#[derive(Clone, Debug)]
+pub(crate) struct CostModel {
+    objectives: Vec<OptimizationObjective>,
+    arch_info: SleighArchInfo,
+    images: Vec<LibraryImage>,
+    write_ranges: Vec<PointerRange>,
+}
+
+impl CostModel {
+    /// Without any objectives, gadgets cost their number of instructions.
+    pub(crate) fn new(
+        objectives: &[OptimizationObjective],
+        arch_info: SleighArchInfo,
+        images: &[LibraryImage],
+        write_ranges: &[PointerRange],
+    ) -> Self {
+        let objectives = if objectives.is_empty() {
+            vec![ObjectiveKind::InstructionCount.into()]
+        } else {
+            objectives.to_vec()
+        };
+        Self {
+            objectives,
+            arch_info,
+            images: images.to_vec(),
+            write_ranges: write_ranges.to_vec(),
+        }
+    }
+
+    pub(crate) fn cost(&self, gadget: &Gadget) -> Result<usize, CrackersError> {
+        let block = if self.objectives.iter().any(|o| o.needs_model()) {
+            Some(gadget.model(&self.arch_info)?)
+        } else {
+            None
+        };
+        let mut total = 0;
+        for objective in &self.objectives {
+            let cost = match (objective.kind, &block) {
+                (ObjectiveKind::InstructionCount, _) => gadget.instructions.len(),
+                (ObjectiveKind::AvoidLibraries, _) => self.in_avoided_library(objective, gadget),
+                (ObjectiveKind::PayloadBytes, Some(block)) => {
+                    self.payload_bytes(objective, block)?
+                }
+                (ObjectiveKind::ClobberedRegisters, Some(block)) => self.clobbered_registers(block),
+                (ObjectiveKind::OutOfRangeWrites, Some(block)) => self.out_of_range_writes(block),
+                _ => 0,
+            };
+            total += objective.weight * cost;
+        }
+        Ok(total)
+    }
+
+    pub(crate) fn costs(&self, gadgets: &[Gadget]) -> Result<Vec<usize>, CrackersError> {
+        gadgets.iter().map(|g| self.cost(g)).collect()
+    }
+
+    /// The cheapest gadget of the class of the `choice`th candidate of the `index`th slot, and
+    /// its cost. Members of a class only differ in their addresses, so the candidate itself is
+    /// returned unless an objective depends on the address.
+    pub(crate) fn cheapest<'a>(
+        &self,
+        candidates: &'a Candidates,
+        index: usize,
+        choice: usize,
+    ) -> Result<(&'a Gadget, usize), CrackersError> {
+        let candidate = &candidates.candidates[index][choice];
+        let mut best = (candidate, self.cost(candidate)?);
+        if self
+            .objectives
+            .iter()
+            .any(|o| o.kind == ObjectiveKind::AvoidLibraries)
+        {
+            for gadget in candidates.equivalents(index, choice) {
+                let cost = self.cost(gadget)?;
+                if cost < best.1 {
+                    best = (gadget, cost);
+                }
+            }
+        }
+        Ok(best)
+    }
+
+    /// The costs of the candidates of the `index`th slot from `start` on, each that of the
+    /// [`cheapest`](Self::cheapest) gadget of its class.
+    pub(crate) fn class_costs(
+        &self,
+        candidates: &Candidates,
+        index: usize,
+        start: usize,
+    ) -> Result<Vec<usize>, CrackersError> {
+        (start..candidates.candidates[index].len())
+            .map(|choice| Ok(self.cheapest(candidates, index, choice)?.1))
+            .collect()
+    }
+
+    fn in_avoided_library(&self, objective: &OptimizationObjective, gadget: &Gadget) -> usize {
+        let avoided = self
+            .images
+            .iter()
+            .filter(|image| image.contains(gadget.address()))
+            .any(|image| {
+                let name = Path::new(&image.path).file_name();
+                objective
+                    .libraries
+                    .iter()
+                    .any(|l| *l == image.path || name.is_some_and(|n| n == l.as_str()))
+            });
+        avoided as usize
+    }
+
+    fn payload_bytes(
+        &self,
+        objective: &OptimizationObjective,
+        block: &ModeledBlock,
+    ) -> Result<usize, CrackersError> {
+        if let Some(sp) = objective
+            .stack_pointer
+            .as_ref()
+            .and_then(|name| self.arch_info.register(name))
+        {
+            let before = block.get_original_state().read_varnode(sp)?;
+            let after = block.get_final_state().read_varnode(sp)?;
+            if let Some(delta) = constant_value(&(after - before))
+                && delta <= MAX_STACK_DELTA
+            {
+                return Ok(delta as usize);
+            }
+        }
+        Ok(block
+            .get_inputs()
+            .iter()
+            .map(|vn| match vn {
+                ResolvedVarnode::Indirect(i) => i.access_size_bytes,
+                ResolvedVarnode::Direct(_) => 0,
+            })
+            .sum())
+    }
+
+    fn clobbered_registers(&self, block: &ModeledBlock) -> usize {
+        block
+            .get_outputs()
+            .iter()
+            .filter(|vn| match vn {
+                ResolvedVarnode::Direct(d) => self
+                    .arch_info
+                    .get_space(d.space_index)
+                    .is_some_and(|s| s._type == SpaceType::IPTR_PROCESSOR),
+                ResolvedVarnode::Indirect(_) => false,
+            })
+            .count()
+    }
+
+    fn out_of_range_writes(&self, block: &ModeledBlock) -> usize {
+        block
+            .get_outputs()
+            .iter()
+            .filter(|vn| match vn {
+                ResolvedVarnode::Indirect(i) => !i
+                    .pointer
+                    .simplify()
+                    .as_u64()
+                    .is_some_and(|p| self.write_ranges.iter().any(|r| r.min <= p && p <= r.max)),
+                ResolvedVarnode::Direct(_) => false,
+            })
+            .count()
+    }
+}
+
+/// The value of `bv`, if it has the same one in every state. Registers are modeled byte by byte,
+/// so simplification alone does not cancel out e.g. the stack pointer in `(RSP + 8) - RSP`.
+fn constant_value(bv: &BV) -> Option<u64> {
+    let bv = bv.simplify();
+    if let Some(value) = bv.as_u64() {
+        return Some(value);
+    }
+    let solver = Solver::new();
+    if solver.check() != SatResult::Sat {
+        return None;
+    }
+    let value = solver.get_model()?.eval(&bv, true)?;
+    solver.assert(bv.eq(&value).not());
+    match solver.check() {
+        SatResult::Unsat => value.as_u64(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jingle::sleigh::{IndirectVarNode, PcodeOperation, VarNode};
+
+    use crate::config::constraint::PointerRange;
+    use crate::gadget::Gadget;
+    use crate::gadget::library::LibraryImage;
+    use crate::synthesis::selection_strategy::objective::{
+        CostModel, ObjectiveKind, OptimizationObjective,
+    };
+    use crate::test_util::{RAM, arch_info, constant, copy, gadget, instruction, register};
+
+    fn cost_model(objectives: Vec<OptimizationObjective>) -> CostModel {
+        let libc = LibraryImage {
+            path: "/lib/libc.so.6".to_string(),
+            base_address: 0x1000,
+            ranges: vec![(0x1000, 0x2000)],
+        };
+        let writable = PointerRange {
+            min: 0x5000,
+            max: 0x50ff,
+        };
+        CostModel::new(&objectives, arch_info(), &[libc], &[writable])
+    }
+
+    fn pointer(location: VarNode) -> IndirectVarNode {
+        IndirectVarNode {
+            pointer_space_index: RAM,
+            pointer_location: location,
+            access_size_bytes: 8,
+        }
+    }
+
+    /// Writes `RAX` to the memory `location` points to.
+    fn store(location: VarNode) -> PcodeOperation {
+        PcodeOperation::Store {
+            output: pointer(location),
+            input: register(0),
+        }
+    }
+
+    #[test]
+    fn test_instruction_count() {
+        let model = cost_model(vec![]);
+        let mut longer = gadget(0x3001, vec![copy(1, 0)]);
+        longer
+            .instructions
+            .insert(0, instruction(0x3000, vec![copy(2, 16)]));
+        assert_eq!(model.cost(&gadget(0x3000, vec![copy(1, 0)])).unwrap(), 1);
+        assert_eq!(model.cost(&longer).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_payload_bytes() {
+        // the return address, and another word read through RBX
+        let load = PcodeOperation::Load {
+            input: pointer(register(8)),
+            output: register(0),
+        };
+        let reads = gadget(0x3000, vec![load.clone()]);
+        let model = cost_model(vec![ObjectiveKind::PayloadBytes.into()]);
+        assert_eq!(model.cost(&reads).unwrap(), 16);
+        // only the return address is popped off the stack
+        let model = cost_model(vec![
+            OptimizationObjective::from(ObjectiveKind::PayloadBytes).with_stack_pointer("RSP"),
+        ]);
+        assert_eq!(model.cost(&reads).unwrap(), 8);
+        // a gadget pivoting the stack to RBX consumes what it reads instead
+        let pivot = PcodeOperation::Copy {
+            input: register(8),
+            output: register(24),
+        };
+        let pivots = gadget(0x3000, vec![load, pivot]);
+        assert_eq!(model.cost(&pivots).unwrap(), 16);
+    }
+
+    #[test]
+    fn test_clobbered_registers() {
+        let model = cost_model(vec![ObjectiveKind::ClobberedRegisters.into()]);
+        // RSP and RDX are written on the way out of every gadget
+        assert_eq!(model.cost(&gadget(0x3000, vec![])).unwrap(), 2);
+        let clobbering = gadget(0x3000, vec![copy(1, 0), copy(2, 16)]);
+        assert_eq!(model.cost(&clobbering).unwrap(), 4);
+    }
+
+    #[test]
+    fn test_out_of_range_writes() {
+        let model = cost_model(vec![ObjectiveKind::OutOfRangeWrites.into()]);
+        let in_range = gadget(0x3000, vec![store(constant(0x5008))]);
+        assert_eq!(model.cost(&in_range).unwrap(), 0);
+        let out_of_range = gadget(0x3000, vec![store(constant(0x6000))]);
+        assert_eq!(model.cost(&out_of_range).unwrap(), 1);
+        // a symbolic target may be anywhere
+        let anywhere = gadget(0x3000, vec![store(register(8))]);
+        assert_eq!(model.cost(&anywhere).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_avoid_libraries() {
+        let inside = gadget(0x1800, vec![copy(1, 0)]);
+        let outside = gadget(0x3000, vec![copy(1, 0)]);
+        for library in ["libc.so.6", "/lib/libc.so.6"] {
+            let model = cost_model(vec![
+                OptimizationObjective::from(ObjectiveKind::AvoidLibraries)
+                    .with_libraries(vec![library.to_string()]),
+            ]);
+            assert_eq!(model.cost(&inside).unwrap(), 1);
+            assert_eq!(model.cost(&outside).unwrap(), 0);
+        }
+        let model = cost_model(vec![
+            OptimizationObjective::from(ObjectiveKind::AvoidLibraries)
+                .with_libraries(vec!["libm.so.6".to_string()]),
+        ]);
+        assert_eq!(model.cost(&inside).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_weighted_sum() {
+        let model = cost_model(vec![
+            OptimizationObjective::from(ObjectiveKind::InstructionCount).with_weight(5),
+            OptimizationObjective::from(ObjectiveKind::ClobberedRegisters).with_weight(2),
+            OptimizationObjective::from(ObjectiveKind::AvoidLibraries)
+                .with_libraries(vec!["libc.so.6".to_string()])
+                .with_weight(100),
+        ]);
+        let gadgets: Vec<Gadget> = [0x1800, 0x3000]
+            .into_iter()
+            .map(|address| gadget(address, vec![copy(1, 0)]))
+            .collect();
+        assert_eq!(model.costs(&gadgets).unwrap(), vec![111, 11]);
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let objectives: Vec<OptimizationObjective> = serde_json::from_str(
+            r#"[{"kind": "payload_bytes", "stack_pointer": "RSP"},
+                {"kind": "avoid_libraries", "libraries": ["libc.so.6"], "weight": 10}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            objectives,
+            vec![
+                OptimizationObjective::from(ObjectiveKind::PayloadBytes).with_stack_pointer("RSP"),
+                OptimizationObjective::from(ObjectiveKind::AvoidLibraries)
+                    .with_libraries(vec!["libc.so.6".to_string()])
+                    .with_weight(10),
+            ]
+        );
+    }
+}
@@ -1,21 +1,26 @@
+use std::time::Duration;
+
 use z3::ast::{Ast, Bool};
-use z3::{Optimize, SatResult};
+use z3::{Optimize, Params, SatResult};
 
 use crate::error::CrackersError;
-use crate::error::CrackersError::ModelGenerationError;
-use crate::synthesis::Decision;
+use crate::error::CrackersError::{BooleanAssignmentTimeout, ModelGenerationError};
 use crate::synthesis::pcode_theory::conflict_clause::ConflictClause;
 use crate::synthesis::selection_strategy::AssignmentResult::{Failure, Success};
 use crate::synthesis::selection_strategy::{
-    AssignmentResult, InstrLen, SelectionFailure, SelectionStrategy,
+    AssignmentResult, Candidate, SelectionFailure, SelectionStrategy,
 };
 use crate::synthesis::slot_assignments::SlotAssignments;
+use crate::synthesis::{Decision, seeded, solver_timeout};
 
 #[derive(Debug)]
 pub struct OptimizationProblem {
     variables: Vec<Vec<Bool>>,
     solver: Optimize,
+    /// For each slot, the literal enabling the constraint that exactly one of its current
+    /// choices is selected. See [`SatProblem`](super::sat_problem::SatProblem).
     index_bools: Vec<Bool>,
+    seed: Option<u32>,
 }
 
 impl OptimizationProblem {
@@ -32,41 +37,61 @@ impl OptimizationProblem {
                 .filter(|(_, t)| core.iter().any(|c| *c == **t))
                 .map(|(i, _)| i)
                 .collect(),
+            statistics: Default::default(),
         }
     }
-}
 
-impl SelectionStrategy for OptimizationProblem {
-    fn initialize<T: InstrLen>(gadgets: &[Vec<T>]) -> Self {
+    /// Creates a problem whose choices have the given costs; the cheapest assignments are
+    /// returned first.
+    pub fn with_costs(costs: &[Vec<usize>]) -> Self {
         let mut prob = Self {
             variables: Default::default(),
             solver: Optimize::new(),
-            index_bools: Vec::with_capacity(gadgets.len()),
+            index_bools: Vec::with_capacity(costs.len()),
+            seed: None,
         };
-        for (i, slot) in gadgets.iter().enumerate() {
-            let mut vars = vec![];
-            for (j, _) in slot.iter().enumerate() {
-                let var = Bool::new_const(Self::derive_var_name(i, j));
-                prob.solver
-                    .assert_soft(&var.not(), gadgets[i][j].instr_len(), None);
-                vars.push(var)
+        for (i, slot) in costs.iter().enumerate() {
+            prob.extend_slot_with_costs(i, slot);
+        }
+        prob
+    }
+
+    /// Seeds the random choices of the solver. See
+    /// [`SatProblem::with_seed`](super::sat_problem::SatProblem::with_seed).
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.seed = Some(seed);
+        self.solver.set_params(&seeded(Params::new(), self.seed));
+        self
+    }
+
+    /// Like [`extend_slot`](SelectionStrategy::extend_slot), with the costs of the new choices.
+    pub fn extend_slot_with_costs(&mut self, index: usize, costs: &[usize]) {
+        if index == self.variables.len() {
+            self.variables.push(vec![]);
+        }
+        for cost in costs {
+            let var = Bool::new_const(Self::derive_var_name(index, self.variables[index].len()));
+            if *cost > 0 {
+                self.solver.assert_soft(&var.not(), *cost, None);
             }
-            prob.variables.push(vars);
+            self.variables[index].push(var);
         }
-        for (i, slot) in prob.variables.iter().enumerate() {
-            let pbs: Vec<(&Bool, i32)> = slot.iter().map(|b| (b, 1)).collect();
-            let b = Bool::fresh_const(&format!("slot_{i}"));
-            prob.index_bools.push(b.clone());
-            prob.solver.assert_and_track(&Bool::pb_eq(&pbs, 1), &b)
+        let pbs: Vec<(&Bool, i32)> = self.variables[index].iter().map(|b| (b, 1)).collect();
+        let b = Bool::fresh_const(&format!("slot_{index}"));
+        self.solver.assert(&b.implies(Bool::pb_eq(&pbs, 1)));
+        if index < self.index_bools.len() {
+            self.index_bools[index] = b;
+        } else {
+            self.index_bools.push(b);
         }
-        prob
     }
+}
+
+impl SelectionStrategy for OptimizationProblem {
     fn get_assignments(&mut self) -> Result<AssignmentResult, CrackersError> {
-        match self.solver.check(&[]) {
+        match self.solver.check(&self.index_bools) {
             SatResult::Unsat => Ok(Failure(self.get_unsat_reason(self.solver.get_unsat_core()))),
-            SatResult::Unknown => {
-                unreachable!("outer SAT solver timed out (this really shouldn't happen)!")
-            }
+            SatResult::Unknown => Err(BooleanAssignmentTimeout),
             SatResult::Sat => {
                 let model = self.solver.get_model().ok_or(ModelGenerationError)?;
                 let assignment =
@@ -84,6 +109,14 @@ impl SelectionStrategy for OptimizationProblem {
     }
 
     fn add_theory_clause(&mut self, clause: &ConflictClause) {
+        if clause.is_slot_restriction(self.variables.len()) {
+            // see `SatProblem::add_theory_clause`
+            for decision in clause.decisions() {
+                self.solver
+                    .assert(&self.get_decision_variable(decision).not());
+            }
+            return;
+        }
         let choices: Vec<&Bool> = clause
             .decisions()
             .iter()
@@ -92,4 +125,24 @@ impl SelectionStrategy for OptimizationProblem {
         self.solver
             .assert(&Bool::and(choices.as_slice()).not().simplify());
     }
+
+    fn extend_slot(&mut self, index: usize, candidates: &[Candidate<'_>]) {
+        let costs: Vec<usize> = candidates.iter().map(|c| c.cost).collect();
+        self.extend_slot_with_costs(index, &costs);
+    }
+
+    fn block_assignment(&mut self, assignment: &SlotAssignments, min_distance: usize) {
+        let decisions = assignment.to_decisions();
+        let same: Vec<(&Bool, i32)> = decisions
+            .iter()
+            .map(|d| (self.get_decision_variable(d), 1))
+            .collect();
+        let bound = decisions.len().saturating_sub(min_distance) as i32;
+        self.solver.assert(&Bool::pb_le(&same, bound));
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.solver
+            .set_params(&seeded(solver_timeout(timeout), self.seed));
+    }
 }
@@ -0,0 +1,410 @@
+//! A [`SelectionStrategy`] racing several outer problems against each other.
+//!
+//! Neither the SAT nor the optimize problem dominates: the former tends to find some chain
+//! quickly, while the latter finds cheaper chains but sometimes stalls. A [`Portfolio`] runs
+//! one problem per [`PortfolioMember`], each on its own thread with its own strategy and seed,
+//! and hands the synthesis whichever assignment is ready first. Every learned clause, blocked
+//! assignment and new candidate is passed on to all members, as is every returned assignment,
+//! so the members search the same space and never propose what another one already did.
+
+use std::collections::HashSet;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "pyo3")]
+use pyo3::{pyclass, pymethods};
+use serde::{Deserialize, Serialize};
+use tracing::{Level, event};
+
+use crate::error::CrackersError;
+use crate::error::CrackersError::{BooleanAssignmentTimeout, ModelGenerationError};
+use crate::gadget::Gadget;
+use crate::synthesis::builder::SynthesisSelectionStrategy;
+use crate::synthesis::cancellation::CancellationToken;
+use crate::synthesis::pcode_theory::conflict_clause::ConflictClause;
+use crate::synthesis::selection_strategy::AssignmentResult::{Failure, Success};
+use crate::synthesis::selection_strategy::{
+    AssignmentResult, Candidate, SelectionStrategy, StrategyBuilder,
+};
+use crate::synthesis::slot_assignments::SlotAssignments;
+
+/// One outer problem of a [`Portfolio`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[cfg_attr(feature = "pyo3", pyclass(get_all, set_all))]
+pub struct PortfolioMember {
+    pub strategy: SynthesisSelectionStrategy,
+    /// Seed of the member's solver.
+    #[serde(default)]
+    pub seed: u32,
+}
+
+#[cfg(feature = "pyo3")]
+#[pymethods]
+impl PortfolioMember {
+    #[new]
+    #[pyo3(signature = (strategy, seed=0))]
+    fn new(strategy: SynthesisSelectionStrategy, seed: u32) -> Self {
+        Self { strategy, seed }
+    }
+}
+
+impl From<SynthesisSelectionStrategy> for PortfolioMember {
+    fn from(strategy: SynthesisSelectionStrategy) -> Self {
+        Self { strategy, seed: 0 }
+    }
+}
+
+impl PortfolioMember {
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.seed = seed;
+        self
+    }
+}
+
+/// Builds a [`PortfolioProblem`] running each of `members`.
+#[derive(Clone, Debug)]
+pub struct Portfolio {
+    members: Vec<PortfolioMember>,
+}
+
+/// A SAT problem, an optimize problem, and a differently seeded SAT problem.
+impl Default for Portfolio {
+    fn default() -> Self {
+        Self {
+            members: vec![
+                SynthesisSelectionStrategy::SatStrategy.into(),
+                SynthesisSelectionStrategy::OptimizeStrategy.into(),
+                PortfolioMember::from(SynthesisSelectionStrategy::SatStrategy).with_seed(1),
+            ],
+        }
+    }
+}
+
+impl Portfolio {
+    /// A portfolio of `members`, or the default one if there are none.
+    pub fn new(members: Vec<PortfolioMember>) -> Self {
+        if members.is_empty() {
+            Self::default()
+        } else {
+            Self { members }
+        }
+    }
+}
+
+impl StrategyBuilder for Portfolio {
+    fn build(
+        &self,
+        candidates: &[Vec<Candidate<'_>>],
+        cancellation: &CancellationToken,
+    ) -> Result<Box<dyn SelectionStrategy>, CrackersError> {
+        let slots: Vec<Vec<(Gadget, usize)>> = candidates
+            .iter()
+            .map(|slot| slot.iter().map(|c| (c.gadget.clone(), c.cost)).collect())
+            .collect();
+        Ok(Box::new(PortfolioProblem::spawn(
+            &self.members,
+            slots,
+            cancellation,
+        )))
+    }
+}
+
+enum Request {
+    /// Asks for the next assignment. `generation` counts the slot extensions made before.
+    Next {
+        timeout: Option<Duration>,
+        generation: usize,
+    },
+    Clause(ConflictClause),
+    Block(SlotAssignments, usize),
+    Extend(usize, Vec<(Gadget, usize)>),
+}
+
+struct Response {
+    member: usize,
+    generation: usize,
+    result: Result<AssignmentResult, CrackersError>,
+}
+
+struct Member {
+    requests: Sender<Request>,
+    /// Whether the member is working on a [`Request::Next`].
+    busy: bool,
+}
+
+pub struct PortfolioProblem {
+    members: Vec<Member>,
+    responses: Receiver<Response>,
+    /// Assignments returned so far, which members that haven't learned of them yet may
+    /// propose again.
+    returned: HashSet<Vec<usize>>,
+    /// Clauses and blocks added so far, which assignments proposed before the members learned
+    /// of them may violate.
+    clauses: Vec<ConflictClause>,
+    blocked: Vec<(SlotAssignments, usize)>,
+    generation: usize,
+    timeout: Option<Duration>,
+    /// Stops the members once the portfolio is dropped or the synthesis is cancelled.
+    stop: CancellationToken,
+}
+
+impl PortfolioProblem {
+    fn spawn(
+        members: &[PortfolioMember],
+        slots: Vec<Vec<(Gadget, usize)>>,
+        cancellation: &CancellationToken,
+    ) -> Self {
+        let stop = cancellation.child();
+        let (response_sender, responses) = std::sync::mpsc::channel();
+        let members = members
+            .iter()
+            .enumerate()
+            .map(|(idx, member)| {
+                let (requests, request_receiver) = std::sync::mpsc::channel();
+                let member = *member;
+                let slots = slots.clone();
+                let responses = response_sender.clone();
+                let stop = stop.clone();
+                std::thread::spawn(move || {
+                    let interrupt = stop.clone();
+                    interrupt.interrupting(|| {
+                        run_member(idx, member, &slots, request_receiver, responses, &stop)
+                    })
+                });
+                Member {
+                    requests,
+                    busy: false,
+                }
+            })
+            .collect();
+        Self {
+            members,
+            responses,
+            returned: HashSet::new(),
+            clauses: vec![],
+            blocked: vec![],
+            generation: 0,
+            timeout: None,
+            stop,
+        }
+    }
+
+    fn broadcast(&self, request: impl Fn() -> Request) {
+        for member in &self.members {
+            let _ = member.requests.send(request());
+        }
+    }
+
+    fn request_next(&mut self, member: usize) {
+        let request = Request::Next {
+            timeout: self.timeout,
+            generation: self.generation,
+        };
+        if self.members[member].requests.send(request).is_ok() {
+            self.members[member].busy = true;
+        }
+    }
+
+    /// Whether `assignment` is new and satisfies every clause and block added so far.
+    fn admissible(&self, assignment: &SlotAssignments) -> bool {
+        !self.returned.contains(assignment.choices())
+            && !self.clauses.iter().any(|c| {
+                c.decisions()
+                    .iter()
+                    .all(|d| assignment.choice(d.index) == d.choice)
+            })
+            && self
+                .blocked
+                .iter()
+                .all(|(b, min_distance)| assignment.distance(b) >= *min_distance)
+    }
+}
+
+impl Drop for PortfolioProblem {
+    fn drop(&mut self) {
+        self.stop.cancel();
+    }
+}
+
+fn run_member(
+    idx: usize,
+    member: PortfolioMember,
+    slots: &[Vec<(Gadget, usize)>],
+    requests: Receiver<Request>,
+    responses: Sender<Response>,
+    stop: &CancellationToken,
+) {
+    let candidates: Vec<Vec<Candidate>> = slots.iter().map(|slot| describe_owned(slot)).collect();
+    let mut problem = match member
+        .strategy
+        .build_seeded(&candidates, Some(member.seed), stop)
+    {
+        Ok(problem) => problem,
+        Err(e) => {
+            // report the failure when first asked for an assignment
+            let generation = requests.iter().find_map(|r| match r {
+                Request::Next { generation, .. } => Some(generation),
+                _ => None,
+            });
+            if let Some(generation) = generation {
+                let _ = responses.send(Response {
+                    member: idx,
+                    generation,
+                    result: Err(e),
+                });
+            }
+            return;
+        }
+    };
+    for request in requests {
+        match request {
+            Request::Next {
+                timeout,
+                generation,
+            } => {
+                if let Some(timeout) = timeout {
+                    problem.set_timeout(timeout);
+                }
+                let result = if stop.is_cancelled() {
+                    Err(BooleanAssignmentTimeout)
+                } else {
+                    problem.get_assignments()
+                };
+                let response = Response {
+                    member: idx,
+                    generation,
+                    result,
+                };
+                if responses.send(response).is_err() {
+                    return;
+                }
+            }
+            Request::Clause(clause) => problem.add_theory_clause(&clause),
+            Request::Block(assignment, min_distance) => {
+                problem.block_assignment(&assignment, min_distance)
+            }
+            Request::Extend(index, gadgets) => {
+                problem.extend_slot(index, &describe_owned(&gadgets))
+            }
+        }
+    }
+}
+
+fn describe_owned(slot: &[(Gadget, usize)]) -> Vec<Candidate<'_>> {
+    slot.iter()
+        .map(|(gadget, cost)| Candidate {
+            gadget,
+            cost: *cost,
+        })
+        .collect()
+}
+
+impl SelectionStrategy for PortfolioProblem {
+    fn get_assignments(&mut self) -> Result<AssignmentResult, CrackersError> {
+        let deadline = self.timeout.map(|t| Instant::now() + t);
+        for member in 0..self.members.len() {
+            if !self.members[member].busy {
+                self.request_next(member);
+            }
+        }
+        loop {
+            let response = match deadline {
+                Some(d) => self
+                    .responses
+                    .recv_timeout(d.saturating_duration_since(Instant::now())),
+                None => self
+                    .responses
+                    .recv()
+                    .map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            let response = match response {
+                Ok(response) => response,
+                Err(RecvTimeoutError::Timeout) => return Err(BooleanAssignmentTimeout),
+                Err(RecvTimeoutError::Disconnected) => return Err(ModelGenerationError),
+            };
+            self.members[response.member].busy = false;
+            match response.result {
+                Ok(Success(a)) if self.admissible(&a) => {
+                    event!(
+                        Level::TRACE,
+                        "Portfolio member {} proposed {}",
+                        response.member,
+                        a
+                    );
+                    self.returned.insert(a.choices().to_vec());
+                    let clause = a.as_conflict_clause();
+                    self.broadcast(|| Request::Clause(clause.clone()));
+                    return Ok(Success(a));
+                }
+                // the failure is final unless slots were extended since it was found
+                Ok(Failure(f)) if response.generation == self.generation => return Ok(Failure(f)),
+                Ok(_) => self.request_next(response.member),
+                Err(BooleanAssignmentTimeout) => {
+                    if self.members.iter().all(|m| !m.busy) {
+                        return Err(BooleanAssignmentTimeout);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    fn add_theory_clause(&mut self, clause: &ConflictClause) {
+        self.clauses.push(clause.clone());
+        self.broadcast(|| Request::Clause(clause.clone()));
+    }
+
+    fn extend_slot(&mut self, index: usize, candidates: &[Candidate<'_>]) {
+        self.generation += 1;
+        let gadgets: Vec<(Gadget, usize)> = candidates
+            .iter()
+            .map(|c| (c.gadget.clone(), c.cost))
+            .collect();
+        self.broadcast(|| Request::Extend(index, gadgets.clone()));
+    }
+
+    fn block_assignment(&mut self, assignment: &SlotAssignments, min_distance: usize) {
+        self.blocked.push((assignment.clone(), min_distance));
+        self.broadcast(|| Request::Block(assignment.clone(), min_distance));
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = Some(timeout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use crate::gadget::Gadget;
+    use crate::synthesis::Decision;
+    use crate::synthesis::cancellation::CancellationToken;
+    use crate::synthesis::pcode_theory::conflict_clause::ConflictClause;
+    use crate::synthesis::selection_strategy::portfolio::{Portfolio, PortfolioProblem};
+    use crate::synthesis::selection_strategy::{AssignmentResult, SelectionStrategy};
+
+    #[test]
+    fn test_members_share_clauses() {
+        let gadget = Gadget::new(0, vec![]);
+        let slot = vec![(gadget.clone(), 1), (gadget.clone(), 2), (gadget, 3)];
+        let members = Portfolio::default().members;
+        let mut prob = PortfolioProblem::spawn(
+            &members,
+            vec![slot.clone(), slot],
+            &CancellationToken::new(),
+        );
+        prob.add_theory_clause(&ConflictClause::from(Decision {
+            index: 0,
+            choice: 0,
+        }));
+        // every member is asked each time, but each of the 6 remaining assignments is
+        // returned exactly once
+        let mut seen = HashSet::new();
+        while let AssignmentResult::Success(a) = prob.get_assignments().unwrap() {
+            assert_ne!(a.choice(0), 0);
+            assert!(seen.insert(a.choices().to_vec()));
+        }
+        assert_eq!(seen.len(), 6);
+    }
+}
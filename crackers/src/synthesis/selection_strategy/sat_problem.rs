@@ -1,13 +1,17 @@
+use std::time::Duration;
+
 use z3::ast::{Ast, Bool};
-use z3::{SatResult, Solver};
+use z3::{Params, SatResult, Solver};
 
 use crate::error::CrackersError;
-use crate::error::CrackersError::ModelGenerationError;
-use crate::synthesis::Decision;
+use crate::error::CrackersError::{BooleanAssignmentTimeout, ModelGenerationError};
 use crate::synthesis::pcode_theory::conflict_clause::ConflictClause;
 use crate::synthesis::selection_strategy::AssignmentResult::{Failure, Success};
-use crate::synthesis::selection_strategy::{AssignmentResult, SelectionFailure, SelectionStrategy};
+use crate::synthesis::selection_strategy::{
+    AssignmentResult, Candidate, SelectionFailure, SelectionStrategy,
+};
 use crate::synthesis::slot_assignments::SlotAssignments;
+use crate::synthesis::{Decision, seeded, solver_timeout};
 
 #[derive(Debug, Clone)]
 pub struct SatProblem {
@@ -15,7 +19,11 @@ pub struct SatProblem {
     solver: Solver,
     last_conflict: Option<ConflictClause>,
     last_assignment: Option<SlotAssignments>,
+    /// For each slot, the literal enabling the constraint that exactly one of its current
+    /// choices is selected. These are assumed in every check, so that a slot can be given more
+    /// choices by enabling a new constraint in place of its old one.
     index_bools: Vec<Bool>,
+    seed: Option<u32>,
 }
 
 impl SatProblem {
@@ -41,26 +49,26 @@ impl SatProblem {
         })
     }
 
-    fn get_unsat_reason(&self, core: Vec<Bool>) -> SelectionFailure {
-        SelectionFailure {
-            indices: self
-                .index_bools
-                .iter()
-                .enumerate()
-                .filter(|(_, t)| core.iter().any(|c| *c == **t))
-                .map(|(i, _)| i)
-                .collect(),
+    fn assert_slot_choice(&mut self, index: usize) {
+        let pbs: Vec<(&Bool, i32)> = self.variables[index].iter().map(|b| (b, 1)).collect();
+        let b = Bool::fresh_const(&format!("slot_{index}"));
+        self.solver.assert(b.implies(Bool::pb_eq(&pbs, 1)));
+        if index < self.index_bools.len() {
+            self.index_bools[index] = b;
+        } else {
+            self.index_bools.push(b);
         }
     }
-}
-impl SelectionStrategy for SatProblem {
-    fn initialize<T>(gadgets: &[Vec<T>]) -> SatProblem {
+
+    /// Creates a problem with a choice for each of the candidates in `gadgets`.
+    pub fn initialize<T>(gadgets: &[Vec<T>]) -> SatProblem {
         let mut prob = SatProblem {
             variables: Default::default(),
             solver: Solver::new(),
             last_conflict: None,
             last_assignment: None,
             index_bools: Vec::with_capacity(gadgets.len()),
+            seed: None,
         };
         for (i, slot) in gadgets.iter().enumerate() {
             let mut vars = vec![];
@@ -69,28 +77,58 @@ impl SelectionStrategy for SatProblem {
             }
             prob.variables.push(vars);
         }
-        for (i, slot) in prob.variables.iter().enumerate() {
-            let pbs: Vec<(&Bool, i32)> = slot.iter().map(|b| (b, 1)).collect();
-            let b = Bool::fresh_const(&format!("slot_{i}"));
-            prob.index_bools.push(b.clone());
-            prob.solver.assert_and_track(Bool::pb_eq(&pbs, 1), &b);
+        for i in 0..prob.variables.len() {
+            prob.assert_slot_choice(i);
         }
         prob
     }
 
+    /// Seeds the random choices of the solver, so that differently seeded problems tend to
+    /// return different assignments first.
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.seed = Some(seed);
+        self.solver.set_params(&seeded(Params::new(), self.seed));
+        self
+    }
+
+    /// Adds `count` further choices for the `index`th slot.
+    pub fn add_choices(&mut self, index: usize, count: usize) {
+        let start = self.variables[index].len();
+        for j in start..start + count {
+            self.variables[index].push(Bool::new_const(SatProblem::derive_var_name(index, j)));
+        }
+        self.assert_slot_choice(index);
+    }
+
+    fn get_unsat_reason(&self, core: Vec<Bool>) -> SelectionFailure {
+        SelectionFailure {
+            indices: self
+                .index_bools
+                .iter()
+                .enumerate()
+                .filter(|(_, t)| core.iter().any(|c| *c == **t))
+                .map(|(i, _)| i)
+                .collect(),
+            statistics: Default::default(),
+        }
+    }
+}
+impl SelectionStrategy for SatProblem {
     fn get_assignments(&mut self) -> Result<AssignmentResult, CrackersError> {
         let sat_result = match self.get_last_conflict_refutation() {
-            Some(c) => match self.solver.check_assumptions(&[c]) {
-                SatResult::Sat => SatResult::Sat,
-                _ => self.solver.check(),
-            },
-            _ => self.solver.check(),
+            Some(c) => {
+                let mut assumptions = self.index_bools.clone();
+                assumptions.push(c);
+                match self.solver.check_assumptions(&assumptions) {
+                    SatResult::Sat => SatResult::Sat,
+                    _ => self.solver.check_assumptions(&self.index_bools),
+                }
+            }
+            _ => self.solver.check_assumptions(&self.index_bools),
         };
         match sat_result {
             SatResult::Unsat => Ok(Failure(self.get_unsat_reason(self.solver.get_unsat_core()))),
-            SatResult::Unknown => {
-                unreachable!("outer SAT solver timed out (this really shouldn't happen)!")
-            }
+            SatResult::Unknown => Err(BooleanAssignmentTimeout),
             SatResult::Sat => {
                 let model = self.solver.get_model().ok_or(ModelGenerationError)?;
                 let assignment =
@@ -109,6 +147,15 @@ impl SelectionStrategy for SatProblem {
     }
 
     fn add_theory_clause(&mut self, clause: &ConflictClause) {
+        if clause.is_slot_restriction(self.variables.len()) {
+            // the gadget is out of its slot for good, whatever the other slots hold, so its
+            // variable is fixed rather than joined with the other choices of the last assignment
+            for decision in clause.decisions() {
+                self.solver
+                    .assert(self.get_decision_variable(decision).not());
+            }
+            return;
+        }
         self.last_conflict = Some(clause.clone());
         let choices: Vec<&Bool> = clause
             .decisions()
@@ -118,14 +165,45 @@ impl SelectionStrategy for SatProblem {
         self.solver
             .assert(Bool::and(choices.as_slice()).not().simplify());
     }
+
+    fn extend_slot(&mut self, index: usize, candidates: &[Candidate<'_>]) {
+        self.add_choices(index, candidates.len());
+    }
+
+    fn block_assignment(&mut self, assignment: &SlotAssignments, min_distance: usize) {
+        let decisions = assignment.to_decisions();
+        let same: Vec<(&Bool, i32)> = decisions
+            .iter()
+            .map(|d| (self.get_decision_variable(d), 1))
+            .collect();
+        let bound = decisions.len().saturating_sub(min_distance) as i32;
+        self.solver.assert(Bool::pb_le(&same, bound));
+    }
+
+    fn set_timeout(&mut self, timeout: Duration) {
+        self.solver
+            .set_params(&seeded(solver_timeout(timeout), self.seed));
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::sync::Arc;
+
+    use jingle::sleigh::PcodeOperation;
+    use z3::ast::{BV, Bool};
+
+    use crate::gadget::Gadget;
+    use crate::gadget::candidates::CandidateBuilder;
+    use crate::reference_program::step::Step;
+    use crate::reference_program::valuation::MemoryValuation;
     use crate::synthesis::Decision;
+    use crate::synthesis::builder::StateConstraintGenerator;
+    use crate::synthesis::pcode_theory::PcodeTheory;
     use crate::synthesis::pcode_theory::conflict_clause::ConflictClause;
     use crate::synthesis::selection_strategy::sat_problem::SatProblem;
     use crate::synthesis::selection_strategy::{AssignmentResult, SelectionStrategy};
+    use crate::test_util::{arch_info, constant, copy, gadget, instruction, register};
 
     #[test]
     fn test_assignment() {
@@ -170,4 +248,142 @@ mod tests {
         // verify that we do not get a model back
         assert!(matches!(assignments3, Ok(AssignmentResult::Failure(_))));
     }
+
+    /// A gadget setting `RAX` to 1 and `RBX` to `rbx`. A good gadget sets `RCX` to 257, and a
+    /// bad one to the product of the two low bytes of its incoming `RBX`, which is never 257,
+    /// a prime too large for either byte. That is plain for a known `RBX`, but takes the solver
+    /// some search otherwise.
+    fn rcx_gadget(address: u64, rbx: u64, bad: bool) -> Gadget {
+        let ops = if bad {
+            let and = |output, input0, mask| PcodeOperation::IntAnd {
+                output: register(output),
+                input0: register(input0),
+                input1: constant(mask),
+            };
+            vec![
+                copy(1, 0),
+                and(16, 8, 0xff),
+                PcodeOperation::IntRightShift {
+                    output: register(32),
+                    input0: register(8),
+                    input1: constant(8),
+                },
+                and(32, 32, 0xff),
+                PcodeOperation::IntMult {
+                    output: register(16),
+                    input0: register(16),
+                    input1: register(32),
+                },
+                copy(rbx, 8),
+            ]
+        } else {
+            vec![copy(1, 0), copy(257, 16), copy(rbx, 8)]
+        };
+        gadget(address, ops)
+    }
+
+    /// Runs the outer problem against a theory checking chains of 3 gadgets, each setting `RAX`
+    /// to 1, with a precondition ruling out the first candidate as the first gadget and a
+    /// postcondition asking for an `RCX` of 257, which the bad candidates can never reach as the
+    /// last gadget. `restrict` chooses whether the theory learns slot restrictions from those.
+    /// Returns the number of assignments checked before running out.
+    fn refute_ends(restrict: bool) -> usize {
+        let info = arch_info();
+        let gadgets: Vec<Gadget> = (0..4)
+            .map(|k| rcx_gadget(0x1000 * (k + 1), 2 * (k + 1), k % 2 == 1))
+            .collect();
+        let candidates = CandidateBuilder::default()
+            .with_random_sample_size(gadgets.len())
+            .build(gadgets.iter().map(|g| vec![Some(g); 3]))
+            .unwrap();
+        let templates = (0..3)
+            .map(|i| {
+                Step::from_instr(instruction(0x400000 + i, vec![copy(1, 0)]))
+                    .model(&info)
+                    .unwrap()
+            })
+            .collect();
+        let precondition: Arc<StateConstraintGenerator> =
+            Arc::new(|_, address| Ok(Bool::from_bool(address != 0x1000)));
+        let postcondition: Arc<StateConstraintGenerator> = Arc::new(|state, _| {
+            let rcx = state.read_varnode(&register(16))?;
+            Ok(rcx.eq(BV::from_u64(257, 64)))
+        });
+        let mut theory = PcodeTheory::new(
+            &info,
+            templates,
+            MemoryValuation::default(),
+            Arc::new(candidates.clone()),
+            vec![precondition],
+            vec![postcondition],
+            vec![],
+        )
+        .unwrap();
+        theory.set_slot_restrictions(restrict);
+        // each refuted check gets the one solver call a slot restriction takes; without them, it
+        // would go to minimizing the core instead, which may find the narrower conflict as well
+        theory.set_minimization_budget(usize::from(restrict));
+        let mut prob = SatProblem::initialize(&candidates.candidates);
+        let mut checked = 0;
+        while let AssignmentResult::Success(a) = prob.get_assignments().unwrap() {
+            checked += 1;
+            let Some(conflicts) = theory.check_assignment(&a).unwrap() else {
+                // valid; the outer problem doesn't return an assignment twice anyway
+                continue;
+            };
+            for conflict in &conflicts {
+                prob.add_theory_clause(conflict);
+            }
+        }
+        checked
+    }
+
+    #[test]
+    fn test_slot_restrictions() {
+        // the 24 valid assignments, plus one refuting the first candidate as the first gadget
+        // and one for each bad candidate as the last
+        let (a, b) = (refute_ends(true), refute_ends(false));
+        eprintln!("DBG {a} {b}");
+        assert_eq!(refute_ends(true), 27);
+        // the unsat core blames a bad last gadget along with the gadget before it, whose RBX
+        // makes the conflict obvious, so that it has to be refuted after each of them
+        assert!(refute_ends(false) > 27);
+    }
+
+    #[test]
+    fn test_block_assignment() {
+        let thing = vec![vec![1, 2], vec![1, 2], vec![1, 2]];
+        let mut prob = SatProblem::initialize(&thing);
+        let AssignmentResult::Success(first) = prob.get_assignments().unwrap() else {
+            panic!()
+        };
+        prob.block_assignment(&first, 2);
+        // of the 8 assignments, only the 4 that differ from the first in at least 2 slots remain
+        let mut remaining = 0;
+        while let AssignmentResult::Success(a) = prob.get_assignments().unwrap() {
+            assert!(a.distance(&first) >= 2);
+            remaining += 1;
+        }
+        assert_eq!(remaining, 4);
+    }
+
+    #[test]
+    fn test_extend_slot() {
+        let thing = vec![vec![1], vec![1, 2]];
+        let mut prob = SatProblem::initialize(&thing);
+        prob.add_theory_clause(&ConflictClause::from(Decision {
+            index: 0,
+            choice: 0,
+        }));
+        let AssignmentResult::Failure(failure) = prob.get_assignments().unwrap() else {
+            panic!()
+        };
+        assert_eq!(failure.indices, vec![0]);
+        prob.add_choices(0, 2);
+        let AssignmentResult::Success(a) = prob.get_assignments().unwrap() else {
+            panic!()
+        };
+        // the refuted choice stays refuted
+        assert_ne!(a.choice(0), 0);
+    }
 }
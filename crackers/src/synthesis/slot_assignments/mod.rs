@@ -40,6 +40,17 @@ impl SlotAssignments {
         self.choices.as_slice()
     }
 
+    /// The number of slots in which this assignment and `other` choose different gadgets.
+    pub fn distance(&self, other: &SlotAssignments) -> usize {
+        let differing = self
+            .choices
+            .iter()
+            .zip(&other.choices)
+            .filter(|(a, b)| a != b)
+            .count();
+        differing + self.choices.len().abs_diff(other.choices.len())
+    }
+
     pub fn create_from_model(model: Model, variables: &[Vec<Bool>]) -> Result<Self, CrackersError> {
         let mut choices = Vec::with_capacity(variables.len());
         for slot_choices in variables {
@@ -78,6 +89,13 @@ impl SlotAssignments {
     }
 }
 
+/// An assignment choosing `choices[i]` for the `i`th slot.
+impl From<Vec<usize>> for SlotAssignments {
+    fn from(choices: Vec<usize>) -> Self {
+        Self { choices }
+    }
+}
+
 impl Display for SlotAssignments {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "[")?;
@@ -0,0 +1,1035 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+use jingle::display::JingleDisplayable;
+use jingle::modeling::{ModelingContext, State};
+use jingle::sleigh::{
+    IndirectVarNode, Instruction, PcodeOperation, SleighArchInfo, SleighEndianness, SpaceType,
+    VarNode,
+};
+use tracing::{Level, event};
+use z3::Model;
+
+use crate::config::constraint::StateEqualityConstraint;
+use crate::config::object::{load_image_base, load_segments};
+use crate::error::CrackersError;
+use crate::gadget::library::image::SegmentFile;
+use crate::reference_program::valuation::MemoryValuation;
+use crate::synthesis::assignment_model::AssignmentModel;
+use crate::synthesis::assignment_model::builder::AssignmentModelBuilder;
+
+/// Upper bound on the number of p-code operations executed for a single slot, to guard
+/// against instructions that loop through relative p-code branches.
+const MAX_OPS_PER_SLOT: usize = 0x10000;
+
+/// The first point at which concrete execution of a chain disagrees with its reference program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the slot at which execution diverged. For postcondition failures, this is the
+    /// length of the chain.
+    pub step: usize,
+    pub kind: DivergenceKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DivergenceKind {
+    /// An operation that has no concrete semantics in the interpreter (e.g. floating point).
+    Unsupported { address: u64, operation: String },
+    /// Control left the slot for somewhere other than the next gadget or the reference target.
+    ControlFlow { expected: u64, actual: u64 },
+    /// A location written by the reference step holds a different value in the chain.
+    Output {
+        location: String,
+        expected: String,
+        actual: String,
+    },
+    /// A configured postcondition does not hold at the end of the chain.
+    Postcondition {
+        location: String,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl Display for Divergence {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            DivergenceKind::Unsupported { address, operation } => write!(
+                f,
+                "step {}: unsupported operation {operation} at {address:#x}",
+                self.step
+            ),
+            DivergenceKind::ControlFlow { expected, actual } => write!(
+                f,
+                "step {}: control flow reached {actual:#x}, expected {expected:#x}",
+                self.step
+            ),
+            DivergenceKind::Output {
+                location,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "step {}: {location} is {actual}, expected {expected}",
+                self.step
+            ),
+            DivergenceKind::Postcondition {
+                location,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "postcondition: {location} is {actual}, expected {expected}"
+            ),
+        }
+    }
+}
+
+/// Re-executes a solved chain concretely and checks it against its reference program.
+///
+/// The chain starts from the initial state of the model, overlaid with the executable segments
+/// of every loaded library. Each gadget is executed op by op, and after every slot the
+/// locations written by the corresponding reference step are compared against a concrete run
+/// of the reference program from the same initial state. Finally, an optional postcondition
+/// is checked against the final state of the chain.
+///
+/// This catches chains that only satisfy the symbolic model, e.g. because a gadget reads
+/// memory whose modeled value disagrees with the binary.
+pub struct ConcreteValidator<'a> {
+    builder: &'a AssignmentModelBuilder,
+    images: Vec<(u64, SegmentFile)>,
+    postcondition: Option<StateEqualityConstraint>,
+}
+
+impl<'a> ConcreteValidator<'a> {
+    pub fn new(builder: &'a AssignmentModelBuilder) -> Result<Self, CrackersError> {
+        let mut images = vec![];
+        for lib in &builder.libraries {
+            let slide = lib.base_address.wrapping_sub(load_image_base(&lib.path)?);
+            images.push((slide, load_segments(&lib.path)?));
+        }
+        Ok(Self {
+            builder,
+            images,
+            postcondition: None,
+        })
+    }
+
+    pub fn with_postcondition(mut self, postcondition: Option<StateEqualityConstraint>) -> Self {
+        self.postcondition = postcondition;
+        self
+    }
+
+    /// Returns the first [`Divergence`] found, or `None` if the chain behaves as modeled.
+    pub fn validate<T: ModelingContext>(
+        &self,
+        model: &AssignmentModel<T>,
+    ) -> Result<Option<Divergence>, CrackersError> {
+        let info = &model.arch_info;
+        let initial = InitialState {
+            model: model.model(),
+            state: model
+                .initial_state()
+                .ok_or(CrackersError::EmptyAssignment)?,
+            code_space: info.default_code_space_index(),
+            images: &self.images,
+        };
+        let mut chain = Machine::new(info, &initial, None);
+        let mut reference = Machine::new(
+            info,
+            &initial,
+            Some(self.builder.templates.initial_memory()),
+        );
+        let steps = self.builder.templates.steps();
+        let gadgets = &self.builder.gadgets;
+        for (index, (step, gadget)) in steps.iter().zip(gadgets).enumerate() {
+            let next = gadgets.get(index + 1).map(|g| g.address());
+            if let Some(kind) = check_slot(
+                &mut reference,
+                &mut chain,
+                step.instructions(),
+                &gadget.instructions,
+                next,
+            )? {
+                return Ok(Some(Divergence { step: index, kind }));
+            }
+        }
+        match &self.postcondition {
+            Some(post) => check_postcondition(&mut chain, post).map(|kind| {
+                kind.map(|kind| Divergence {
+                    step: gadgets.len(),
+                    kind,
+                })
+            }),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Runs a reference step and the gadget of its slot, and compares the locations written by
+/// the step and where control went. `next` is the address of the next gadget, if any.
+fn check_slot(
+    reference: &mut Machine,
+    chain: &mut Machine,
+    step: &[Instruction],
+    gadget: &[Instruction],
+    next: Option<u64>,
+) -> Result<Option<DivergenceKind>, CrackersError> {
+    let info = chain.info;
+    reference.written_log = Some(vec![]);
+    let expected_target = match reference.run(step, false) {
+        Ok(target) => target,
+        Err(Stop::Unsupported { address, operation }) => {
+            return Ok(Some(DivergenceKind::Unsupported { address, operation }));
+        }
+        Err(Stop::Error(e)) => return Err(e),
+    };
+    let actual_target = match chain.run(gadget, true) {
+        Ok(target) => {
+            target.unwrap_or_else(|| gadget.last().map(|i| i.next_addr()).unwrap_or_default())
+        }
+        Err(Stop::Unsupported { address, operation }) => {
+            return Ok(Some(DivergenceKind::Unsupported { address, operation }));
+        }
+        Err(Stop::Error(e)) => return Err(e),
+    };
+
+    for vn in reference.written_log.take().unwrap_or_default() {
+        let expected = reference.read(&vn)?;
+        let actual = chain.read(&vn)?;
+        if expected != actual {
+            return Ok(Some(DivergenceKind::Output {
+                location: format!("{}", vn.display(info)),
+                expected: hex(&expected),
+                actual: hex(&actual),
+            }));
+        }
+    }
+    if let Some(next) = next
+        && next != actual_target
+    {
+        return Ok(Some(DivergenceKind::ControlFlow {
+            expected: next,
+            actual: actual_target,
+        }));
+    }
+    if let Some(expected) = expected_target
+        && expected != actual_target
+    {
+        return Ok(Some(DivergenceKind::ControlFlow {
+            expected,
+            actual: actual_target,
+        }));
+    }
+    Ok(None)
+}
+
+fn check_postcondition(
+    chain: &mut Machine,
+    post: &StateEqualityConstraint,
+) -> Result<Option<DivergenceKind>, CrackersError> {
+    let info = chain.info;
+    let mismatch = |location: String, expected: &[u8], actual: &[u8]| {
+        Some(DivergenceKind::Postcondition {
+            location,
+            expected: hex(expected),
+            actual: hex(actual),
+        })
+    };
+    let mut registers: Vec<_> = post.register.iter().flatten().collect();
+    registers.sort();
+    for (name, value) in registers {
+        let Some(vn) = info.register(name) else {
+            event!(Level::WARN, "Unrecognized register name: {}", name);
+            continue;
+        };
+        let expected = le_bytes(*value as u64 as u128, vn.size);
+        let actual = chain.read(vn)?;
+        if expected != actual {
+            return Ok(mismatch(name.clone(), &expected, &actual));
+        }
+    }
+    if let Some(m) = &post.memory {
+        let vn = info
+            .varnode(&m.space, m.address, m.size)
+            .ok_or(CrackersError::ModelParsingError)?;
+        let expected = le_bytes(m.value as u128, m.size);
+        let actual = chain.read(&vn)?;
+        if expected != actual {
+            return Ok(mismatch(
+                format!("{}[{:#x}]:{:x}", m.space, m.address, m.size),
+                &expected,
+                &actual,
+            ));
+        }
+    }
+    let mut pointers: Vec<_> = post.pointer.iter().flatten().collect();
+    pointers.sort();
+    for (name, value) in pointers {
+        let Some(vn) = info.register(name) else {
+            event!(Level::WARN, "Unrecognized register name: {}", name);
+            continue;
+        };
+        let pointer = to_u64(&chain.read(vn)?);
+        let space = info.default_code_space_index();
+        let actual = (0..value.len() as u64)
+            .map(|i| chain.read_byte(space, pointer.wrapping_add(i)))
+            .collect::<Result<Vec<u8>, _>>()?;
+        if actual != value.as_bytes() {
+            let mut expected = value.as_bytes().to_vec();
+            let mut actual = actual;
+            // Strings are compared in memory order; display them that way too.
+            expected.reverse();
+            actual.reverse();
+            return Ok(mismatch(format!("*{name}"), &expected, &actual));
+        }
+    }
+    Ok(None)
+}
+
+/// The state a chain starts in: the loaded images, falling back to the solved model.
+struct InitialState<'a> {
+    model: &'a Model,
+    state: &'a State,
+    code_space: usize,
+    images: &'a [(u64, SegmentFile)],
+}
+
+impl InitialState<'_> {
+    fn read_byte(&self, space_index: usize, offset: u64) -> Result<u8, CrackersError> {
+        if space_index == self.code_space
+            && let Some(b) = self
+                .images
+                .iter()
+                .find_map(|(slide, img)| img.read_byte(offset.wrapping_sub(*slide)))
+        {
+            return Ok(b);
+        }
+        let bv = self.state.read_varnode(&VarNode {
+            space_index,
+            offset,
+            size: 1,
+        })?;
+        self.model
+            .eval(&bv, true)
+            .and_then(|v| v.as_u64())
+            .map(|v| v as u8)
+            .ok_or(CrackersError::ModelParsingError)
+    }
+}
+
+enum Stop {
+    Unsupported { address: u64, operation: String },
+    Error(CrackersError),
+}
+
+impl From<CrackersError> for Stop {
+    fn from(value: CrackersError) -> Self {
+        Stop::Error(value)
+    }
+}
+
+enum Flow {
+    Next,
+    /// A direct branch; depending on context, constant destinations are p-code relative.
+    Branch(VarNode),
+    /// A branch to a computed address, which always leaves the instruction.
+    Exit(u64),
+    /// Records a destination without interrupting execution, as is done for `CALLOTHER`.
+    Mark(u64),
+}
+
+/// A concrete p-code machine. Values are passed around as little-endian byte vectors,
+/// regardless of the endianness of the space they are stored in.
+struct Machine<'a> {
+    info: &'a SleighArchInfo,
+    initial: &'a InitialState<'a>,
+    valuation: Option<&'a MemoryValuation>,
+    written: HashMap<(usize, u64), u8>,
+    written_log: Option<Vec<VarNode>>,
+}
+
+impl<'a> Machine<'a> {
+    fn new(
+        info: &'a SleighArchInfo,
+        initial: &'a InitialState<'a>,
+        valuation: Option<&'a MemoryValuation>,
+    ) -> Self {
+        Self {
+            info,
+            initial,
+            valuation,
+            written: HashMap::new(),
+            written_log: None,
+        }
+    }
+
+    /// Executes a sequence of instructions and returns the destination control was handed to.
+    ///
+    /// With `relative` set, this behaves like a processor: constant branch destinations index
+    /// p-code ops within the current instruction and execution stops at the first branch out.
+    /// Otherwise, it mirrors how reference programs are modeled: every branch destination is
+    /// absolute and only the last one is kept.
+    fn run(&mut self, instructions: &[Instruction], relative: bool) -> Result<Option<u64>, Stop> {
+        let mut target = None;
+        let mut executed = 0;
+        for instr in instructions {
+            let mut pc = 0;
+            while let Some(op) = instr.ops.get(pc) {
+                executed += 1;
+                if executed > MAX_OPS_PER_SLOT {
+                    return Err(Stop::Unsupported {
+                        address: instr.address,
+                        operation: "p-code loop".to_string(),
+                    });
+                }
+                pc += 1;
+                match self.step(op, instr.address)? {
+                    Flow::Next => {}
+                    Flow::Mark(dest) => target = Some(dest),
+                    Flow::Branch(vn) if relative && vn.is_const() => {
+                        let shift = 64 - 8 * vn.size.min(8) as u32;
+                        let delta = ((vn.offset << shift) as i64) >> shift;
+                        pc = (pc as i64 - 1 + delta).clamp(0, instr.ops.len() as i64) as usize;
+                    }
+                    Flow::Branch(vn) if relative => return Ok(Some(vn.offset)),
+                    Flow::Exit(dest) if relative => return Ok(Some(dest)),
+                    Flow::Branch(vn) => target = Some(vn.offset),
+                    Flow::Exit(dest) => target = Some(dest),
+                }
+            }
+        }
+        Ok(target)
+    }
+
+    fn step(&mut self, op: &PcodeOperation, address: u64) -> Result<Flow, Stop> {
+        let unsupported = |operation: String| Stop::Unsupported { address, operation };
+        match op {
+            PcodeOperation::Copy { input, output } => {
+                let v = self.read(input)?;
+                self.write(output, v)?;
+            }
+            PcodeOperation::Load { input, output } => {
+                let (space, offset) = self.pointer(input)?;
+                let v = self.load(space, offset, output.size)?;
+                self.write(output, v)?;
+            }
+            PcodeOperation::Store { output, input } => {
+                let (space, offset) = self.pointer(output)?;
+                let v = self.read(input)?;
+                self.store(space, offset, v)?;
+            }
+            PcodeOperation::Branch { input } => return Ok(Flow::Branch(input.clone())),
+            PcodeOperation::Call { dest, .. } => return Ok(Flow::Branch(dest.clone())),
+            PcodeOperation::CBranch { input0, input1 } => {
+                if self.read(input1)?.iter().any(|b| *b != 0) {
+                    return Ok(Flow::Branch(input0.clone()));
+                }
+            }
+            PcodeOperation::BranchInd { input }
+            | PcodeOperation::CallInd { input }
+            | PcodeOperation::Return { input } => {
+                let dest = to_u64(&self.read(&input.pointer_location)?);
+                return Ok(Flow::Exit(dest));
+            }
+            PcodeOperation::CallOther { output, inputs, .. } => {
+                // Mirrors the symbolic model: a userop is identified by a hash of its inputs.
+                let mut hasher = DefaultHasher::new();
+                for vn in inputs {
+                    vn.hash(&mut hasher);
+                }
+                let hash = hasher.finish();
+                if let Some(out) = output {
+                    self.write(out, le_bytes(hash as u128, out.size))?;
+                }
+                return Ok(Flow::Mark(hash));
+            }
+            PcodeOperation::IntZExt { input, output } => {
+                let v = self.read(input)?;
+                self.write(output, resize(v, output.size, false))?;
+            }
+            PcodeOperation::IntSExt { input, output } => {
+                let v = self.read(input)?;
+                self.write(output, resize(v, output.size, true))?;
+            }
+            PcodeOperation::Piece {
+                output,
+                input0,
+                input1,
+            } => {
+                let mut v = self.read(input1)?;
+                v.extend(self.read(input0)?);
+                self.write(output, resize(v, output.size, false))?;
+            }
+            PcodeOperation::SubPiece {
+                output,
+                input0,
+                input1,
+            } => {
+                let v = self.read(input0)?;
+                let v = v.get(input1.offset as usize..).unwrap_or_default().to_vec();
+                self.write(output, resize(v, output.size, false))?;
+            }
+            PcodeOperation::PopCount { input, output } => {
+                let count: u32 = self.read(input)?.iter().map(|b| b.count_ones()).sum();
+                self.write(output, le_bytes(count as u128, output.size))?;
+            }
+            PcodeOperation::IntNegate { input, output } => {
+                let v = self.int(input, address)?;
+                self.write(output, le_bytes(!v, output.size))?;
+            }
+            PcodeOperation::Int2Comp { input, output } => {
+                let v = self.int(input, address)?;
+                self.write(output, le_bytes(v.wrapping_neg(), output.size))?;
+            }
+            PcodeOperation::BoolNegate { input, output } => {
+                let v = self.int(input, address)?;
+                self.write(output, le_bytes((v & 1) ^ 1, output.size))?;
+            }
+            PcodeOperation::IntEqual {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntNotEqual {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntLess {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntLessEqual {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntSignedLess {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntSignedLessEqual {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntAdd {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntSub {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntCarry {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntSignedCarry {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntSignedBorrow {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntXor {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntAnd {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntOr {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntLeftShift {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntRightShift {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntSignedRightShift {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntMult {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntDiv {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntSignedDiv {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntRem {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::IntSignedRem {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::BoolXor {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::BoolAnd {
+                output,
+                input0,
+                input1,
+            }
+            | PcodeOperation::BoolOr {
+                output,
+                input0,
+                input1,
+            } => {
+                let a = self.int(input0, address)?;
+                let b = self.int(input1, address)?;
+                let v = binary_op(op, a, b, input0.size)
+                    .ok_or_else(|| unsupported(format!("{:?} with a zero divisor", op.opcode())))?;
+                self.write(output, le_bytes(v, output.size))?;
+            }
+            _ => return Err(unsupported(format!("{:?}", op.opcode()))),
+        }
+        Ok(Flow::Next)
+    }
+
+    fn int(&mut self, vn: &VarNode, address: u64) -> Result<u128, Stop> {
+        if vn.size > 16 {
+            return Err(Stop::Unsupported {
+                address,
+                operation: format!("{}-byte integer arithmetic", vn.size),
+            });
+        }
+        Ok(self
+            .read(vn)?
+            .iter()
+            .rev()
+            .fold(0u128, |acc, b| (acc << 8) | *b as u128))
+    }
+
+    fn pointer(&mut self, vn: &IndirectVarNode) -> Result<(usize, u64), CrackersError> {
+        let offset = to_u64(&self.read(&vn.pointer_location)?);
+        Ok((vn.pointer_space_index, offset))
+    }
+
+    fn read(&mut self, vn: &VarNode) -> Result<Vec<u8>, CrackersError> {
+        if vn.is_const() {
+            return Ok(le_bytes(vn.offset as u128, vn.size));
+        }
+        self.load(vn.space_index, vn.offset, vn.size)
+    }
+
+    fn write(&mut self, vn: &VarNode, value: Vec<u8>) -> Result<(), CrackersError> {
+        self.store(vn.space_index, vn.offset, value)
+    }
+
+    fn read_byte(&self, space_index: usize, offset: u64) -> Result<u8, CrackersError> {
+        if let Some(b) = self.written.get(&(space_index, offset)) {
+            return Ok(*b);
+        }
+        if let Some(b) = self
+            .valuation
+            .and_then(|v| v.read_byte(space_index, offset))
+        {
+            return Ok(b);
+        }
+        self.initial.read_byte(space_index, offset)
+    }
+
+    fn load(
+        &mut self,
+        space_index: usize,
+        offset: u64,
+        size: usize,
+    ) -> Result<Vec<u8>, CrackersError> {
+        let mut bytes = (0..size as u64)
+            .map(|i| self.read_byte(space_index, offset.wrapping_add(i)))
+            .collect::<Result<Vec<u8>, _>>()?;
+        if self.is_big_endian(space_index) {
+            bytes.reverse();
+        }
+        Ok(bytes)
+    }
+
+    fn store(
+        &mut self,
+        space_index: usize,
+        offset: u64,
+        mut value: Vec<u8>,
+    ) -> Result<(), CrackersError> {
+        let space = self
+            .info
+            .get_space(space_index)
+            .ok_or(CrackersError::ModelParsingError)?;
+        if space.endianness == SleighEndianness::Big {
+            value.reverse();
+        }
+        if space._type == SpaceType::IPTR_PROCESSOR
+            && let Some(log) = &mut self.written_log
+        {
+            let vn = VarNode {
+                space_index,
+                offset,
+                size: value.len(),
+            };
+            if !log.contains(&vn) {
+                log.push(vn);
+            }
+        }
+        for (i, b) in value.into_iter().enumerate() {
+            self.written
+                .insert((space_index, offset.wrapping_add(i as u64)), b);
+        }
+        Ok(())
+    }
+
+    fn is_big_endian(&self, space_index: usize) -> bool {
+        self.info
+            .get_space(space_index)
+            .map(|s| s.endianness == SleighEndianness::Big)
+            .unwrap_or(false)
+    }
+}
+
+/// Evaluates a two-input integer or boolean operation on values of `size` bytes. Returns
+/// `None` on division by zero.
+fn binary_op(op: &PcodeOperation, a: u128, b: u128, size: usize) -> Option<u128> {
+    let bits = (size.min(16) * 8) as u32;
+    let mask = u128::MAX >> (128 - bits);
+    let signed = |v: u128| ((v << (128 - bits)) as i128) >> (128 - bits);
+    let (sa, sb) = (signed(a), signed(b));
+    let fits = |v: Option<i128>| v.is_some_and(|v| signed(v as u128 & mask) == v);
+    let shift = |f: fn(u128, u32) -> u128, fill: u128| match b < bits as u128 {
+        true => f(a, b as u32),
+        false => fill,
+    };
+    let v = match op {
+        PcodeOperation::IntEqual { .. } => (a == b) as u128,
+        PcodeOperation::IntNotEqual { .. } => (a != b) as u128,
+        PcodeOperation::IntLess { .. } => (a < b) as u128,
+        PcodeOperation::IntLessEqual { .. } => (a <= b) as u128,
+        PcodeOperation::IntSignedLess { .. } => (sa < sb) as u128,
+        PcodeOperation::IntSignedLessEqual { .. } => (sa <= sb) as u128,
+        PcodeOperation::IntAdd { .. } => a.wrapping_add(b),
+        PcodeOperation::IntSub { .. } => a.wrapping_sub(b),
+        PcodeOperation::IntCarry { .. } => a.checked_add(b).is_none_or(|v| v > mask) as u128,
+        PcodeOperation::IntSignedCarry { .. } => !fits(sa.checked_add(sb)) as u128,
+        PcodeOperation::IntSignedBorrow { .. } => !fits(sa.checked_sub(sb)) as u128,
+        PcodeOperation::IntXor { .. } => a ^ b,
+        PcodeOperation::IntAnd { .. } => a & b,
+        PcodeOperation::IntOr { .. } => a | b,
+        PcodeOperation::IntLeftShift { .. } => shift(|a, b| a << b, 0),
+        PcodeOperation::IntRightShift { .. } => shift(|a, b| a >> b, 0),
+        PcodeOperation::IntSignedRightShift { .. } => {
+            let fill = if sa < 0 { u128::MAX } else { 0 };
+            match b < bits as u128 {
+                true => (sa >> b) as u128,
+                false => fill,
+            }
+        }
+        PcodeOperation::IntMult { .. } => a.wrapping_mul(b),
+        PcodeOperation::IntDiv { .. } => a.checked_div(b)?,
+        PcodeOperation::IntRem { .. } => a.checked_rem(b)?,
+        PcodeOperation::IntSignedDiv { .. } if sb != 0 => sa.wrapping_div(sb) as u128,
+        PcodeOperation::IntSignedRem { .. } if sb != 0 => sa.wrapping_rem(sb) as u128,
+        PcodeOperation::IntSignedDiv { .. } | PcodeOperation::IntSignedRem { .. } => return None,
+        PcodeOperation::BoolXor { .. } => (a ^ b) & 1,
+        PcodeOperation::BoolAnd { .. } => a & b & 1,
+        PcodeOperation::BoolOr { .. } => (a | b) & 1,
+        _ => unreachable!("not a binary operation"),
+    };
+    Some(v & mask)
+}
+
+fn le_bytes(value: u128, size: usize) -> Vec<u8> {
+    resize(value.to_le_bytes().to_vec(), size, false)
+}
+
+fn resize(mut value: Vec<u8>, size: usize, sign_extend: bool) -> Vec<u8> {
+    let negative = value.last().is_some_and(|b| b & 0x80 != 0);
+    let fill = if sign_extend && negative { 0xff } else { 0 };
+    value.resize(size, fill);
+    value
+}
+
+fn to_u64(value: &[u8]) -> u64 {
+    value
+        .iter()
+        .take(8)
+        .rev()
+        .fold(0u64, |acc, b| (acc << 8) | *b as u64)
+}
+
+fn hex(value: &[u8]) -> String {
+    let digits: String = value.iter().rev().map(|b| format!("{b:02x}")).collect();
+    format!("0x{digits}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use jingle::modeling::State;
+    use jingle::sleigh::{IndirectVarNode, PcodeOperation, VarNode};
+    use z3::{Model, Solver};
+
+    use crate::config::constraint::StateEqualityConstraint;
+    use crate::synthesis::validation::{
+        DivergenceKind, InitialState, Machine, Stop, binary_op, check_postcondition, check_slot,
+        le_bytes,
+    };
+    use crate::test_util::{RAM, REGISTER, arch_info, constant, copy, instruction, register};
+
+    /// Returns to the address held in `RBX`.
+    fn ret() -> PcodeOperation {
+        PcodeOperation::Return {
+            input: IndirectVarNode {
+                pointer_space_index: RAM,
+                pointer_location: register(8),
+                access_size_bytes: 8,
+            },
+        }
+    }
+
+    /// An unconstrained model, so that initial values are whatever z3 completes them to.
+    fn model() -> Model {
+        let solver = Solver::new();
+        solver.check();
+        solver.get_model().unwrap()
+    }
+
+    #[test]
+    fn test_run() {
+        let info = arch_info();
+        let (model, state) = (model(), State::new(&info));
+        let initial = InitialState {
+            model: &model,
+            state: &state,
+            code_space: RAM,
+            images: &[],
+        };
+        let chain = [
+            instruction(
+                0x1000,
+                vec![
+                    copy(5, 0),
+                    PcodeOperation::IntAdd {
+                        output: register(0),
+                        input0: register(0),
+                        input1: constant(3),
+                    },
+                ],
+            ),
+            instruction(
+                0x1001,
+                vec![
+                    PcodeOperation::Copy {
+                        input: register(0),
+                        output: register(8),
+                    },
+                    ret(),
+                    copy(1, 16),
+                ],
+            ),
+        ];
+        let mut machine = Machine::new(&info, &initial, None);
+        assert_eq!(machine.run(&chain, true).ok().flatten(), Some(8));
+        assert_eq!(machine.read(&register(8)).unwrap(), le_bytes(8, 8));
+        // execution stops at the return
+        assert!(!machine.written.contains_key(&(REGISTER, 16)));
+    }
+
+    #[test]
+    fn test_relative_branches() {
+        let info = arch_info();
+        let (model, state) = (model(), State::new(&info));
+        let initial = InitialState {
+            model: &model,
+            state: &state,
+            code_space: RAM,
+            images: &[],
+        };
+        let skip = [instruction(
+            0x2000,
+            vec![
+                PcodeOperation::CBranch {
+                    input0: constant(2),
+                    input1: constant(1),
+                },
+                copy(1, 0),
+                copy(2, 16),
+            ],
+        )];
+        let mut machine = Machine::new(&info, &initial, None);
+        assert_eq!(machine.run(&skip, true).ok(), Some(None));
+        assert!(!machine.written.contains_key(&(REGISTER, 0)));
+        assert_eq!(machine.read(&register(16)).unwrap(), le_bytes(2, 8));
+
+        // in reference programs, constant destinations are absolute and execution goes on
+        let mut machine = Machine::new(&info, &initial, None);
+        assert_eq!(machine.run(&skip, false).ok(), Some(Some(2)));
+        assert_eq!(machine.read(&register(0)).unwrap(), le_bytes(1, 8));
+
+        let spin = [instruction(
+            0x3000,
+            vec![
+                copy(1, 0),
+                PcodeOperation::Branch {
+                    input: constant(u64::MAX),
+                },
+            ],
+        )];
+        let mut machine = Machine::new(&info, &initial, None);
+        assert!(matches!(
+            machine.run(&spin, true),
+            Err(Stop::Unsupported {
+                address: 0x3000,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_check_slot() {
+        let info = arch_info();
+        let (model, state) = (model(), State::new(&info));
+        let initial = InitialState {
+            model: &model,
+            state: &state,
+            code_space: RAM,
+            images: &[],
+        };
+        let step = [instruction(0, vec![copy(7, 0)])];
+        let check = |gadget: Vec<PcodeOperation>, next: Option<u64>| {
+            let mut reference = Machine::new(&info, &initial, None);
+            let mut chain = Machine::new(&info, &initial, None);
+            let gadget = [instruction(0x1000, gadget)];
+            check_slot(&mut reference, &mut chain, &step, &gadget, next).unwrap()
+        };
+
+        assert_eq!(
+            check(vec![copy(7, 0), copy(0x5000, 8), ret()], Some(0x5000)),
+            None
+        );
+        assert_eq!(
+            check(vec![copy(6, 0), copy(0x5000, 8), ret()], Some(0x5000)),
+            Some(DivergenceKind::Output {
+                location: "RAX".to_string(),
+                expected: "0x0000000000000007".to_string(),
+                actual: "0x0000000000000006".to_string(),
+            })
+        );
+        assert_eq!(
+            check(vec![copy(7, 0), copy(0x6000, 8), ret()], Some(0x5000)),
+            Some(DivergenceKind::ControlFlow {
+                expected: 0x5000,
+                actual: 0x6000,
+            })
+        );
+    }
+
+    #[test]
+    fn test_check_postcondition() {
+        let info = arch_info();
+        let (model, state) = (model(), State::new(&info));
+        let initial = InitialState {
+            model: &model,
+            state: &state,
+            code_space: RAM,
+            images: &[],
+        };
+        let mut chain = Machine::new(&info, &initial, None);
+        chain.write(&register(0), le_bytes(7, 8)).unwrap();
+        let post = |value: i64| StateEqualityConstraint {
+            register: Some(HashMap::from([("RAX".to_string(), value)])),
+            pointer: None,
+            memory: None,
+        };
+        assert_eq!(check_postcondition(&mut chain, &post(7)).unwrap(), None);
+        assert_eq!(
+            check_postcondition(&mut chain, &post(8)).unwrap(),
+            Some(DivergenceKind::Postcondition {
+                location: "RAX".to_string(),
+                expected: "0x0000000000000008".to_string(),
+                actual: "0x0000000000000007".to_string(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_binary_op() {
+        let vn = VarNode {
+            space_index: 0,
+            offset: 0,
+            size: 1,
+        };
+        let op = |f: fn(VarNode, VarNode, VarNode) -> PcodeOperation| {
+            f(vn.clone(), vn.clone(), vn.clone())
+        };
+        let add = op(|output, input0, input1| PcodeOperation::IntAdd {
+            output,
+            input0,
+            input1,
+        });
+        let carry = op(|output, input0, input1| PcodeOperation::IntCarry {
+            output,
+            input0,
+            input1,
+        });
+        let scarry = op(|output, input0, input1| PcodeOperation::IntSignedCarry {
+            output,
+            input0,
+            input1,
+        });
+        let sar = op(
+            |output, input0, input1| PcodeOperation::IntSignedRightShift {
+                output,
+                input0,
+                input1,
+            },
+        );
+        let sdiv = op(|output, input0, input1| PcodeOperation::IntSignedDiv {
+            output,
+            input0,
+            input1,
+        });
+        assert_eq!(binary_op(&add, 0xff, 0x02, 1), Some(0x01));
+        assert_eq!(binary_op(&carry, 0xff, 0x02, 1), Some(1));
+        assert_eq!(binary_op(&carry, 0x7f, 0x01, 1), Some(0));
+        assert_eq!(binary_op(&scarry, 0x7f, 0x01, 1), Some(1));
+        assert_eq!(binary_op(&sar, 0x80, 0x04, 1), Some(0xf8));
+        assert_eq!(binary_op(&sar, 0x80, 0x20, 1), Some(0xff));
+        assert_eq!(binary_op(&sdiv, 0xfc, 0x02, 1), Some(0xfe));
+        assert_eq!(binary_op(&sdiv, 0xfc, 0x00, 1), None);
+    }
+}
@@ -0,0 +1,105 @@
+//! A small synthetic architecture, and gadgets for it, for tests that can't load a sleigh
+//! specification.
+
+use jingle::sleigh::{
+    Disassembly, IndirectVarNode, Instruction, PcodeOperation, SleighArchInfo, SleighEndianness,
+    SpaceInfo, SpaceType, VarNode,
+};
+
+use crate::gadget::Gadget;
+
+pub(crate) const RAM: usize = 1;
+pub(crate) const REGISTER: usize = 2;
+
+/// A little-endian architecture with constant, `ram` and `register` spaces, and the 8-byte
+/// registers `RAX`, `RBX`, `RCX`, `RSP` and `RDX`.
+pub(crate) fn arch_info() -> SleighArchInfo {
+    let spaces = [
+        ("const", SpaceType::IPTR_CONSTANT),
+        ("ram", SpaceType::IPTR_PROCESSOR),
+        ("register", SpaceType::IPTR_PROCESSOR),
+    ]
+    .into_iter()
+    .enumerate()
+    .map(|(index, (name, _type))| SpaceInfo {
+        name: name.to_string(),
+        index,
+        index_size_bytes: 8,
+        word_size_bytes: 1,
+        _type,
+        endianness: SleighEndianness::Little,
+    });
+    let registers = [
+        ("RAX", 0),
+        ("RBX", 8),
+        ("RCX", 16),
+        ("RSP", 24),
+        ("RDX", 32),
+    ]
+    .into_iter()
+    .map(|(name, offset)| (register(offset), name.to_string()));
+    SleighArchInfo::new("test".to_string(), registers, spaces, RAM, vec![])
+}
+
+pub(crate) fn register(offset: u64) -> VarNode {
+    VarNode {
+        space_index: REGISTER,
+        offset,
+        size: 8,
+    }
+}
+
+pub(crate) fn constant(value: u64) -> VarNode {
+    VarNode {
+        space_index: VarNode::CONST_SPACE_INDEX,
+        offset: value,
+        size: 8,
+    }
+}
+
+/// Sets the register at `output` to `value`.
+pub(crate) fn copy(value: u64, output: u64) -> PcodeOperation {
+    PcodeOperation::Copy {
+        input: constant(value),
+        output: register(output),
+    }
+}
+
+/// An instruction one byte long.
+pub(crate) fn instruction(address: u64, ops: Vec<PcodeOperation>) -> Instruction {
+    Instruction {
+        disassembly: Disassembly {
+            mnemonic: "test".to_string(),
+            args: "".to_string(),
+        },
+        ops,
+        length: 1,
+        address,
+    }
+}
+
+/// A gadget of a single instruction at `address`, which runs `ops` and then pops the address at
+/// the top of the stack and returns to it, clobbering `RDX`.
+pub(crate) fn gadget(address: u64, mut ops: Vec<PcodeOperation>) -> Gadget {
+    ops.push(PcodeOperation::Load {
+        input: IndirectVarNode {
+            pointer_space_index: RAM,
+            pointer_location: register(24),
+            access_size_bytes: 8,
+        },
+        output: register(32),
+    });
+    ops.push(PcodeOperation::IntAdd {
+        input0: register(24),
+        input1: constant(8),
+        output: register(24),
+    });
+    ops.push(PcodeOperation::Return {
+        input: IndirectVarNode {
+            pointer_space_index: RAM,
+            pointer_location: register(32),
+            access_size_bytes: 8,
+        },
+    });
+    Gadget::new(RAM, vec![instruction(address, ops)])
+}
@@ -1,12 +1,14 @@
 from .crackers import (
     AssignmentModel,
     ConstraintConfig,
+    ControlledLocation,
     CrackersConfig,
     CrackersLogLevel,
     DecisionResult,
     GadgetLibraryConfig,
     MemoryEqualityConstraint,
     MetaConfig,
+    Payload,
     PointerRange,
     PointerRangeConstraints,
     SleighConfig,
@@ -29,12 +31,14 @@ from .jingle import (
 class crackers:
     AssignmentModel: AssignmentModel
     ConstraintConfig: ConstraintConfig
+    ControlledLocation: ControlledLocation
     CrackersConfig: CrackersConfig
     CrackersLogLevel: CrackersLogLevel
     DecisionResult: DecisionResult
     GadgetLibraryConfig: GadgetLibraryConfig
     MemoryEqualityConstraint: MemoryEqualityConstraint
     MetaConfig: MetaConfig
+    Payload: Payload
     PointerRange: PointerRange
     PointerRangeConstraints: PointerRangeConstraints
     SleighConfig: SleighConfig
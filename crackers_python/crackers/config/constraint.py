@@ -162,11 +162,13 @@ class ConstraintConfig(BaseModel):
         precondition (list[StateConstraint] | None): Constraints on the initial state.
         postcondition (list[StateConstraint] | None): Constraints on the final state.
         pointer (list[TransitionConstraint] | None): Constraints on the transitions between states (named 'pointer' for compatibility reasons, but can express any transition constraint)
+        bad_bytes (list[int] | None): Bytes that may not appear in attacker-controlled memory read by the chain or in the addresses of its gadgets.
     """
 
     precondition: list[StateConstraint] | None = None
     postcondition: list[StateConstraint] | None = None
     pointer: list[TransitionConstraint] | None = None
+    bad_bytes: list[int] | None = None
 
     @field_serializer("precondition", "postcondition")
     def serialize_state_constraints(value, _info):
@@ -1,4 +1,5 @@
 import json
+from typing import Callable, Optional
 
 from pydantic import BaseModel
 
@@ -13,7 +14,7 @@ from crackers.config.meta import MetaConfig
 from crackers.config.sleigh import SleighConfig
 from crackers.config.specification import ReferenceProgramConfig
 from crackers.config.synthesis import SynthesisConfig
-from crackers.crackers import DecisionResult
+from crackers.crackers import DecisionResult, SynthesisProgress
 
 
 class CrackersConfig(BaseModel):
@@ -36,7 +37,13 @@ class CrackersConfig(BaseModel):
     synthesis: SynthesisConfig
     constraint: ConstraintConfig
 
-    def run(self) -> DecisionResult:
+    def run(
+        self, on_progress: Optional[Callable[[SynthesisProgress], None]] = None
+    ) -> DecisionResult:
+        """
+        Runs synthesis. If given, `on_progress` is called with the running statistics of the
+        search whenever it makes progress.
+        """
         # Dump to a Python dict so we can transform the `specification` into
         # the enum-shaped representation the Rust side expects (serde enum).
         data = self.model_dump(mode="json")
@@ -92,5 +99,7 @@ class CrackersConfig(BaseModel):
             resolved.add_postcondition(c._code)
         for d in custom_transition_constraints:
             resolved.add_transition_constraint(d._code)
+        if on_progress is not None:
+            resolved.on_progress(on_progress)
 
         return resolved.run()
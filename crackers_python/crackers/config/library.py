@@ -24,6 +24,7 @@ class LibraryConfig(BaseModel):
         sample_size (int | None): Maximum number of gadgets to randomly sample (None to use all gadgets).
         base_address (int | None): Base address for loading the library, or None if not specified.
         loaded_libraries (list[LoadedLibraryConfig] | None): Optional additional libraries to load alongside the primary one.
+        cache_path (str | None): Optional path of a gadget cache; reused while the binaries, gadget settings and sleigh configuration are unchanged.
     """
 
     max_gadget_length: int
@@ -31,3 +32,4 @@ class LibraryConfig(BaseModel):
     sample_size: int | None
     base_address: int | None
     loaded_libraries: list[LoadedLibraryConfig] | None = None
+    cache_path: str | None = None
@@ -10,10 +10,61 @@ class SynthesisStrategy(str, Enum):
     Members:
         Sat: The default strategy; should generally be used.
         Optimize: Uses an alternative encoding that biases the algorithm to select shorter gadgets, which may negatively impact synthesis performance.
+        Portfolio: Races several SAT and optimize encodings against each other, sharing what they learn.
     """
 
     SAT = "sat"
     OPTIMIZE = "optimize"
+    PORTFOLIO = "portfolio"
+
+
+class ObjectiveKind(str, Enum):
+    """
+    A cost of a gadget that the optimize strategy can minimize.
+
+    Members:
+        INSTRUCTION_COUNT: Number of instructions in the gadget.
+        PAYLOAD_BYTES: Number of payload bytes the gadget consumes: how far it moves the stack pointer if stack_pointer is set and that is constant, or else the number of bytes it reads through pointers.
+        CLOBBERED_REGISTERS: Number of registers the gadget writes.
+        OUT_OF_RANGE_WRITES: Number of memory writes whose target is not a constant address within a declared pointer write range.
+        AVOID_LIBRARIES: One for gadgets located in one of the given libraries, zero otherwise.
+    """
+
+    INSTRUCTION_COUNT = "instruction_count"
+    PAYLOAD_BYTES = "payload_bytes"
+    CLOBBERED_REGISTERS = "clobbered_registers"
+    OUT_OF_RANGE_WRITES = "out_of_range_writes"
+    AVOID_LIBRARIES = "avoid_libraries"
+
+
+class OptimizationObjective(BaseModel):
+    """
+    One term of the cost minimized by the optimize strategy.
+
+    Attributes:
+        kind (ObjectiveKind): The cost of each gadget.
+        weight (int): Multiplier applied to the cost.
+        libraries (list[str]): For AVOID_LIBRARIES, the libraries to avoid, by path or file name.
+        stack_pointer (str | None): For PAYLOAD_BYTES, the name of the stack pointer register.
+    """
+
+    kind: ObjectiveKind
+    weight: int = 1
+    libraries: list[str] = []
+    stack_pointer: str | None = None
+
+
+class PortfolioMember(BaseModel):
+    """
+    One encoding raced by the portfolio strategy.
+
+    Attributes:
+        strategy (SynthesisStrategy): The encoding; either SAT or OPTIMIZE.
+        seed (int): Seed of the encoding's solver.
+    """
+
+    strategy: SynthesisStrategy
+    seed: int = 0
 
 
 class SynthesisConfig(BaseModel):
@@ -22,12 +73,30 @@ class SynthesisConfig(BaseModel):
 
     Attributes:
         strategy (SynthesisStrategy): The gadget selection strategy to use.
+        objectives (list[OptimizationObjective] | None): Terms of the cost minimized by the optimize strategy. Defaults to the number of instructions.
+        portfolio (list[PortfolioMember] | None): The encodings raced by the portfolio strategy. Defaults to a SAT encoding, an optimize encoding and a differently seeded SAT encoding.
         max_candidates_per_slot (int): Number of gadgets to collect for each step of the reference program. Higher values provide more choices but increase runtime.
         parallel (int): Number of worker threads for evaluating candidate chains.
         combine_instructions (bool): Whether to allow synthesis of shorter gadget chains.
+        timeout_secs (int | None): Wall-clock budget for the whole synthesis run, in seconds. When it runs out, a Timeout result describing the progress made is returned.
+        theory_timeout_ms (int | None): Timeout for checking each candidate chain, in milliseconds. Chains whose check times out are retried once on another worker, then skipped.
+        concurrent_partitions (int | None): Number of instruction partitions to search at once when combine_instructions is set, sharing the parallel workers. At most parallel partitions are searched at once. Defaults to one.
+        candidate_expansions (int | None): Number of times a step that runs out of candidate gadgets may be given another max_candidates_per_slot of them from the library. Defaults to zero.
+        prefilter_candidates (bool | None): Whether to check each candidate gadget against its step alone before the search, ruling out the incompatible ones up front. Defaults to true.
+        conflict_minimization (int | None): Extra solver calls each refuted theory check may make to narrow its conflicts to restrictions on the first or last slot and to shrink them. Conflicts independent of the first are found regardless. Defaults to one.
+        improvement_timeout_secs (int | None): Once a chain is found, how many more seconds to search for cheaper ones under the objectives. The cheapest chain found is returned.
     """
 
     strategy: SynthesisStrategy
+    objectives: list[OptimizationObjective] | None = None
+    portfolio: list[PortfolioMember] | None = None
     max_candidates_per_slot: int
     parallel: int
     combine_instructions: bool
+    timeout_secs: int | None = None
+    theory_timeout_ms: int | None = None
+    concurrent_partitions: int | None = None
+    candidate_expansions: int | None = None
+    prefilter_candidates: bool | None = None
+    conflict_minimization: int | None = None
+    improvement_timeout_secs: int | None = None
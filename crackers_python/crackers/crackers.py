@@ -2,37 +2,53 @@ from ._internal import crackers as _crackers
 
 # Expose all public symbols from crackers.pyi
 AssignmentModel = _crackers.AssignmentModel
+ChainIterator = _crackers.ChainIterator
 ConstraintConfig = _crackers.ConstraintConfig
+ControlledLocation = _crackers.ControlledLocation
 CrackersConfig = _crackers.CrackersConfig
 CrackersLogLevel = _crackers.CrackersLogLevel
 DecisionResult = _crackers.DecisionResult
 GadgetLibraryConfig = _crackers.GadgetLibraryConfig
 MemoryEqualityConstraint = _crackers.MemoryEqualityConstraint
 MetaConfig = _crackers.MetaConfig
+ObjectiveKind = _crackers.ObjectiveKind
+OptimizationObjective = _crackers.OptimizationObjective
+Payload = _crackers.Payload
 PointerRange = _crackers.PointerRange
+PortfolioMember = _crackers.PortfolioMember
 PointerRangeConstraints = _crackers.PointerRangeConstraints
 SleighConfig = _crackers.SleighConfig
+StageCounts = _crackers.StageCounts
 SpecificationConfig = _crackers.SpecificationConfig
 StateEqualityConstraint = _crackers.StateEqualityConstraint
 SynthesisConfig = _crackers.SynthesisConfig
 SynthesisParams = _crackers.SynthesisParams
+SynthesisProgress = _crackers.SynthesisProgress
 SynthesisSelectionStrategy = _crackers.SynthesisSelectionStrategy
 
 __all__ = [
     "AssignmentModel",
+    "ChainIterator",
     "ConstraintConfig",
+    "ControlledLocation",
     "CrackersConfig",
     "CrackersLogLevel",
     "DecisionResult",
     "GadgetLibraryConfig",
     "MemoryEqualityConstraint",
     "MetaConfig",
+    "ObjectiveKind",
+    "OptimizationObjective",
+    "Payload",
     "PointerRange",
+    "PortfolioMember",
     "PointerRangeConstraints",
     "SleighConfig",
+    "StageCounts",
     "SpecificationConfig",
     "StateEqualityConstraint",
     "SynthesisConfig",
     "SynthesisParams",
+    "SynthesisProgress",
     "SynthesisSelectionStrategy",
 ]
@@ -1,4 +1,4 @@
-from typing import Callable, Iterable, List, Optional, Union
+from typing import Callable, Iterable, Iterator, List, Optional, Union
 
 from z3 import z3  # type: ignore
 
@@ -6,13 +6,16 @@ from .jingle import ModeledBlock, ResolvedVarNode, State
 
 __all__ = [
     "AssignmentModel",
+    "ChainIterator",
     "ConstraintConfig",
+    "ControlledLocation",
     "CrackersConfig",
     "CrackersLogLevel",
     "DecisionResult",
     "GadgetLibraryConfig",
     "MemoryEqualityConstraint",
     "MetaConfig",
+    "Payload",
     "PointerRange",
     "PointerRangeConstraints",
     "SleighConfig",
@@ -20,6 +23,7 @@ __all__ = [
     "StateEqualityConstraint",
     "SynthesisConfig",
     "SynthesisParams",
+    "SynthesisProgress",
     "SynthesisSelectionStrategy",
     "DecisionResultType",
     "StateConstraintGenerator",
@@ -37,11 +41,30 @@ class AssignmentModel:
         Iterable[tuple[str, z3.BitVecRef]]
     def output_summary(self, model_completion: bool):
         Iterable[tuple[str, z3.BitVecRef]]
+    def payload(
+        self, stack_pointer: str, filler: int = 0, max_length: int = 0x1000
+    ) -> Payload: ...
+    def pwntools_script(
+        self, stack_pointer: str, filler: int = 0, max_length: int = 0x1000
+    ) -> str: ...
+    def validate(self) -> Optional[str]: ...
+    def statistics(self) -> Optional[SynthesisProgress]: ...
+
+class ControlledLocation:
+    space: str
+    address: int
+    bytes: bytes
+
+class Payload:
+    base_address: int
+    bytes: bytes
+    external: list[ControlledLocation]
 
 class ConstraintConfig:
     precondition: Optional[StateEqualityConstraint]
     postcondition: Optional[StateEqualityConstraint]
     pointer: Optional[PointerRangeConstraints]
+    bad_bytes: Optional[list[int]]
 
 class CrackersConfig:
     meta: MetaConfig
@@ -75,6 +98,7 @@ class GadgetLibraryConfig:
     sample_size: Optional[int]
     base_address: Optional[int]
     loaded_libraries: Optional[List[LoadedLibraryConfig]]
+    cache_path: Optional[str]
 
 class MemoryEqualityConstraint:
     space: str
@@ -126,12 +150,39 @@ class StateEqualityConstraint:
 class SynthesisSelectionStrategy:
     SatStrategy: int
     OptimizeStrategy: int
+    PortfolioStrategy: int
+
+class ObjectiveKind:
+    InstructionCount: int
+    PayloadBytes: int
+    ClobberedRegisters: int
+    OutOfRangeWrites: int
+    AvoidLibraries: int
+
+class OptimizationObjective:
+    kind: ObjectiveKind
+    weight: int
+    libraries: list[str]
+    stack_pointer: Optional[str]
+
+class PortfolioMember:
+    strategy: SynthesisSelectionStrategy
+    seed: int
 
 class SynthesisConfig:
     strategy: SynthesisSelectionStrategy
+    objectives: Optional[list[OptimizationObjective]]
+    portfolio: Optional[list[PortfolioMember]]
     max_candidates_per_slot: int
     parallel: int
     combine_instructions: bool
+    timeout_secs: Optional[int]
+    theory_timeout_ms: Optional[int]
+    concurrent_partitions: Optional[int]
+    candidate_expansions: Optional[int]
+    prefilter_candidates: Optional[bool]
+    conflict_minimization: Optional[int]
+    improvement_timeout_secs: Optional[int]
 
 class PythonDecisionResult_AssignmentFound(DecisionResult):
     _0: AssignmentModel
@@ -139,24 +190,61 @@ class PythonDecisionResult_AssignmentFound(DecisionResult):
 
 class SelectionFailure:
     indices: list[int]
+    statistics: SynthesisProgress
 
 class PythonDecisionResult_Unsat(DecisionResult):
     _0: SelectionFailure
     __match_args__ = ("_0",)
 
+class StageCounts:
+    combined_semantics: int
+    consistency: int
+    branch: int
+    precondition: int
+    postcondition: int
+    bad_bytes: int
+
+class SynthesisProgress:
+    partitions_attempted: int
+    assignments_issued: int
+    assignments_checked: int
+    conflicts_learned: int
+    conflicts_by_stage: StageCounts
+    conflict_decisions: int
+    largest_conflict: int
+    theory_timeouts: int
+    chains_found: int
+    candidates_per_slot: list[int]
+    candidate_expansions: int
+    candidates_prefiltered: int
+    prefiltered_per_slot: list[int]
+
+class PythonDecisionResult_Timeout(DecisionResult):
+    _0: SynthesisProgress
+    __match_args__ = ("_0",)
+
 class DecisionResult:
     AssignmentFound: PythonDecisionResult_AssignmentFound
     Unsat: PythonDecisionResult_Unsat
+    Timeout: PythonDecisionResult_Timeout
 
 DecisionResultType = Union[
-    "PythonDecisionResult_AssignmentFound", "PythonDecisionResult_Unsat"
+    "PythonDecisionResult_AssignmentFound",
+    "PythonDecisionResult_Unsat",
+    "PythonDecisionResult_Timeout",
 ]
 
 StateConstraintGenerator = Callable[[State, int], z3.BoolRef]
 TransitionConstraintGenerator = Callable[[ModeledBlock], z3.BoolRef]
 
+class ChainIterator(Iterator[DecisionResultType]):
+    def __iter__(self) -> ChainIterator: ...
+    def __next__(self) -> DecisionResultType: ...
+
 class SynthesisParams:
     def run(self) -> DecisionResultType: ...
+    def chains(self, limit: int, min_distance: int = 1) -> ChainIterator: ...
+    def on_progress(self, fn: Callable[[SynthesisProgress], None]) -> None: ...
     def add_precondition(self, fn: StateConstraintGenerator) -> None: ...
     def add_postcondition(self, fn: StateConstraintGenerator) -> None: ...
     def add_transition_constraint(self, fn: TransitionConstraintGenerator) -> None: ...
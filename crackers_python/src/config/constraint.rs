@@ -11,6 +11,7 @@ pub struct PythonConstraintConfig {
     pub precondition: Py<PythonStateEqualityConstraint>,
     pub postcondition: Py<PythonStateEqualityConstraint>,
     pub pointer: Py<PythonPointerRangeConstraints>,
+    pub bad_bytes: Option<Vec<u8>>,
 }
 
 impl TryFrom<ConstraintConfig> for PythonConstraintConfig {
@@ -34,6 +35,7 @@ impl TryFrom<ConstraintConfig> for PythonConstraintConfig {
                 precondition: Py::new(py, precondition)?,
                 postcondition: Py::new(py, postcondition)?,
                 pointer: Py::new(py, pointer)?,
+                bad_bytes: value.bad_bytes,
             })
         })
     }
@@ -51,6 +53,7 @@ impl TryFrom<PythonConstraintConfig> for ConstraintConfig {
                 precondition,
                 postcondition,
                 pointer,
+                bad_bytes: value.bad_bytes,
             })
         })
     }
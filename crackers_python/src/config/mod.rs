@@ -92,6 +92,10 @@ impl PythonCrackersConfig {
     pub fn resolve_config(&self) -> PyResult<PythonSynthesisParams> {
         let cfg = CrackersConfig::try_from(self)?;
         let syn = cfg.resolve()?;
-        Ok(PythonSynthesisParams { inner: syn })
+        Ok(PythonSynthesisParams {
+            inner: syn,
+            progress_callback: None,
+            postcondition: cfg.constraint.and_then(|c| c.postcondition),
+        })
     }
 }
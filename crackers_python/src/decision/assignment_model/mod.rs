@@ -1,7 +1,15 @@
 mod model_varnode_iterator;
 
 use crate::decision::assignment_model::model_varnode_iterator::ModelVarNodeIterator;
+use crackers::config::constraint::StateEqualityConstraint;
+use crackers::synthesis::SynthesisProgress;
 use crackers::synthesis::assignment_model::AssignmentModel;
+use crackers::synthesis::assignment_model::builder::AssignmentModelBuilder;
+use crackers::synthesis::assignment_model::payload::{
+    DEFAULT_MAX_PAYLOAD_LENGTH, Payload, PayloadBuilder,
+};
+use crackers::synthesis::assignment_model::pwntools::PwntoolsExporter;
+use crackers::synthesis::validation::ConcreteValidator;
 use jingle::display::{JingleDisplay, JingleDisplayable};
 use jingle::modeling::{ModeledBlock, ModelingContext, State};
 use jingle::python::modeled_block::PythonModeledBlock;
@@ -19,6 +27,10 @@ use z3::ast::BV;
 #[derive(Clone)]
 pub struct PythonAssignmentModel {
     pub inner: Rc<AssignmentModel<ModeledBlock>>,
+    /// The builder this model was produced from, when it came out of a synthesis run.
+    pub builder: Option<Rc<AssignmentModelBuilder>>,
+    /// The configured postcondition of that run, checked by [`validate`](Self::validate).
+    pub postcondition: Option<StateEqualityConstraint>,
 }
 
 impl PythonAssignmentModel {
@@ -66,6 +78,8 @@ impl TryFrom<AssignmentModel<ModeledBlock>> for PythonAssignmentModel {
     fn try_from(value: AssignmentModel<ModeledBlock>) -> Result<Self, Self::Error> {
         Ok(PythonAssignmentModel {
             inner: Rc::new(value),
+            builder: None,
+            postcondition: None,
         })
     }
 }
@@ -160,4 +174,51 @@ impl PythonAssignmentModel {
             .collect();
         Some(ModelVarNodeIterator::new(iter.into_iter()))
     }
+
+    #[pyo3(signature = (stack_pointer, filler = 0, max_length = DEFAULT_MAX_PAYLOAD_LENGTH))]
+    pub fn payload(&self, stack_pointer: &str, filler: u8, max_length: usize) -> PyResult<Payload> {
+        Ok(PayloadBuilder::new(stack_pointer)
+            .with_filler(filler)
+            .with_max_length(max_length)
+            .build(self.inner.as_ref())?)
+    }
+
+    #[pyo3(signature = (stack_pointer, filler = 0, max_length = DEFAULT_MAX_PAYLOAD_LENGTH))]
+    pub fn pwntools_script(
+        &self,
+        stack_pointer: &str,
+        filler: u8,
+        max_length: usize,
+    ) -> PyResult<String> {
+        let payload = PayloadBuilder::new(stack_pointer)
+            .with_filler(filler)
+            .with_max_length(max_length);
+        let script = PwntoolsExporter::new(payload)
+            .with_libraries(
+                self.builder
+                    .as_ref()
+                    .map(|b| b.libraries.as_slice())
+                    .unwrap_or_default(),
+            )
+            .export(self.inner.as_ref())?;
+        Ok(script.to_string())
+    }
+
+    /// Re-executes the chain concretely against its reference program, returning a description
+    /// of the first divergence, or `None` if the chain behaves as modeled. The final state is
+    /// also checked against the configured postcondition, if any.
+    pub fn validate(&self) -> PyResult<Option<String>> {
+        let builder = self.builder.as_ref().ok_or(PyRuntimeError::new_err(
+            "Validation requires a model produced by a synthesis run",
+        ))?;
+        let divergence = ConcreteValidator::new(builder)?
+            .with_postcondition(self.postcondition.clone())
+            .validate(self.inner.as_ref())?;
+        Ok(divergence.map(|d| d.to_string()))
+    }
+
+    /// Statistics of the search that found this chain, if it came out of a synthesis run.
+    pub fn statistics(&self) -> Option<SynthesisProgress> {
+        self.builder.as_ref().map(|b| b.statistics.clone())
+    }
 }
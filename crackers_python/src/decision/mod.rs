@@ -1,4 +1,5 @@
 use crate::decision::assignment_model::PythonAssignmentModel;
+use crackers::synthesis::SynthesisProgress;
 use crackers::synthesis::selection_strategy::SelectionFailure;
 use pyo3::pyclass;
 
@@ -8,4 +9,5 @@ pub mod assignment_model;
 pub enum PythonDecisionResult {
     AssignmentFound(PythonAssignmentModel),
     Unsat(SelectionFailure),
+    Timeout(SynthesisProgress),
 }
@@ -6,7 +6,7 @@ mod synthesis;
 use crate::config::PythonCrackersConfig;
 use crate::decision::PythonDecisionResult;
 use crate::decision::assignment_model::PythonAssignmentModel;
-use crate::synthesis::PythonSynthesisParams;
+use crate::synthesis::{PythonChainIterator, PythonSynthesisParams};
 use ::crackers::config::constraint::{
     ConstraintConfig, MemoryEqualityConstraint, PointerRange, PointerRangeConstraints,
     StateEqualityConstraint,
@@ -16,7 +16,12 @@ use ::crackers::config::sleigh::SleighConfig;
 use ::crackers::config::specification::SpecificationConfig;
 use ::crackers::config::synthesis::SynthesisConfig;
 use ::crackers::gadget::library::builder::GadgetLibraryConfig;
+use ::crackers::synthesis::SynthesisProgress;
+use ::crackers::synthesis::assignment_model::payload::{ControlledLocation, Payload};
 use ::crackers::synthesis::builder::SynthesisSelectionStrategy;
+use ::crackers::synthesis::progress::StageCounts;
+use ::crackers::synthesis::selection_strategy::objective::{ObjectiveKind, OptimizationObjective};
+use ::crackers::synthesis::selection_strategy::portfolio::PortfolioMember;
 use ::jingle::python::instruction::PythonInstruction;
 use ::jingle::python::modeled_block::PythonModeledBlock;
 use ::jingle::python::modeled_instruction::PythonModeledInstruction;
@@ -45,7 +50,12 @@ fn crackers(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PythonCrackersConfig>()?;
     m.add_class::<PythonDecisionResult>()?;
     m.add_class::<PythonSynthesisParams>()?;
+    m.add_class::<PythonChainIterator>()?;
     m.add_class::<PythonAssignmentModel>()?;
+    m.add_class::<SynthesisProgress>()?;
+    m.add_class::<StageCounts>()?;
+    m.add_class::<Payload>()?;
+    m.add_class::<ControlledLocation>()?;
     m.add_class::<MetaConfig>()?;
     m.add_class::<SpecificationConfig>()?;
     m.add_class::<SleighConfig>()?;
@@ -53,6 +63,9 @@ fn crackers(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<SynthesisConfig>()?;
     m.add_class::<CrackersLogLevel>()?;
     m.add_class::<SynthesisSelectionStrategy>()?;
+    m.add_class::<ObjectiveKind>()?;
+    m.add_class::<OptimizationObjective>()?;
+    m.add_class::<PortfolioMember>()?;
     m.add_class::<PointerRange>()?;
     m.add_class::<MemoryEqualityConstraint>()?;
     m.add_class::<PointerRangeConstraints>()?;
@@ -1,18 +1,26 @@
 use crate::decision::PythonDecisionResult;
 use crate::decision::assignment_model::PythonAssignmentModel;
 use crate::python_logger_layer::PythonLoggerLayer;
+use crackers::config::constraint::StateEqualityConstraint;
 use crackers::error::CrackersError;
-use crackers::synthesis::DecisionResult;
 use crackers::synthesis::builder::{
     StateConstraintGenerator, SynthesisParams, TransitionConstraintGenerator,
 };
+use crackers::synthesis::cancellation::CancellationToken;
+use crackers::synthesis::progress::ProgressCallback;
+use crackers::synthesis::{DecisionResult, SynthesisProgress};
 use jingle::modeling::{ModeledBlock, State};
 use jingle::python::modeled_block::PythonModeledBlock;
 use jingle::python::state::PythonState;
 use jingle::python::z3::ast::PythonAst;
 use lazy_static::lazy_static;
-use pyo3::{Py, PyAny, PyResult, Python, pyclass, pymethods};
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::{Py, PyAny, PyRef, PyResult, Python, pyclass, pymethods};
+use std::rc::Rc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
 use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tracing::{Level, event};
 use tracing_subscriber::Registry;
 use tracing_subscriber::prelude::*;
 use z3::ast::Bool;
@@ -21,35 +29,86 @@ lazy_static! {
     static ref MUTEX: Arc<Mutex<()>> = Arc::new(Mutex::new(()));
 }
 
+/// How often a running synthesis checks for a pending `KeyboardInterrupt`.
+const SIGNAL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
 #[pyclass(name = "SynthesisParams")]
 #[derive(Clone)]
 pub struct PythonSynthesisParams {
     pub inner: SynthesisParams,
+    /// Called with the running statistics of each synthesis run.
+    pub progress_callback: Option<Arc<Py<PyAny>>>,
+    /// The configured postcondition, which found chains are validated against.
+    pub postcondition: Option<StateEqualityConstraint>,
 }
 
 #[pymethods]
 impl PythonSynthesisParams {
-    pub fn run(&self) -> PyResult<PythonDecisionResult> {
+    pub fn run(&self, py: Python<'_>) -> PyResult<PythonDecisionResult> {
         // Register tracing to Python logging
         let subscriber = Registry::default().with(PythonLoggerLayer);
         let _ = tracing::subscriber::set_global_default(subscriber);
 
-        let res = Python::attach(|py| {
-            py.detach(|| match self.inner.combine_instructions {
-                false => self.inner.build_single()?.decide(),
-                true => self.inner.build_combined()?.decide(),
-            })
-        })?;
-        match res {
-            DecisionResult::AssignmentFound(a) => {
-                let a = a.build()?;
-                let a = PythonAssignmentModel::try_from(a)?;
-                Ok(PythonDecisionResult::AssignmentFound(a))
+        // synthesis runs on its own thread so that this one can notice a KeyboardInterrupt
+        let (params, token) = self.cancellable();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let res = match params.combine_instructions {
+                false => params.build_single().and_then(|mut s| s.decide()),
+                true => params.build_combined().and_then(|mut c| c.decide()),
+            };
+            let _ = sender.send(res);
+        });
+        match wait(py, &Mutex::new(receiver), &token)? {
+            Some(res) => to_python(res?, &self.postcondition),
+            None => Err(PyRuntimeError::new_err(
+                "Synthesis thread exited without a result",
+            )),
+        }
+    }
+
+    /// Searches for up to `limit` distinct chains, each using a different gadget than all
+    /// previous ones in at least `min_distance` slots. The search runs in the background and
+    /// results are yielded as they are found.
+    #[pyo3(signature = (limit, min_distance=1))]
+    pub fn chains(&self, limit: usize, min_distance: usize) -> PythonChainIterator {
+        let subscriber = Registry::default().with(PythonLoggerLayer);
+        let _ = tracing::subscriber::set_global_default(subscriber);
+
+        // a rendezvous channel, so that the search stays at most one chain ahead of the caller
+        let (sender, receiver) = std::sync::mpsc::sync_channel(0);
+        let (params, token) = self.cancellable();
+        std::thread::spawn(move || {
+            let chains: Box<dyn Iterator<Item = _>> = match params.combine_instructions {
+                false => match params.build_single() {
+                    Ok(s) => Box::new(s.chains(limit, min_distance)),
+                    Err(e) => Box::new(std::iter::once(Err(e))),
+                },
+                true => match params.build_combined() {
+                    Ok(c) => Box::new(c.chains(limit, min_distance)),
+                    Err(e) => Box::new(std::iter::once(Err(e))),
+                },
+            };
+            for result in chains {
+                if sender.send(result).is_err() {
+                    // the iterator was dropped, which also cancelled the search
+                    break;
+                }
             }
-            DecisionResult::Unsat(u) => Ok(PythonDecisionResult::Unsat(u)),
+        });
+        PythonChainIterator {
+            receiver: Mutex::new(receiver),
+            token,
+            postcondition: self.postcondition.clone(),
         }
     }
 
+    /// Calls `obj` with the running [`SynthesisProgress`] of a synthesis whenever it makes
+    /// progress.
+    pub fn on_progress(&mut self, obj: Py<PyAny>) {
+        self.progress_callback = Some(Arc::new(obj));
+    }
+
     pub fn add_precondition(&mut self, obj: Py<PyAny>) {
         let closure: Arc<PythonStateConstraintGenerator> = Arc::new(move |s, a| {
             let g = MUTEX.lock().unwrap();
@@ -104,6 +163,102 @@ impl PythonSynthesisParams {
     }
 }
 
+impl PythonSynthesisParams {
+    /// A copy of these params for a single run, with a fresh cancellation token (so that
+    /// interrupting one run does not cancel later ones) and fresh progress statistics.
+    fn cancellable(&self) -> (SynthesisParams, CancellationToken) {
+        let mut params = self.inner.clone();
+        params.cancellation = CancellationToken::new();
+        if let Some(obj) = &self.progress_callback {
+            let obj = obj.clone();
+            let stats = Mutex::new(SynthesisProgress::default());
+            let closure: Arc<ProgressCallback> = Arc::new(move |event| {
+                let mut stats = stats.lock().unwrap();
+                stats.record(event);
+                let g = MUTEX.lock().unwrap();
+                Python::attach(|py| {
+                    if let Err(e) = obj.call1(py, (stats.clone(),)) {
+                        event!(Level::WARN, "Progress callback failed: {}", e);
+                    }
+                });
+                drop(g);
+            });
+            params.progress_callback = Some(closure);
+        }
+        let token = params.cancellation.clone();
+        (params, token)
+    }
+}
+
+/// Waits for the next message from a synthesis thread, returning `None` once it has finished.
+/// If a signal handler raises meanwhile (e.g. `KeyboardInterrupt`), the synthesis is cancelled
+/// and the exception is returned once it has stopped.
+fn wait<T: Send>(
+    py: Python<'_>,
+    receiver: &Mutex<Receiver<T>>,
+    token: &CancellationToken,
+) -> PyResult<Option<T>> {
+    loop {
+        match py.detach(|| receiver.lock().unwrap().recv_timeout(SIGNAL_POLL_INTERVAL)) {
+            Ok(t) => return Ok(Some(t)),
+            Err(RecvTimeoutError::Disconnected) => return Ok(None),
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+        if let Err(e) = py.check_signals() {
+            token.cancel();
+            py.detach(|| while receiver.lock().unwrap().recv().is_ok() {});
+            return Err(e);
+        }
+    }
+}
+
+fn to_python(
+    res: DecisionResult,
+    postcondition: &Option<StateEqualityConstraint>,
+) -> PyResult<PythonDecisionResult> {
+    match res {
+        DecisionResult::AssignmentFound(a) => {
+            let model = a.build()?;
+            let mut model = PythonAssignmentModel::try_from(model)?;
+            model.builder = Some(Rc::new(a));
+            model.postcondition = postcondition.clone();
+            Ok(PythonDecisionResult::AssignmentFound(model))
+        }
+        DecisionResult::Unsat(u) => Ok(PythonDecisionResult::Unsat(u)),
+        DecisionResult::Timeout(p) => Ok(PythonDecisionResult::Timeout(p)),
+    }
+}
+
+/// Yields the results of [`PythonSynthesisParams::chains`].
+#[pyclass(name = "ChainIterator")]
+pub struct PythonChainIterator {
+    receiver: Mutex<Receiver<Result<DecisionResult, CrackersError>>>,
+    token: CancellationToken,
+    postcondition: Option<StateEqualityConstraint>,
+}
+
+#[pymethods]
+impl PythonChainIterator {
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&self, py: Python<'_>) -> PyResult<Option<PythonDecisionResult>> {
+        match wait(py, &self.receiver, &self.token)? {
+            Some(res) => Ok(Some(to_python(res?, &self.postcondition)?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Drop for PythonChainIterator {
+    /// Stops the background search once the iterator is no longer used, rather than letting it
+    /// run until it next finds a chain.
+    fn drop(&mut self) {
+        self.token.cancel();
+    }
+}
+
 pub type PythonStateConstraintGenerator =
     dyn Fn(&State, u64) -> Result<Bool, CrackersError> + Send + Sync + 'static;
 